@@ -0,0 +1,54 @@
+//! Smoke test for the API's `/ws` endpoint: sends one prompt, prints the
+//! reply as it streams in, and fails unless at least one delta arrives
+//! before the `done` frame.
+//!
+//! ```text
+//! cargo run --example ws_client -- ws://127.0.0.1:8080/ws "Tell me a crab joke"
+//! ```
+//!
+//! Set `RUSTYCLAW_API_TOKEN` when the server has `api.token` set.
+
+use anyhow::{anyhow, bail, Result};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::io::Write;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let url = args.next().unwrap_or_else(|| "ws://127.0.0.1:8080/ws".to_string());
+    let prompt = args.next().unwrap_or_else(|| "Say hello in five words.".to_string());
+
+    let mut request = url.as_str().into_client_request()?;
+    if let Ok(token) = std::env::var("RUSTYCLAW_API_TOKEN") {
+        request.headers_mut().insert("Authorization", format!("Bearer {}", token).parse()?);
+    }
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await?;
+    socket.send(Message::text(json!({ "message": prompt }).to_string())).await?;
+
+    let mut deltas = 0;
+    while let Some(frame) = socket.next().await {
+        let Message::Text(text) = frame? else {
+            continue;
+        };
+        let frame: Value = serde_json::from_str(&text)?;
+        match frame["type"].as_str() {
+            Some("delta") => {
+                deltas += 1;
+                print!("{}", frame["content"].as_str().unwrap_or_default());
+                std::io::stdout().flush()?;
+            }
+            Some("done") => {
+                println!("\n\n{} deltas; final reply: {}", deltas, frame["response"]);
+                if deltas == 0 {
+                    bail!("The reply finished without streaming any deltas");
+                }
+                return Ok(());
+            }
+            _ => bail!("Server error: {}", frame["error"]),
+        }
+    }
+    Err(anyhow!("The connection closed before the reply finished"))
+}
@@ -0,0 +1,130 @@
+//! Spotting action blocks in a reply that arrives in pieces.
+//!
+//! `BlockDetector` buffers the deltas of a streamed reply and reports each
+//! ```` ```cron ````, ```` ```save: ```` and ```` ```memory ```` block as soon as
+//! its closing fence arrives, using the same parsers as a complete reply.
+
+use super::{Agent, CronJobData, SaveBlock};
+
+/// An action block completed by the latest delta.
+#[derive(Debug, Clone)]
+pub enum ActionBlock {
+    Cron(CronJobData),
+    CronError(String),
+    Save(SaveBlock),
+    Memory(String),
+}
+
+/// Reports action blocks as a reply streams in, even when a fence is split
+/// across deltas:
+///
+/// ```
+/// use rustyclaw::agent::{ActionBlock, BlockDetector};
+///
+/// let mut detector = BlockDetector::new();
+/// assert!(detector.push("Noted! ```mem").is_empty());
+/// assert!(detector.push("ory\nLikes green tea\n``").is_empty());
+/// let blocks = detector.push("`\nAnd here it is: ```save:tea.txt\ngreen\n");
+/// assert!(matches!(&blocks[..], [ActionBlock::Memory(fact)] if fact == "Likes green tea"));
+/// let blocks = detector.push("```\nDone.");
+/// assert!(matches!(&blocks[..], [ActionBlock::Save(block)] if block.filename == "tea.txt"));
+///
+/// // One character at a time finds the same blocks, and skips the one
+/// // inside the model's reasoning.
+/// let reply = "Sure. <think>```memory\nNot this\n```</think> ```memory\nLikes tea\n  ```";
+/// let mut detector = BlockDetector::new().with_thinking_tags(vec!["think".to_string()]);
+/// let blocks: Vec<ActionBlock> = reply.chars().flat_map(|c| detector.push(&c.to_string())).collect();
+/// assert!(matches!(&blocks[..], [ActionBlock::Memory(fact)] if fact == "Likes tea"));
+/// assert_eq!(detector.text(), reply);
+/// ```
+#[derive(Debug, Default)]
+pub struct BlockDetector {
+    buffer: String,
+    /// Everything before this offset has been reported or is plain text.
+    scanned: usize,
+    /// Blocks inside these reasoning tags are not reported.
+    thinking_tags: Vec<String>,
+}
+
+impl BlockDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip blocks the model writes inside `tags`, as `Agent::strip_thinking_tags`
+    /// would remove them.
+    pub fn with_thinking_tags(mut self, tags: Vec<String>) -> Self {
+        self.thinking_tags = tags;
+        self
+    }
+
+    /// The reply received so far.
+    pub fn text(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Append the next piece of the reply and return the blocks it completed,
+    /// in order. A fence split across deltas is picked up once it is whole.
+    pub fn push(&mut self, delta: &str) -> Vec<ActionBlock> {
+        self.buffer.push_str(delta);
+        let mut blocks = Vec::new();
+
+        loop {
+            let rest = &self.buffer[self.scanned..];
+            let Some(open) = rest.find("```") else {
+                // Keep a trailing "`" or "``" in view; it may be half a fence.
+                let keep = rest.len() - rest.trim_end_matches('`').len();
+                self.scanned = self.buffer.len() - keep;
+                break;
+            };
+            let start = self.scanned + open;
+
+            // The body starts after the opening line, and the block ends at
+            // the first later line that starts with a fence.
+            let Some(line_end) = self.buffer[start..].find('\n') else {
+                self.scanned = start;
+                break;
+            };
+            let body = start + line_end;
+            let Some(close) = closing_fence(&self.buffer[body..]) else {
+                self.scanned = start;
+                break;
+            };
+            let end = body + close + "```".len();
+
+            if !self.in_reasoning(start, end) {
+                let block = &self.buffer[start..end];
+                let (jobs, errors) = Agent::parse_cron_blocks(block);
+                blocks.extend(jobs.into_iter().map(ActionBlock::Cron));
+                blocks.extend(errors.into_iter().map(ActionBlock::CronError));
+                blocks.extend(Agent::parse_save_blocks(block).into_iter().map(ActionBlock::Save));
+                blocks.extend(Agent::parse_memory_blocks(block).into_iter().map(ActionBlock::Memory));
+            }
+
+            self.scanned = end;
+        }
+
+        blocks
+    }
+
+    /// Whether the block at `start..end` is hidden inside reasoning tags.
+    fn in_reasoning(&self, start: usize, end: usize) -> bool {
+        if self.thinking_tags.is_empty() {
+            return false;
+        }
+        // Stripping hides the block only if it is inside the tags, and
+        // otherwise leaves it at the end of the text.
+        let before = Agent::strip_thinking_tags(&self.buffer[..start], &self.thinking_tags);
+        let through = Agent::strip_thinking_tags(&self.buffer[..end], &self.thinking_tags);
+        through.len() <= before.len() || !through.ends_with(&self.buffer[start..end])
+    }
+}
+
+/// Offset in `body` of the first fence that only whitespace separates from
+/// an earlier newline, matching the parsers' `\n\s*```` ending.
+fn closing_fence(body: &str) -> Option<usize> {
+    body.match_indices("```").map(|(i, _)| i).find(|&i| {
+        let before = &body[..i];
+        before[before.trim_end().len()..].contains('\n')
+    })
+}
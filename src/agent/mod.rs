@@ -1,17 +1,63 @@
 use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
 use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, RwLock, Semaphore, SemaphorePermit};
 use tracing::{debug, info, warn};
 
+use crate::audit::{AuditEntry, AuditLog};
 use crate::config::OllamaConfig;
-use crate::memory::Message;
+use crate::error::{self, RustyClawError};
+use crate::memory::{Memory, Message, SessionSettings};
+use crate::metrics;
+use crate::scheduler::check_schedule;
+use crate::workspace::format_size;
+
+mod blocks;
+
+pub use blocks::{ActionBlock, BlockDetector};
 
 const MEMORY_FILE: &str = "memory.md";
 const MAX_MEMORY_LINES: usize = 100;
+const CONSOLIDATE_PROMPT: &str = "You maintain a list of facts about a user. \
+Merge duplicates, drop facts that a later fact contradicts or makes obsolete, and combine \
+closely related facts. Keep every distinct piece of information. Reply with only the \
+condensed facts, one per line, each starting with \"- \".";
+const EXTRACT_PROMPT: &str = "You maintain a list of facts about a user. \
+Read the conversation below and list any new, lasting facts it reveals about the user \
+(preferences, plans, people, circumstances) that are not already in the known facts. \
+Skip small talk and anything temporary. Reply with only the new facts, one per line, each \
+starting with \"- \", or with NONE if there are none.";
+const SCHEDULE_INSTRUCTIONS: &str = "To schedule a task when the user asks for a reminder \
+or a recurring job, reply with a cron block holding one line of JSON:
+```cron
+{\"schedule\": \"0 9 * * MON\", \"task\": \"Weekly plan\", \"message\": \"Draft my plan for the week\"}
+```
+`schedule` has five fields: minute hour day month weekday. Weekdays are SUN-SAT, or 1-7 \
+starting with Sunday. `message` is the prompt you will \
+be given when it fires. Optional fields: `\"once\": true` to fire only once, `\"chat_id\"` to \
+deliver to a Telegram chat, and `\"output\"` to save each run as a file. No other fields are allowed.";
+const SAVE_INSTRUCTIONS: &str = "To save code or text the user asks for as a file, open the \
+block with save: and the filename, optionally followed by the language and a quoted description:
+```save:hello.py python \"greeting script\"
+print(\"Hello!\")
+```";
+const MEMORY_INSTRUCTIONS: &str = "To remember a lasting fact the user tells you about \
+themselves, reply with a memory block holding the fact on one line:
+```memory
+User is learning Rust
+```
+Only use these blocks when they are called for; they are hidden from the user.";
+/// Heading under which memory.md facts are appended to the system prompt.
+pub const MEMORY_HEADING: &str = "## Personal Memory";
+/// Opens the system message holding the session's pins, which follows the
+/// system prompt.
+pub const PINNED_HEADING: &str = "Pinned by the user; keep these in mind throughout the conversation:";
 
 #[derive(Debug, Serialize)]
 struct ChatRequest {
@@ -19,6 +65,15 @@ struct ChatRequest {
     messages: Vec<ChatMessage>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
+    options: ChatOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatOptions {
+    temperature: f32,
+    num_ctx: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,28 +85,178 @@ struct ChatMessage {
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     message: ChatMessage,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+/// One line of a streamed reply from `/api/chat`.
+#[derive(Debug, Deserialize)]
+struct ChatChunk {
+    message: Option<ChatMessage>,
+    #[serde(default)]
+    done: bool,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct GenerateRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suffix: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    raw: bool,
+    stream: bool,
+    options: ChatOptions,
+}
+
+/// A `/api/generate` request without a prompt, which only loads the model.
+#[derive(Debug, Serialize)]
+struct LoadRequest<'a> {
+    model: &'a str,
+    stream: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenerateResponse {
+    response: String,
+}
+
+/// Settings for one `Agent::generate` call. Unset values fall back to the
+/// Ollama config.
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// System prompt; `None` uses the model's own template default, not
+    /// `soul.md`.
+    pub system: Option<String>,
+    /// Text after the insertion point, for fill-in-the-middle with models
+    /// that support it.
+    pub suffix: Option<String>,
+    /// Send `prompt` exactly as given, skipping the model's prompt template.
+    pub raw: bool,
+    pub temperature: Option<f32>,
+    pub seed: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct PullRequest<'a> {
+    model: &'a str,
+    stream: bool,
+}
+
+/// One line of the progress stream from `/api/pull`.
+#[derive(Debug, Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    status: String,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f32>,
 }
 
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    models: Vec<ModelInfo>,
+}
+
+/// A model available on the Ollama host.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelInfo {
+    pub name: String,
+    /// Size on disk in bytes, when Ollama reports it.
+    #[serde(default)]
+    pub size: Option<u64>,
+}
+
+/// Outcome of `Agent::health_check`.
+#[derive(Debug, Clone)]
+pub enum Health {
+    Ok { latency: Duration, models: Vec<String> },
+    ModelMissing { model: String, models: Vec<String> },
+    Unreachable { host: String, error: String },
+}
+
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 pub struct CronJobData {
     pub schedule: String,
     pub task: String,
     pub message: String,
+    /// Fire at the next matching time only, then cancel.
+    pub once: bool,
+    /// Telegram chat to deliver to instead of the usual destinations.
+    pub chat_id: Option<i64>,
+    /// Save each run's reply to the workspace under this file name template.
+    pub output: Option<String>,
 }
 
+const CRON_FIELDS: [&str; 6] = ["schedule", "task", "message", "once", "chat_id", "output"];
+
 #[derive(Debug, Clone)]
 pub struct SaveBlock {
     pub filename: String,
+    pub language: Option<String>,
+    pub description: Option<String>,
     pub content: String,
 }
 
+/// Produces text for the system prompt at request time, or `None` to add
+/// nothing this time.
+pub type PromptFragment = Arc<dyn Fn() -> Option<String> + Send + Sync>;
+
 pub struct Agent {
-    config: OllamaConfig,
-    base_prompt: String,
+    config: RwLock<OllamaConfig>,
+    base_prompt: RwLock<String>,
     memory_content: Arc<RwLock<String>>,
     system_prompt: Arc<RwLock<String>>,
     client: Client,
     memory_path: PathBuf,
+    in_flight: Arc<AtomicUsize>,
+    /// When the last Ollama request finished, or when the agent was made.
+    last_active: Arc<Mutex<Instant>>,
+    /// One permit per request Ollama may be working on at once
+    /// (`ollama.max_concurrent`).
+    slots: Semaphore,
+    store: Option<Memory>,
+    max_memory_lines: usize,
+    fragments: RwLock<Vec<(String, PromptFragment)>>,
+    audit: Option<AuditLog>,
+    /// Whether the scheduler runs, so cron blocks are worth teaching.
+    scheduling: bool,
+}
+
+/// Counts a request as in flight for as long as it is alive, and marks the
+/// agent active when it ends.
+struct InFlight(Arc<AtomicUsize>, Arc<Mutex<Instant>>);
+
+impl InFlight {
+    fn new(agent: &Agent) -> Self {
+        agent.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self(agent.in_flight.clone(), agent.last_active.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        *self.1.lock().unwrap() = Instant::now();
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl Agent {
@@ -60,18 +265,66 @@ impl Agent {
         let full_prompt = Self::build_full_prompt(&system_prompt, &memory_content);
         
         Self {
-            config,
-            base_prompt: system_prompt,
+            slots: Semaphore::new(config.max_concurrent.max(1)),
+            config: RwLock::new(config),
+            base_prompt: RwLock::new(system_prompt),
             memory_content: Arc::new(RwLock::new(memory_content)),
             system_prompt: Arc::new(RwLock::new(full_prompt)),
             client: Client::builder()
-                .timeout(std::time::Duration::from_secs(120))
+                .timeout(Duration::from_secs(120))
                 .build()
                 .unwrap(),
             memory_path: PathBuf::from(MEMORY_FILE),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            last_active: Arc::new(Mutex::new(Instant::now())),
+            store: None,
+            max_memory_lines: MAX_MEMORY_LINES,
+            fragments: RwLock::new(Vec::new()),
+            audit: None,
+            scheduling: true,
         }
     }
 
+    /// Read and write long-term facts at `path` instead of `./memory.md`.
+    pub fn with_memory_file(mut self, path: PathBuf) -> Self {
+        let memory_content = Self::load_memory(&path);
+        let full_prompt = Self::build_full_prompt(self.base_prompt.get_mut(), &memory_content);
+        self.memory_content = Arc::new(RwLock::new(memory_content));
+        self.system_prompt = Arc::new(RwLock::new(full_prompt));
+        self.memory_path = path;
+        self
+    }
+
+    /// Consolidate memory.md once it grows past `lines` lines.
+    pub fn with_max_memory_lines(mut self, lines: usize) -> Self {
+        self.max_memory_lines = lines;
+        self
+    }
+
+    /// Give the agent a database for embeddings, enabling `ollama.semantic_memory`.
+    pub fn with_store(mut self, memory: Memory) -> Self {
+        self.store = Some(memory);
+        self
+    }
+
+    /// Leave cron blocks out of the action instructions when `enabled` is
+    /// false, because nothing would run the jobs.
+    pub fn with_scheduling(mut self, enabled: bool) -> Self {
+        self.scheduling = enabled;
+        self
+    }
+
+    /// Record every chat exchange, including failed ones, in `audit`.
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// The audit log from `with_audit`, for shutting it down.
+    pub fn audit(&self) -> Option<&AuditLog> {
+        self.audit.as_ref()
+    }
+
     fn load_memory(path: &Path) -> String {
         if path.exists() {
             match std::fs::read_to_string(path) {
@@ -86,13 +339,23 @@ impl Agent {
         String::new()
     }
 
+    /// Individual facts from memory.md, which stores one `- fact` per line.
+    fn memory_facts(memory: &str) -> Vec<String> {
+        memory
+            .lines()
+            .map(|l| l.trim().trim_start_matches("- ").trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.to_string())
+            .collect()
+    }
+
     fn build_full_prompt(base: &str, memory: &str) -> String {
         if memory.is_empty() {
             base.to_string()
         } else {
             format!(
-                "{}\n\n## Personal Memory\nThese are important facts to remember about the user:\n{}",
-                base, memory
+                "{}\n\n{}\nThese are important facts to remember about the user:\n{}",
+                base, MEMORY_HEADING, memory
             )
         }
     }
@@ -100,10 +363,16 @@ impl Agent {
     pub async fn check_memory_size(&self) -> (bool, usize) {
         let content = self.memory_content.read().await;
         let lines = if content.is_empty() { 0 } else { content.lines().count() };
-        (lines > MAX_MEMORY_LINES, lines)
+        (lines > self.max_memory_lines, lines)
+    }
+
+    /// Append `fact` to memory.md unless it's already there. Returns whether
+    /// it was added.
+    pub async fn save_to_memory(&self, fact: &str) -> error::Result<bool> {
+        self.append_fact(fact).await.map_err(RustyClawError::Database)
     }
 
-    pub async fn save_to_memory(&self, fact: &str) -> Result<bool> {
+    async fn append_fact(&self, fact: &str) -> Result<bool> {
         let memory = self.memory_content.read().await;
         if memory.contains(fact.trim()) {
             debug!("Fact already in memory: {}", fact);
@@ -125,23 +394,159 @@ impl Agent {
             write!(file, "{}", fact_line)?;
         }
 
-        let new_memory = Self::load_memory(&self.memory_path);
-        let new_prompt = Self::build_full_prompt(&self.base_prompt, &new_memory);
-        
-        let mut memory = self.memory_content.write().await;
-        *memory = new_memory;
-        drop(memory);
-        
-        let mut prompt = self.system_prompt.write().await;
-        *prompt = new_prompt;
+        self.refresh_memory().await;
+
+        if let Err(e) = self.store_embedding(fact.trim()).await {
+            warn!("Failed to embed memory, it will be retried at chat time: {}", e);
+        }
 
         info!("Saved to memory: {}", fact);
+
+        if self.check_memory_size().await.0 {
+            match self.consolidate_memory().await {
+                Ok((before, after)) => info!("Consolidated memory from {} to {} facts", before, after),
+                Err(e) => warn!("Memory consolidation failed, keeping it as is: {}", e),
+            }
+        }
         Ok(true)
     }
 
-    pub async fn clear_memory(&self) -> Result<bool> {
+    /// Remove `fact` from memory.md and its stored embedding. Returns false
+    /// if memory.md doesn't hold it, for example after consolidation.
+    pub async fn forget_fact(&self, fact: &str) -> error::Result<bool> {
+        self.remove_fact(fact).await.map_err(RustyClawError::Database)
+    }
+
+    async fn remove_fact(&self, fact: &str) -> Result<bool> {
+        let fact = fact.trim();
+        let memory = self.memory_content.read().await.clone();
+        let lines: Vec<&str> = memory.lines().collect();
+        let kept: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|l| l.trim().trim_start_matches("- ").trim() != fact)
+            .collect();
+        if kept.len() == lines.len() {
+            return Ok(false);
+        }
+
+        std::fs::write(&self.memory_path, format!("{}\n", kept.join("\n").trim()))?;
+        self.refresh_memory().await;
+
+        if let Some(store) = &self.store {
+            store.delete_embedding(fact).await?;
+        }
+
+        info!("Forgot memory: {}", fact);
+        Ok(true)
+    }
+
+    /// Re-read memory.md and rebuild the system prompt from it.
+    async fn refresh_memory(&self) {
+        let new_memory = Self::load_memory(&self.memory_path);
+        let new_prompt = Self::build_full_prompt(&self.base_prompt.read().await, &new_memory);
+
+        *self.memory_content.write().await = new_memory;
+        *self.system_prompt.write().await = new_prompt;
+    }
+
+    /// Ask the model to merge and deduplicate memory.md, then rewrite it with
+    /// the result. The previous file is kept as `<file>.bak`. Returns the fact
+    /// counts before and after.
+    pub async fn consolidate_memory(&self) -> error::Result<(usize, usize)> {
+        let facts = Self::memory_facts(&self.memory_content.read().await);
+        let listed: Vec<String> = facts.iter().map(|f| format!("- {}", f)).collect();
+
+        let response = self
+            .send_chat(
+                vec![
+                    ChatMessage {
+                        role: "system".to_string(),
+                        content: CONSOLIDATE_PROMPT.to_string(),
+                    },
+                    ChatMessage {
+                        role: "user".to_string(),
+                        content: listed.join("\n"),
+                    },
+                ],
+                self.config.read().await.clone(),
+            )
+            .await
+            .map_err(RustyClawError::Ollama)?;
+
+        let condensed: Vec<String> = response
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("- ").or_else(|| l.trim().strip_prefix("* ")))
+            .map(|f| format!("- {}", f.trim()))
+            .collect();
+        if condensed.is_empty() {
+            return Err(RustyClawError::Ollama(anyhow!("model returned no facts")));
+        }
+
+        let mut backup = self.memory_path.clone().into_os_string();
+        backup.push(".bak");
+        std::fs::copy(&self.memory_path, &backup)
+            .and_then(|_| std::fs::write(&self.memory_path, format!("{}\n", condensed.join("\n"))))
+            .map_err(|e| RustyClawError::Database(e.into()))?;
+        self.refresh_memory().await;
+
+        Ok((facts.len(), condensed.len()))
+    }
+
+    /// Ask the model which lasting facts about the user `messages` reveal
+    /// that memory.md doesn't already hold. Nothing is saved; pass the result
+    /// to `save_to_memory`.
+    pub async fn extract_facts(&self, messages: &[Message]) -> error::Result<Vec<String>> {
+        let known = Self::memory_facts(&self.memory_content.read().await);
+        let transcript: Vec<String> = messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect();
+        let known_list = if known.is_empty() {
+            "(none)".to_string()
+        } else {
+            known.iter().map(|f| format!("- {}", f)).collect::<Vec<_>>().join("\n")
+        };
+
+        let response = self
+            .send_chat(
+                vec![
+                    ChatMessage {
+                        role: "system".to_string(),
+                        content: EXTRACT_PROMPT.to_string(),
+                    },
+                    ChatMessage {
+                        role: "user".to_string(),
+                        content: format!(
+                            "Known facts:\n{}\n\nConversation:\n{}",
+                            known_list,
+                            transcript.join("\n")
+                        ),
+                    },
+                ],
+                self.config.read().await.clone(),
+            )
+            .await
+            .map_err(RustyClawError::Ollama)?;
+        let response = self.strip_thinking(&response).await;
+
+        let mut facts: Vec<String> = Vec::new();
+        for line in response.lines() {
+            let Some(fact) = line.trim().strip_prefix("- ").or_else(|| line.trim().strip_prefix("* ")) else {
+                continue;
+            };
+            let fact = fact.trim().to_string();
+            let seen = known.iter().chain(&facts).any(|f| f.eq_ignore_ascii_case(&fact));
+            if !fact.is_empty() && !seen {
+                facts.push(fact);
+            }
+        }
+        Ok(facts)
+    }
+
+    pub async fn clear_memory(&self) -> error::Result<bool> {
         if self.memory_path.exists() {
-            std::fs::remove_file(&self.memory_path)?;
+            std::fs::remove_file(&self.memory_path).map_err(|e| RustyClawError::Database(e.into()))?;
         }
         
         {
@@ -150,7 +555,11 @@ impl Agent {
         }
         
         let mut prompt = self.system_prompt.write().await;
-        *prompt = self.base_prompt.clone();
+        *prompt = self.base_prompt.read().await.clone();
+
+        if let Some(store) = &self.store {
+            store.clear_embeddings().await?;
+        }
         
         info!("Memory cleared");
         Ok(true)
@@ -160,15 +569,245 @@ impl Agent {
         self.memory_content.read().await.clone()
     }
 
-    pub async fn warm_up(&self) -> Result<()> {
-        info!("Warming up model: {}", self.config.model);
+    /// Facts in memory.md that contain `query`, ignoring case and runs of
+    /// whitespace. An empty query matches nothing.
+    pub async fn search_memory(&self, query: &str) -> Vec<String> {
+        let normalize = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let query = normalize(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        Self::memory_facts(&self.memory_content().await)
+            .into_iter()
+            .filter(|fact| normalize(fact).contains(&query))
+            .collect()
+    }
+
+    pub async fn ollama_config(&self) -> OllamaConfig {
+        self.config.read().await.clone()
+    }
+
+    /// Use `seed` (or random sampling, for `None`) until the next restart or
+    /// config reload.
+    pub async fn set_seed(&self, seed: Option<i64>) {
+        info!("Sampling seed set to {:?}", seed);
+        self.config.write().await.seed = seed;
+    }
+
+    /// Sample at `temperature` until the next restart or config reload.
+    pub async fn set_temperature(&self, temperature: f32) {
+        info!("Temperature set to {}", temperature);
+        self.config.write().await.temperature = temperature;
+    }
+
+    /// Swap in a new Ollama config and base system prompt without restarting.
+    pub async fn reload(&self, config: OllamaConfig, system_prompt: String) {
+        let memory = self.memory_content.read().await.clone();
+        let full_prompt = Self::build_full_prompt(&system_prompt, &memory);
+
+        info!("Reloading agent: model {} @ {}", config.model, config.host);
+        *self.config.write().await = config;
+        *self.base_prompt.write().await = system_prompt;
+        *self.system_prompt.write().await = full_prompt;
+    }
+
+    /// Wait up to `timeout` for in-flight Ollama requests to finish.
+    /// Returns `false` if some were still running when the time ran out.
+    pub async fn wait_idle(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.in_flight.load(Ordering::SeqCst) > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        true
+    }
+
+    /// How long since the last Ollama request finished, or `None` while one
+    /// is running.
+    pub fn idle_for(&self) -> Option<Duration> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        Some(self.last_active.lock().unwrap().elapsed())
+    }
+
+    /// Ask Ollama to load the model without generating anything, which also
+    /// restarts its `keep_alive` countdown. Doesn't wait for a request slot,
+    /// since a loaded model answers at once.
+    pub async fn ping(&self) -> error::Result<()> {
+        self.load_request().await.map_err(RustyClawError::Ollama)
+    }
+
+    async fn load_request(&self) -> Result<()> {
+        let _in_flight = InFlight::new(self);
+        let config = self.config.read().await.clone();
+        let request = LoadRequest {
+            model: &config.model,
+            stream: false,
+        };
+
+        let response = self.client
+            .post(format!("{}/api/generate", config.host))
+            .json(&request)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Ollama returned error {}: {}", status, text));
+        }
+        Ok(())
+    }
+
+    async fn fetch_tags(&self, timeout: Duration) -> Result<TagsResponse> {
+        let host = self.config.read().await.host.clone();
+        let response = self.client
+            .get(format!("{}/api/tags", host))
+            .timeout(timeout)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Ollama returned error {}", response.status()));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Models pulled on the Ollama host, sorted by name.
+    pub async fn list_models(&self) -> error::Result<Vec<ModelInfo>> {
+        let mut models = self.fetch_tags(HEALTH_TIMEOUT).await.map_err(RustyClawError::Ollama)?.models;
+        models.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(models)
+    }
+
+    /// Check that Ollama answers and has the configured model pulled.
+    pub async fn health_check(&self) -> Health {
+        let config = self.config.read().await.clone();
+        let started = std::time::Instant::now();
+
+        let tags = match self.fetch_tags(HEALTH_TIMEOUT).await {
+            Ok(tags) => tags,
+            Err(e) => {
+                return Health::Unreachable {
+                    host: config.host,
+                    error: e.to_string(),
+                }
+            }
+        };
+        let latency = started.elapsed();
+
+        let models: Vec<String> = tags.models.into_iter().map(|m| m.name).collect();
+        let pulled = models
+            .iter()
+            .any(|m| *m == config.model || *m == format!("{}:latest", config.model));
+
+        if pulled {
+            Health::Ok { latency, models }
+        } else {
+            Health::ModelMissing {
+                model: config.model,
+                models,
+            }
+        }
+    }
+
+    /// Download `model` to the Ollama host, logging progress as it goes.
+    /// Gives up after `ollama.pull_timeout_secs`.
+    pub async fn pull_model(&self, model: &str) -> error::Result<()> {
+        let timeout = Duration::from_secs(self.config.read().await.pull_timeout_secs);
+        self.try_pull(model, timeout).await.map_err(|e| {
+            let timed_out = e.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_timeout());
+            RustyClawError::Ollama(if timed_out {
+                anyhow!("Pulling {} timed out after {}s", model, timeout.as_secs())
+            } else {
+                anyhow!("Failed to pull {}: {}", model, e)
+            })
+        })
+    }
+
+    async fn try_pull(&self, model: &str, timeout: Duration) -> Result<()> {
+        let host = self.config.read().await.host.clone();
+        let mut response = self.client
+            .post(format!("{}/api/pull", host))
+            .json(&PullRequest { model, stream: true })
+            .timeout(timeout)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Ollama returned error {}: {}", status, text));
+        }
+
+        // Log each new stage, and downloads every 10%.
+        let mut stage = String::new();
+        let mut logged_tenths = None;
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if line.trim_ascii().is_empty() {
+                    continue;
+                }
+                let progress: PullProgress = serde_json::from_slice(&line)?;
+                if let Some(error) = progress.error {
+                    return Err(anyhow!(error));
+                }
+                if progress.status == "success" {
+                    info!("Pulled {}", model);
+                    return Ok(());
+                }
+
+                if progress.status != stage {
+                    stage = progress.status;
+                    logged_tenths = None;
+                    info!("Pulling {}: {}", model, stage);
+                }
+                if let (Some(total), Some(completed)) = (progress.total, progress.completed) {
+                    let tenths = (completed * 10).checked_div(total).unwrap_or(0);
+                    if logged_tenths.map_or(tenths > 0, |logged| tenths > logged) {
+                        logged_tenths = Some(tenths);
+                        info!(
+                            "Pulling {}: {} {}% of {}",
+                            model,
+                            stage,
+                            tenths * 10,
+                            format_size(total)
+                        );
+                    }
+                }
+            }
+        }
+
+        Err(anyhow!("Ollama closed the connection before the pull finished"))
+    }
+
+    /// Load the model so the first reply is quick. With `ollama.auto_pull`,
+    /// a model the host doesn't have is pulled first, and a failed pull is
+    /// returned as an error.
+    pub async fn warm_up(&self) -> error::Result<()> {
+        let config = self.config.read().await.clone();
+        if config.auto_pull {
+            if let Health::ModelMissing { model, .. } = self.health_check().await {
+                info!("Model {} is not on {}, pulling it", model, config.host);
+                self.pull_model(&model).await?;
+            }
+        }
+
+        info!("Warming up model: {}", config.model);
         
-        let messages = vec![ChatMessage {
+        let messages = vec![Message {
             role: "user".to_string(),
             content: "hi".to_string(),
+            timestamp: String::new(),
+            model: None,
         }];
 
-        match self.chat_request(&messages).await {
+        match self.chat_request(&messages, None, None).await {
             Ok(_) => info!("Model loaded and ready"),
             Err(e) => warn!("Warm-up failed, continuing anyway: {}", e),
         }
@@ -176,27 +815,327 @@ impl Agent {
         Ok(())
     }
 
-    async fn chat_request(&self, messages: &[ChatMessage]) -> Result<String> {
-        let url = format!("{}/api/chat", self.config.host);
-        
-        let system_prompt = self.system_prompt.read().await.clone();
+    async fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let config = self.config.read().await.clone();
+        let url = format!("{}/api/embeddings", config.host);
+        let request = EmbeddingRequest {
+            model: &config.embedding_model,
+            prompt: text,
+        };
+
+        let response = self.client.post(&url).json(&request).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Ollama embeddings returned error {}: {}", status, text));
+        }
+
+        Ok(response.json::<EmbeddingResponse>().await?.embedding)
+    }
+
+    async fn store_embedding(&self, fact: &str) -> Result<()> {
+        let config = self.config.read().await.clone();
+        let Some(store) = self.store.as_ref().filter(|_| config.semantic_memory) else {
+            return Ok(());
+        };
+
+        let embedding = self.embed(fact).await?;
+        store.save_embedding(fact, &config.embedding_model, &embedding).await?;
+        Ok(())
+    }
+
+    /// System prompt carrying only the `memory_top_k` facts closest to `query`
+    /// after `persona`, or the base prompt. Facts saved before semantic memory
+    /// was enabled are embedded on demand.
+    async fn semantic_prompt(&self, store: &Memory, query: &str, persona: Option<&str>) -> Result<String> {
+        let config = self.config.read().await.clone();
+        let facts = Self::memory_facts(&self.memory_content.read().await);
+        let mut stored = store.get_embeddings(&config.embedding_model).await?;
+
+        for fact in &facts {
+            if !stored.iter().any(|(f, _)| f == fact) {
+                let embedding = self.embed(fact).await?;
+                store.save_embedding(fact, &config.embedding_model, &embedding).await?;
+                stored.push((fact.clone(), embedding));
+            }
+        }
+
+        let query_embedding = self.embed(query).await?;
+        let mut scored: Vec<(f32, &String)> = stored
+            .iter()
+            .filter(|(f, _)| facts.contains(f))
+            .map(|(f, e)| (cosine_similarity(&query_embedding, e), f))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let relevant: Vec<String> = scored
+            .into_iter()
+            .take(config.memory_top_k)
+            .map(|(_, f)| format!("- {}", f))
+            .collect();
+
+        let base = match persona {
+            Some(persona) => persona.to_string(),
+            None => self.base_prompt.read().await.clone(),
+        };
+        Ok(Self::build_full_prompt(&base, &relevant.join("\n")))
+    }
+
+    /// The system prompt with memory, starting with `persona` in place of the
+    /// base prompt if given.
+    async fn system_prompt_for(&self, messages: &[Message], persona: Option<&str>) -> String {
+        let semantic = self.config.read().await.semantic_memory;
+        let query = messages.iter().rev().find(|m| m.role == "user");
+
+        if let (true, Some(store), Some(query)) = (semantic, &self.store, query) {
+            match self.semantic_prompt(store, &query.content, persona).await {
+                Ok(prompt) => return prompt,
+                Err(e) => warn!("Semantic memory lookup failed, using full memory: {}", e),
+            }
+        }
+
+        match persona {
+            Some(persona) => Self::build_full_prompt(persona, &self.memory_content.read().await),
+            None => self.system_prompt.read().await.clone(),
+        }
+    }
+
+    /// The active session's pins as one message, or `None` if it has
+    /// none or the agent has no store. A failed lookup is logged and treated
+    /// as none.
+    async fn pinned(&self) -> Option<String> {
+        let pins = match self.store.as_ref()?.pins().await {
+            Ok(pins) => pins,
+            Err(e) => {
+                warn!("Could not read pinned messages: {}", e);
+                return None;
+            }
+        };
+        if pins.is_empty() {
+            return None;
+        }
+        let pins: Vec<&str> = pins.iter().map(|p| p.content.trim()).collect();
+        Some(format!("{}\n\n{}", PINNED_HEADING, pins.join("\n\n")))
+    }
+
+    /// The active session's overrides, or none when the agent has no store.
+    /// A failed lookup is logged and treated as none.
+    pub async fn session_settings(&self) -> SessionSettings {
+        let Some(store) = &self.store else {
+            return SessionSettings::default();
+        };
+        store.session_settings().await.unwrap_or_else(|e| {
+            warn!("Could not read session settings, using the global config: {}", e);
+            SessionSettings::default()
+        })
+    }
+
+    /// The Ollama config with the active session's model and temperature
+    /// overrides applied: what the next chat request will use.
+    pub async fn effective_config(&self) -> OllamaConfig {
+        let settings = self.session_settings().await;
+        self.config_with(&settings).await
+    }
+
+    async fn config_with(&self, settings: &SessionSettings) -> OllamaConfig {
+        let mut config = self.config.read().await.clone();
+        if let Some(model) = &settings.model {
+            config.model = model.clone();
+        }
+        if let Some(temperature) = settings.temperature {
+            config.temperature = temperature;
+        }
+        config
+    }
+
+    /// Append `fragment`'s output to the system prompt of every request,
+    /// after the base prompt and memory. Fragments are evaluated per request
+    /// in the order they were first added; adding one under an existing
+    /// `name` replaces it in place.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::OllamaConfig;
+    /// use rustyclaw::memory::Message;
+    /// use rustyclaw::Agent;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-fragment-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// std::fs::write(dir.join("memory.md"), "- Likes tea\n").unwrap();
+    /// let agent = Agent::new(OllamaConfig::default(), "You are helpful.".into())
+    ///     .with_memory_file(dir.join("memory.md"));
+    ///
+    /// agent.add_prompt_fragment("clock", || Some("It is noon.".to_string())).await;
+    /// agent.add_prompt_fragment("tools", || None).await;
+    /// agent.add_prompt_fragment("features", || Some("Scheduling is on.".to_string())).await;
+    /// agent.add_prompt_fragment("clock", || Some("It is one o'clock.".to_string())).await;
+    ///
+    /// let message = Message {
+    ///     role: "user".into(),
+    ///     content: "What time is it?".into(),
+    ///     timestamp: String::new(),
+    ///     model: None,
+    /// };
+    /// let preview = agent.context_preview(&[message]).await;
+    /// let (role, system) = &preview[0];
+    /// assert_eq!(role, "system");
+    /// let at = |text: &str| system.find(text).unwrap();
+    /// assert!(at("You are helpful.") < at("Likes tea"));
+    /// assert!(at("Likes tea") < at("It is one o'clock.") && at("It is one o'clock.") < at("Scheduling is on."));
+    /// assert!(!system.contains("It is noon."));
+    ///
+    /// assert!(agent.remove_prompt_fragment("features").await);
+    /// assert!(!agent.context_preview(&[]).await[0].1.contains("Scheduling is on."));
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn add_prompt_fragment<F>(&self, name: &str, fragment: F)
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        let fragment: PromptFragment = Arc::new(fragment);
+        let mut fragments = self.fragments.write().await;
+        match fragments.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = fragment,
+            None => fragments.push((name.to_string(), fragment)),
+        }
+    }
+
+    /// Stop adding the fragment registered as `name`. Returns whether there was one.
+    pub async fn remove_prompt_fragment(&self, name: &str) -> bool {
+        let mut fragments = self.fragments.write().await;
+        let before = fragments.len();
+        fragments.retain(|(n, _)| n != name);
+        fragments.len() != before
+    }
+
+    /// The system prompt, with its variables filled for `user`, followed by
+    /// as much of `messages` as fits in `context_length` (see
+    /// `fit_history`), as sent to Ollama.
+    async fn request_messages(&self, messages: &[Message], persona: Option<&str>, user: Option<&str>) -> Vec<ChatMessage> {
+        let system_prompt = self.system_prompt_for(messages, persona).await;
+        let mut system_prompt = fill_prompt_variables(&system_prompt, user, Local::now());
+        for (_, fragment) in self.fragments.read().await.iter() {
+            if let Some(text) = fragment().filter(|t| !t.trim().is_empty()) {
+                system_prompt = format!("{}\n\n{}", system_prompt.trim_end(), text.trim());
+            }
+        }
+
+        let (context_length, action_instructions) = {
+            let config = self.config.read().await;
+            (config.context_length as usize, config.action_instructions)
+        };
+        if action_instructions {
+            system_prompt = format!("{}\n\n{}", system_prompt.trim_end(), Self::action_instructions(self.scheduling));
+        }
+        let pinned = self.pinned().await;
+        let prompt_tokens = Self::estimate_tokens(&system_prompt) + pinned.as_deref().map_or(0, Self::estimate_tokens);
+        let budget = context_length.saturating_sub(prompt_tokens);
         let mut full_messages = vec![ChatMessage {
             role: "system".to_string(),
             content: system_prompt,
         }];
-        full_messages.extend(messages.iter().cloned());
+        full_messages.extend(pinned.map(|content| ChatMessage {
+            role: "system".to_string(),
+            content,
+        }));
+        full_messages.extend(Self::fit_history(messages, budget).into_iter().map(|m| ChatMessage {
+            role: m.role,
+            content: m.content,
+        }));
+        full_messages
+    }
+
+    /// Send `messages` with the active session's model, temperature, and
+    /// persona, and record the exchange in the audit log. With `deltas`, the
+    /// reply is streamed there as it is generated.
+    async fn chat_request(
+        &self,
+        messages: &[Message],
+        user: Option<&str>,
+        deltas: Option<&mpsc::UnboundedSender<String>>,
+    ) -> Result<String> {
+        let settings = self.session_settings().await;
+        let full_messages = self.request_messages(messages, settings.persona.as_deref(), user).await;
+        let config = self.config_with(&settings).await;
+        let Some(audit) = &self.audit else {
+            return Ok(self.post_chat(full_messages, config, deltas).await?.message.content);
+        };
+
+        let model = config.model.clone();
+        let user = messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        let started = std::time::Instant::now();
+        let result = self.post_chat(full_messages, config, deltas).await;
+        let session = match &self.store {
+            Some(store) => store.active_session().await.unwrap_or_default(),
+            None => String::new(),
+        };
+        let (response, error, prompt_tokens, response_tokens) = match &result {
+            Ok(data) => (Some(data.message.content.clone()), None, data.prompt_eval_count, data.eval_count),
+            Err(e) => (None, Some(e.to_string()), None, None),
+        };
+        audit.record(AuditEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            session,
+            user,
+            model,
+            response,
+            error,
+            prompt_tokens,
+            response_tokens,
+            latency_ms: started.elapsed().as_millis() as u64,
+        });
+        Ok(result?.message.content)
+    }
+
+    /// Send `full_messages` to Ollama as-is with `config` and return the reply.
+    async fn send_chat(&self, full_messages: Vec<ChatMessage>, config: OllamaConfig) -> Result<String> {
+        Ok(self.post_chat(full_messages, config, None).await?.message.content)
+    }
+
+    /// Wait for one of the `ollama.max_concurrent` request slots. It is
+    /// freed when the returned permit is dropped.
+    async fn request_slot(&self) -> Result<SemaphorePermit<'_>> {
+        if self.slots.available_permits() == 0 {
+            debug!("Waiting for a free Ollama request slot");
+        }
+        Ok(self.slots.acquire().await?)
+    }
+
+    async fn post_chat(
+        &self,
+        full_messages: Vec<ChatMessage>,
+        config: OllamaConfig,
+        deltas: Option<&mpsc::UnboundedSender<String>>,
+    ) -> Result<ChatResponse> {
+        let _in_flight = InFlight::new(self);
+        let _slot = self.request_slot().await?;
+        let url = format!("{}/api/chat", config.host);
 
         let request = ChatRequest {
-            model: self.config.model.clone(),
+            model: config.model,
             messages: full_messages,
-            stream: Some(false),
+            stream: Some(deltas.is_some()),
+            options: ChatOptions {
+                temperature: config.temperature,
+                num_ctx: config.context_length,
+                seed: config.seed,
+            },
         };
 
+        let started = std::time::Instant::now();
         let response = self.client
             .post(&url)
             .json(&request)
             .send()
-            .await?;
+            .await;
+        metrics::observe_ollama_latency(started.elapsed());
+        let response = response?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -204,28 +1143,309 @@ impl Agent {
             return Err(anyhow!("Ollama returned error {}: {}", status, text));
         }
 
-        let data: ChatResponse = response.json().await?;
-        Ok(data.message.content)
+        match deltas {
+            Some(deltas) => Self::read_chat_stream(response, deltas).await,
+            None => Ok(response.json().await?),
+        }
     }
 
-    pub async fn chat(&self, messages: &[Message]) -> Result<String> {
-        let chat_messages: Vec<ChatMessage> = messages
-            .iter()
-            .map(|m| ChatMessage {
-                role: m.role.clone(),
-                content: m.content.clone(),
-            })
-            .collect();
+    /// Collect a streamed reply, passing each piece on to `deltas`. Gives up
+    /// once `deltas` is closed, which drops the connection and so stops the
+    /// generation.
+    async fn read_chat_stream(
+        mut response: reqwest::Response,
+        deltas: &mpsc::UnboundedSender<String>,
+    ) -> Result<ChatResponse> {
+        let mut content = String::new();
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if line.trim_ascii().is_empty() {
+                    continue;
+                }
+                let chunk: ChatChunk = serde_json::from_slice(&line)?;
+                if let Some(error) = chunk.error {
+                    return Err(anyhow!(error));
+                }
+                if let Some(delta) = chunk.message.map(|m| m.content).filter(|c| !c.is_empty()) {
+                    content.push_str(&delta);
+                    if deltas.send(delta).is_err() {
+                        return Err(anyhow!("The reply was cancelled"));
+                    }
+                }
+                if chunk.done {
+                    return Ok(ChatResponse {
+                        message: ChatMessage {
+                            role: "assistant".to_string(),
+                            content,
+                        },
+                        prompt_eval_count: chunk.prompt_eval_count,
+                        eval_count: chunk.eval_count,
+                    });
+                }
+            }
+        }
 
-        match self.chat_request(&chat_messages).await {
-            Ok(response) => Ok(response),
-            Err(e) => {
+        Err(anyhow!("Ollama closed the connection before the reply finished"))
+    }
+
+    /// Complete `prompt` with Ollama's `/api/generate`: no system prompt,
+    /// memory, history, or prompt fragments, just the prompt and `options`.
+    pub async fn generate(&self, prompt: &str, options: &GenerateOptions) -> error::Result<String> {
+        self.generate_request(prompt, options).await.map_err(RustyClawError::Ollama)
+    }
+
+    async fn generate_request(&self, prompt: &str, options: &GenerateOptions) -> Result<String> {
+        let _in_flight = InFlight::new(self);
+        let _slot = self.request_slot().await?;
+        let config = self.config.read().await.clone();
+        let url = format!("{}/api/generate", config.host);
+
+        let request = GenerateRequest {
+            model: &config.model,
+            prompt,
+            suffix: options.suffix.as_deref(),
+            system: options.system.as_deref(),
+            raw: options.raw,
+            stream: false,
+            options: ChatOptions {
+                temperature: options.temperature.unwrap_or(config.temperature),
+                num_ctx: config.context_length,
+                seed: options.seed.or(config.seed),
+            },
+        };
+
+        let started = std::time::Instant::now();
+        let response = self.client.post(&url).json(&request).send().await;
+        metrics::observe_ollama_latency(started.elapsed());
+        let response = response?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Ollama returned error {}: {}", status, text));
+        }
+
+        let data: GenerateResponse = response.json().await?;
+        Ok(data.response)
+    }
+
+    /// Like `chat`, without counting or logging failures.
+    pub async fn try_chat(&self, messages: &[Message]) -> error::Result<String> {
+        self.chat_request(messages, None, None).await.map_err(RustyClawError::Ollama)
+    }
+
+    /// `(role, content)` for every message `chat` would send for `messages`,
+    /// starting with the system prompt and its injected memory.
+    pub async fn context_preview(&self, messages: &[Message]) -> Vec<(String, String)> {
+        let persona = self.session_settings().await.persona;
+        self.request_messages(messages, persona.as_deref(), None)
+            .await
+            .into_iter()
+            .map(|m| (m.role, m.content))
+            .collect()
+    }
+
+    /// Rough token count: about four characters per token, which is close
+    /// enough to tell whether a prompt will fit in `context_length`.
+    pub fn estimate_tokens(text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
+    /// The newest part of `messages` that fits in `budget` estimated tokens.
+    /// History is cut from the front a whole exchange at a time (each group
+    /// ends with an assistant reply), so no reply is left without the message
+    /// it answers. The latest exchange is always kept; if it alone is over
+    /// budget, it is reduced to its last user message, and that message is
+    /// cut in the middle as a last resort.
+    ///
+    /// ```
+    /// use rustyclaw::memory::Message;
+    /// use rustyclaw::Agent;
+    ///
+    /// // Each message is 10 tokens unless given other content.
+    /// let message = |role: &str, name: &str| Message {
+    ///     role: role.into(),
+    ///     content: format!("{:<40}", name),
+    ///     timestamp: String::new(),
+    ///     model: None,
+    /// };
+    /// let names = |history: Vec<Message>| history.into_iter().map(|m| m.content.trim().to_string()).collect::<Vec<_>>();
+    ///
+    /// // Odd length, ending on a question: pairs go whole, oldest first.
+    /// let history = [
+    ///     message("user", "q1"), message("assistant", "a1"),
+    ///     message("user", "q2"), message("assistant", "a2"),
+    ///     message("user", "q3"),
+    /// ];
+    /// assert_eq!(names(Agent::fit_history(&history, 50)), ["q1", "a1", "q2", "a2", "q3"]);
+    /// assert_eq!(names(Agent::fit_history(&history, 40)), ["q2", "a2", "q3"]);
+    /// assert_eq!(names(Agent::fit_history(&history, 25)), ["q3"]);
+    ///
+    /// // Odd length, starting with a reply whose question is already gone:
+    /// // it is the first to go, so no reply is sent alone.
+    /// let history = [
+    ///     message("assistant", "a0"),
+    ///     message("user", "q1"), message("assistant", "a1"),
+    ///     message("user", "q2"),
+    /// ];
+    /// assert_eq!(names(Agent::fit_history(&history, 35)), ["q1", "a1", "q2"]);
+    ///
+    /// // A giant latest message is kept, cut in the middle to fit.
+    /// let mut giant = message("user", "");
+    /// giant.content = format!("START{}END", "x".repeat(4000));
+    /// let history = [message("user", "q1"), message("assistant", "a1"), giant];
+    /// let fitted = Agent::fit_history(&history, 100);
+    /// assert_eq!(fitted.len(), 1);
+    /// assert_eq!(fitted[0].role, "user");
+    /// assert!(fitted[0].content.starts_with("START") && fitted[0].content.ends_with("END"));
+    /// assert!(fitted[0].content.contains("characters cut to fit the context window"));
+    /// assert!(Agent::estimate_tokens(&fitted[0].content) <= 100);
+    /// ```
+    pub fn fit_history(messages: &[Message], budget: usize) -> Vec<Message> {
+        let tokens = |ms: &[Message]| ms.iter().map(|m| Self::estimate_tokens(&m.content)).sum::<usize>();
+        if tokens(messages) <= budget {
+            return messages.to_vec();
+        }
+
+        let mut exchanges: Vec<&[Message]> = messages.split_inclusive(|m| m.role == "assistant").collect();
+        // A leading reply whose question was already cut off goes first.
+        let mut start = 0;
+        let mut total = tokens(messages);
+        while start + 1 < exchanges.len() && (total > budget || exchanges[start].iter().all(|m| m.role != "user")) {
+            total -= tokens(exchanges[start]);
+            start += 1;
+        }
+        if total <= budget {
+            return exchanges[start..].concat();
+        }
+
+        let last = exchanges.pop().unwrap_or_default();
+        let Some(mut latest) = last.iter().rev().find(|m| m.role == "user").cloned() else {
+            return last.to_vec();
+        };
+        let max_chars = budget * 4;
+        let chars = latest.content.chars().count();
+        if chars > max_chars {
+            let marker = format!("\n[… {} characters cut to fit the context window …]\n", chars - max_chars);
+            let keep = max_chars.saturating_sub(marker.chars().count());
+            let head: String = latest.content.chars().take(keep / 2).collect();
+            let tail: String = latest.content.chars().skip(chars - (keep - keep / 2)).collect();
+            latest.content = format!("{}{}{}", head, marker, tail);
+        }
+        vec![latest]
+    }
+
+    /// Get the model's reply to `messages`. Failures are counted in metrics
+    /// and logged, then returned so callers can keep them out of the history.
+    pub async fn chat(&self, messages: &[Message]) -> error::Result<String> {
+        self.chat_as(messages, None).await
+    }
+
+    /// Like `chat`, with `{user}` in the system prompt filled with `user`.
+    pub async fn chat_as(&self, messages: &[Message], user: Option<&str>) -> error::Result<String> {
+        self.logged_chat(messages, user, None).await
+    }
+
+    /// Like `chat_as`, also sending each piece of the reply to `deltas` as
+    /// Ollama generates it. Returns the whole reply; closing `deltas` stops
+    /// the generation early with an error.
+    pub async fn chat_stream(
+        &self,
+        messages: &[Message],
+        user: Option<&str>,
+        deltas: mpsc::UnboundedSender<String>,
+    ) -> error::Result<String> {
+        self.logged_chat(messages, user, Some(&deltas)).await
+    }
+
+    async fn logged_chat(
+        &self,
+        messages: &[Message],
+        user: Option<&str>,
+        deltas: Option<&mpsc::UnboundedSender<String>>,
+    ) -> error::Result<String> {
+        self.chat_request(messages, user, deltas)
+            .await
+            .map_err(RustyClawError::Ollama)
+            .inspect_err(|e| {
+                metrics::record_error();
                 warn!("Ollama chat error: {}", e);
-                Ok(format!("Sorry, I had trouble thinking about that. Error: {}", e))
-            }
+            })
+    }
+
+    /// How to write the action blocks the parsers below accept, as added to
+    /// the system prompt while `ollama.action_instructions` is on. Cron
+    /// blocks are left out unless `scheduling`.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::OllamaConfig;
+    /// use rustyclaw::Agent;
+    ///
+    /// let guide = Agent::action_instructions(true);
+    /// assert_eq!(Agent::parse_cron_blocks(&guide).0.len(), 1);
+    /// assert_eq!(Agent::parse_save_blocks(&guide).len(), 1);
+    /// assert_eq!(Agent::parse_memory_blocks(&guide).len(), 1);
+    /// assert!(Agent::parse_cron_blocks(&Agent::action_instructions(false)).0.is_empty());
+    ///
+    /// async fn system_prompt(config: OllamaConfig) -> String {
+    ///     let memory_file = std::env::temp_dir().join("rustyclaw-no-memory.md");
+    ///     let agent = Agent::new(config, "You are helpful.".into()).with_memory_file(memory_file);
+    ///     agent.context_preview(&[]).await.remove(0).1
+    /// }
+    /// assert!(system_prompt(OllamaConfig::default()).await.ends_with(&guide));
+    /// let off = OllamaConfig { action_instructions: false, ..Default::default() };
+    /// assert_eq!(system_prompt(off).await, "You are helpful.");
+    /// # });
+    /// ```
+    pub fn action_instructions(scheduling: bool) -> String {
+        let mut parts = vec!["## Actions"];
+        if scheduling {
+            parts.push(SCHEDULE_INSTRUCTIONS);
         }
+        parts.push(SAVE_INSTRUCTIONS);
+        parts.push(MEMORY_INSTRUCTIONS);
+        parts.join("\n\n")
     }
 
+    /// The jobs in `text`'s ```` ```cron ```` blocks, and a message for each
+    /// block that can't be used.
+    ///
+    /// ````
+    /// use rustyclaw::Agent;
+    ///
+    /// let reply = r#"```cron
+    /// {"schedule": "0 9 * * *", "task": "Report", "message": "Write the report", "output": "report-{date}.md"}
+    /// ```
+    /// ```cron
+    /// {"schedule": "0 9 * * *", "task": "Report", "message": "Write the report", "output": "reports/{date}.md"}
+    /// ```"#;
+    /// let (jobs, errors) = Agent::parse_cron_blocks(reply);
+    /// assert_eq!(jobs.len(), 1);
+    /// assert_eq!(jobs[0].output.as_deref(), Some("report-{date}.md"));
+    /// assert_eq!(errors, [r#"'output' must be a file name without directories, not "reports/{date}.md""#]);
+    ///
+    /// let reply = r#"```cron
+    /// {"schedule": "30 17 * * *", "task": "Call", "message": "Call the dentist", "once": true}
+    /// ```
+    /// ```cron
+    /// {"schedule": "0 9 * * *", "task": "Water", "message": "Water the plants", "repeat": 3, "where": "home"}
+    /// ```
+    /// ```cron
+    /// {"schedule": "0 9 * * *", "task": "Water", "message": "Water the plants", "once": "yes"}
+    /// ```"#;
+    /// let (jobs, errors) = Agent::parse_cron_blocks(reply);
+    /// assert_eq!(jobs.len(), 1);
+    /// assert!(jobs[0].once);
+    /// assert_eq!((jobs[0].task.as_str(), jobs[0].chat_id), ("Call", None));
+    /// assert_eq!(errors, [
+    ///     "Unknown fields in cron block: repeat, where",
+    ///     r#"'once' must be true or false, not "yes""#,
+    /// ]);
+    /// ````
     pub fn parse_cron_blocks(text: &str) -> (Vec<CronJobData>, Vec<String>) {
         let re = Regex::new(r"```cron\s*\n(.*?)\n\s*```").unwrap();
         let mut jobs = Vec::new();
@@ -237,7 +1457,7 @@ impl Agent {
                 Ok(json) => {
                     let missing: Vec<&str> = ["schedule", "task", "message"]
                         .iter()
-                        .filter(|k| !json.get(**k).is_some())
+                        .filter(|k| json.get(**k).is_none())
                         .copied()
                         .collect();
 
@@ -246,14 +1466,49 @@ impl Agent {
                         continue;
                     }
 
+                    let unknown: Vec<&str> = json
+                        .as_object()
+                        .map(|o| o.keys().map(String::as_str).filter(|k| !CRON_FIELDS.contains(k)).collect())
+                        .unwrap_or_default();
+                    if !unknown.is_empty() {
+                        errors.push(format!("Unknown fields in cron block: {}", unknown.join(", ")));
+                        continue;
+                    }
+
+                    let once = match json.get("once") {
+                        None => false,
+                        Some(v) => match v.as_bool() {
+                            Some(once) => once,
+                            None => {
+                                errors.push(format!("'once' must be true or false, not {}", v));
+                                continue;
+                            }
+                        },
+                    };
+                    let chat_id = match json.get("chat_id") {
+                        None => None,
+                        Some(v) => match v.as_i64() {
+                            Some(id) => Some(id),
+                            None => {
+                                errors.push(format!("'chat_id' must be an integer chat id, not {}", v));
+                                continue;
+                            }
+                        },
+                    };
+                    let output = match json.get("output") {
+                        None => None,
+                        Some(v) => match v.as_str().map(str::trim).filter(|f| !f.is_empty() && !f.contains(['/', '\\'])) {
+                            Some(template) => Some(template.to_string()),
+                            None => {
+                                errors.push(format!("'output' must be a file name without directories, not {}", v));
+                                continue;
+                            }
+                        },
+                    };
+
                     let schedule = json["schedule"].as_str().unwrap_or("").to_string();
-                    let parts: Vec<&str> = schedule.split_whitespace().collect();
-                    
-                    if parts.len() != 5 {
-                        errors.push(format!(
-                            "Invalid cron format '{}' - needs 5 fields (minute hour day month weekday)",
-                            schedule
-                        ));
+                    if let Err(e) = check_schedule(&schedule) {
+                        errors.push(e.to_string());
                         continue;
                     }
 
@@ -261,6 +1516,9 @@ impl Agent {
                         schedule,
                         task: json["task"].as_str().unwrap_or("").to_string(),
                         message: json["message"].as_str().unwrap_or("").to_string(),
+                        once,
+                        chat_id,
+                        output,
                     });
                 }
                 Err(_) => {
@@ -273,11 +1531,16 @@ impl Agent {
     }
 
     pub fn parse_save_blocks(text: &str) -> Vec<SaveBlock> {
-        let re = Regex::new(r"```save:(\S+)\s*\n(.*?)\n\s*```").unwrap();
+        let re = Regex::new(r#"```save:(\S+)(?:[ \t]+(\w+))?(?:[ \t]+"([^"\n]*)")?\s*\n(.*?)\n\s*```"#).unwrap();
         re.captures_iter(text)
             .map(|cap| SaveBlock {
                 filename: cap[1].to_string(),
-                content: cap[2].to_string(),
+                language: cap.get(2).map(|m| m.as_str().to_lowercase()),
+                description: cap
+                    .get(3)
+                    .map(|m| m.as_str().trim().to_string())
+                    .filter(|d| !d.is_empty()),
+                content: cap[4].to_string(),
             })
             .collect()
     }
@@ -300,13 +1563,83 @@ impl Agent {
             .collect()
     }
 
+    /// `text` with the configured `thinking_tags` blocks removed.
+    pub async fn strip_thinking(&self, text: &str) -> String {
+        let tags = self.config.read().await.thinking_tags.clone();
+        Self::strip_thinking_tags(text, &tags)
+    }
+
+    /// Remove `<tag>...</tag>` blocks for each of `tags`, including nested
+    /// and multiline ones. An unclosed block hides the rest of the text.
+    /// Tags inside code blocks and inline code are left alone, as is any
+    /// other markup.
+    ///
+    /// ````
+    /// use rustyclaw::Agent;
+    ///
+    /// let tags = vec!["think".to_string(), "reasoning".to_string()];
+    /// let strip = |text: &str| Agent::strip_thinking_tags(text, &tags);
+    ///
+    /// assert_eq!(strip("<think>\nFirst, the user wants...\n\nSo: 4.\n</think>\nIt's 4."), "It's 4.");
+    /// assert_eq!(strip("<think>outer <think>inner</think> still thinking</think>Done."), "Done.");
+    /// assert_eq!(strip("<THINK>a</think>A <reasoning>\nb\n</reasoning>B"), "A B");
+    /// assert_eq!(strip("Answer.<think>never closed\n<b>bold</b>"), "Answer.");
+    ///
+    /// // Code and other markup come through untouched, as does a stray closing tag.
+    /// let code = "Use:\n```html\n<think>x</think>\n```\nor `<think>`.";
+    /// assert_eq!(strip(code), code);
+    /// assert_eq!(strip("<b>Hi</b></think>"), "<b>Hi</b></think>");
+    /// assert_eq!(Agent::strip_thinking_tags("<think>x</think>y", &[]), "<think>x</think>y");
+    /// ````
+    pub fn strip_thinking_tags(text: &str, tags: &[String]) -> String {
+        if tags.is_empty() {
+            return text.to_string();
+        }
+        let names: Vec<String> = tags.iter().map(|t| regex::escape(t.trim())).collect();
+        let re = Regex::new(&format!(r"(?i)```|`[^`\n]*`|<(/?)(?:{})>", names.join("|"))).unwrap();
+
+        let mut result = String::with_capacity(text.len());
+        let mut depth = 0usize;
+        let mut in_fence = false;
+        let mut last = 0;
+        for cap in re.captures_iter(text) {
+            let m = cap.get(0).unwrap();
+            if depth == 0 {
+                result.push_str(&text[last..m.start()]);
+            }
+            last = m.end();
+
+            if m.as_str() == "```" {
+                if depth == 0 {
+                    in_fence = !in_fence;
+                    result.push_str(m.as_str());
+                }
+            } else if in_fence || m.as_str().starts_with('`') {
+                if depth == 0 {
+                    result.push_str(m.as_str());
+                }
+            } else if cap[1].is_empty() {
+                depth += 1;
+            } else if depth > 0 {
+                depth -= 1;
+            } else {
+                result.push_str(m.as_str());
+            }
+        }
+        if depth == 0 {
+            result.push_str(&text[last..]);
+        }
+
+        result.trim().to_string()
+    }
+
     pub fn clean_response(text: &str) -> String {
         let mut result = text.to_string();
         
         let re_cron = Regex::new(r"```cron\s*\n.*?\n\s*```").unwrap();
         result = re_cron.replace_all(&result, "").to_string();
         
-        let re_save = Regex::new(r"```save:\S+\s*\n.*?\n\s*```").unwrap();
+        let re_save = Regex::new(r#"```save:\S+(?:[ \t]+\w+)?(?:[ \t]+"[^"\n]*")?\s*\n.*?\n\s*```"#).unwrap();
         result = re_save.replace_all(&result, "").to_string();
         
         let re_memory = Regex::new(r"```memory\s*\n.*?\n\s*```").unwrap();
@@ -315,3 +1648,55 @@ impl Agent {
         result.trim().to_string()
     }
 }
+
+/// Fill the variables in a system prompt at request time: `{date}` becomes
+/// `2024-05-01`, `{time}` becomes `09:30`, and `{user}` the name of the
+/// person chatting, or "the user" when it isn't known. Anything else in
+/// braces is left alone.
+///
+/// ```
+/// use chrono::{Local, NaiveDate};
+/// use rustyclaw::agent::fill_prompt_variables;
+///
+/// let prompt = fill_prompt_variables("Today is {date}. You are talking to {user}.", Some("Ana"), Local::now());
+/// let date = &prompt["Today is ".len()..prompt.find('.').unwrap()];
+/// assert!(NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok());
+/// assert!(prompt.ends_with("You are talking to Ana."));
+/// assert_eq!(fill_prompt_variables("Reply in {language}.", None, Local::now()), "Reply in {language}.");
+/// ```
+pub fn fill_prompt_variables(prompt: &str, user: Option<&str>, now: DateTime<Local>) -> String {
+    prompt
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H:%M").to_string())
+        .replace("{user}", user.unwrap_or("the user"))
+}
+
+/// Cosine similarity of two vectors; 0.0 if either is empty, zero, or the lengths differ.
+///
+/// ```
+/// use rustyclaw::agent::cosine_similarity;
+///
+/// assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-6);
+/// assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+/// assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+/// assert!((cosine_similarity(&[1.0, 1.0], &[1.0, 0.0]) - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+///
+/// assert_eq!(cosine_similarity(&[], &[]), 0.0);
+/// assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 2.0]), 0.0);
+/// assert_eq!(cosine_similarity(&[1.0, 2.0], &[1.0, 2.0, 3.0]), 0.0);
+/// ```
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::Result;
+use axum::{
+    extract::{
+        ws::{Message as WsMessage, WebSocket, WebSocketUpgrade},
+        Path, Request, State,
+    },
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::info;
+
+use crate::agent::Agent;
+use crate::config::Config;
+use crate::frontend::{Context, Reply};
+use crate::memory::Memory;
+use crate::scheduler::{self, Scheduler};
+use crate::workspace::Workspace;
+
+#[derive(Clone)]
+struct ApiState {
+    config: Arc<Config>,
+    agent: Arc<Agent>,
+    memory: Arc<Memory>,
+    scheduler: Arc<Scheduler>,
+    workspace: Arc<Workspace>,
+}
+
+impl ApiState {
+    fn context(&self) -> Context {
+        Context {
+            agent: self.agent.clone(),
+            memory: self.memory.clone(),
+            scheduler: self.scheduler.clone(),
+            workspace: self.workspace.clone(),
+            max_history: self.config.memory.max_history,
+            confirmations: Default::default(),
+            undo: Default::default(),
+            user: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChatBody {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct JobBody {
+    schedule: String,
+    message: String,
+    task: Option<String>,
+}
+
+pub struct ApiServer {
+    state: ApiState,
+}
+
+impl ApiServer {
+    pub fn new(
+        config: Config,
+        agent: Arc<Agent>,
+        memory: Arc<Memory>,
+        scheduler: Arc<Scheduler>,
+        workspace: Arc<Workspace>,
+    ) -> Self {
+        Self {
+            state: ApiState {
+                config: Arc::new(config),
+                agent,
+                memory,
+                scheduler,
+                workspace,
+            },
+        }
+    }
+
+    fn router(&self) -> Router {
+        Router::new()
+            .route("/chat", post(chat))
+            .route("/ws", get(chat_socket))
+            .route("/jobs", get(list_jobs).post(create_job))
+            .route("/jobs/:id", delete(cancel_job))
+            .route("/workspace", get(list_workspace))
+            .layer(middleware::from_fn_with_state(self.state.clone(), require_token))
+            .with_state(self.state.clone())
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let addr = (self.state.config.api.host.as_str(), self.state.config.api.port);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("🦀 API server listening on http://{}", listener.local_addr()?);
+        self.serve(listener).await
+    }
+
+    /// Serve the API on an already bound `listener`, ignoring `api.host` and
+    /// `api.port`.
+    pub async fn serve(&self, listener: tokio::net::TcpListener) -> Result<()> {
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+}
+
+fn error(status: StatusCode, message: impl std::fmt::Display) -> Response {
+    (status, Json(json!({ "error": message.to_string() }))).into_response()
+}
+
+async fn require_token(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let Some(expected) = state.config.api.token.as_deref().filter(|t| !t.is_empty()) else {
+        return next.run(request).await;
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if provided == Some(expected) {
+        next.run(request).await
+    } else {
+        error(StatusCode::UNAUTHORIZED, "Missing or invalid bearer token")
+    }
+}
+
+async fn chat(State(state): State<ApiState>, Json(body): Json<ChatBody>) -> Response {
+    if body.message.trim().is_empty() {
+        return error(StatusCode::BAD_REQUEST, "message must not be empty");
+    }
+
+    info!("API message received: {}...", body.message.chars().take(80).collect::<String>());
+
+    let reply = state.context().respond(&body.message).await;
+    Json(reply_json(&reply)).into_response()
+}
+
+fn reply_json(reply: &Reply) -> Value {
+    let actions: Vec<String> = reply.actions.iter().map(|a| a.to_string()).collect();
+    json!({
+        "response": reply.text,
+        "actions": actions,
+    })
+}
+
+async fn chat_socket(State(state): State<ApiState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| stream_replies(state, socket))
+}
+
+/// Answer each `{"message": "..."}` text frame on `socket`, one at a time.
+/// The reply is streamed as `{"type": "delta", "content": "..."}` frames,
+/// then a `{"type": "done"}` frame carries `/chat`'s response fields.
+/// Closing the socket mid-reply cancels it.
+async fn stream_replies(state: ApiState, mut socket: WebSocket) {
+    while let Some(Ok(frame)) = socket.recv().await {
+        let WsMessage::Text(text) = frame else {
+            continue;
+        };
+        let body = match serde_json::from_str::<ChatBody>(&text) {
+            Ok(body) if body.message.trim().is_empty() => Err("message must not be empty".to_string()),
+            Ok(body) => Ok(body),
+            Err(e) => Err(format!("Invalid request: {}", e)),
+        };
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => {
+                if send_frame(&mut socket, json!({ "type": "error", "error": e })).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        info!("API WebSocket message received: {}...", body.message.chars().take(80).collect::<String>());
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let context = state.context();
+        let mut task = tokio::spawn(async move { context.respond_streaming(&body.message, tx).await });
+        loop {
+            // Biased so every delta is sent before the reply is finished.
+            tokio::select! {
+                biased;
+                Some(delta) = rx.recv() => {
+                    if send_frame(&mut socket, json!({ "type": "delta", "content": delta })).await.is_err() {
+                        task.abort();
+                        return;
+                    }
+                }
+                reply = &mut task => {
+                    let Ok(reply) = reply else {
+                        return;
+                    };
+                    let mut done = reply_json(&reply);
+                    done["type"] = json!("done");
+                    if send_frame(&mut socket, done).await.is_err() {
+                        return;
+                    }
+                    break;
+                }
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(WsMessage::Text(_))) => {
+                        let busy = json!({ "type": "error", "error": "Still answering the previous message" });
+                        if send_frame(&mut socket, busy).await.is_err() {
+                            task.abort();
+                            return;
+                        }
+                    }
+                    Some(Ok(WsMessage::Close(_)) | Err(_)) | None => {
+                        info!("API WebSocket client disconnected; cancelling the reply");
+                        task.abort();
+                        return;
+                    }
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+}
+
+async fn send_frame(socket: &mut WebSocket, frame: Value) -> Result<(), axum::Error> {
+    socket.send(WsMessage::Text(frame.to_string())).await
+}
+
+async fn list_jobs(State(state): State<ApiState>) -> Response {
+    match state.scheduler.list_jobs().await {
+        Ok(jobs) => {
+            let jobs: Vec<Value> = jobs
+                .into_iter()
+                .map(|j| {
+                    json!({
+                        "id": j.id,
+                        "schedule": j.schedule,
+                        "task": j.task,
+                        "message": j.message,
+                        "output": j.output,
+                    })
+                })
+                .collect();
+            Json(jobs).into_response()
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn create_job(State(state): State<ApiState>, Json(body): Json<JobBody>) -> Response {
+    let task = body.task.unwrap_or_else(|| scheduler::task_label(&body.message));
+
+    match state.scheduler.add_job(&body.schedule, &task, &body.message).await {
+        Ok(id) => (
+            StatusCode::CREATED,
+            Json(json!({
+                "id": id,
+                "schedule": body.schedule,
+                "task": task,
+                "message": body.message,
+            })),
+        )
+            .into_response(),
+        Err(e) => error(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+async fn cancel_job(State(state): State<ApiState>, Path(id): Path<i64>) -> Response {
+    match state.scheduler.cancel_job(id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => error(StatusCode::NOT_FOUND, format!("Job #{} not found", id)),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e),
+    }
+}
+
+async fn list_workspace(State(state): State<ApiState>) -> Response {
+    let files: Vec<Value> = state
+        .workspace
+        .list_files_with_metadata()
+        .await
+        .into_iter()
+        .map(|f| {
+            json!({
+                "name": f.name,
+                "size": f.size,
+                "language": f.language,
+                "description": f.description,
+            })
+        })
+        .collect();
+    Json(files).into_response()
+}
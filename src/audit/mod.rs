@@ -0,0 +1,185 @@
+//! Audit log: one JSON object per exchange with the model, appended to
+//! `audit.path` for operators. It is separate from the tracing logs and the
+//! conversation history, and records failed requests too.
+//!
+//! Entries are handed to a background writer over a channel, so recording
+//! never waits on the disk. When the file reaches `audit.max_bytes` it is
+//! renamed to `<path>.1`, replacing the previous one, and a new file starts.
+//! Call `AuditLog::shutdown` before exiting so queued entries reach the disk.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::config::AuditConfig;
+
+/// One exchange with the model.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// When the reply (or error) arrived, RFC 3339 in UTC.
+    pub timestamp: String,
+    pub session: String,
+    /// The latest user message sent.
+    pub user: String,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Tokens in the prompt and in the reply, as counted by Ollama.
+    pub prompt_tokens: Option<u64>,
+    pub response_tokens: Option<u64>,
+    pub latency_ms: u64,
+}
+
+/// Handle for appending to the audit log. Cheap to clone; clones share the
+/// writer, and `shutdown` on any of them stops it for all.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    tx: Arc<Mutex<Option<mpsc::UnboundedSender<AuditEntry>>>>,
+    writer: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl AuditLog {
+    /// Start the writer task for `config.path`. Must be called inside a
+    /// Tokio runtime.
+    pub fn start(config: &AuditConfig) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let writer = tokio::spawn(write_entries(rx, config.path.clone(), config.max_bytes));
+        Self {
+            tx: Arc::new(Mutex::new(Some(tx))),
+            writer: Arc::new(Mutex::new(Some(writer))),
+        }
+    }
+
+    /// Queue `entry` to be written. Never blocks.
+    pub fn record(&self, entry: AuditEntry) {
+        let tx = self.tx.lock().unwrap_or_else(|e| e.into_inner());
+        if tx.as_ref().is_none_or(|tx| tx.send(entry).is_err()) {
+            warn!("Audit log writer has stopped; entry dropped");
+        }
+    }
+
+    /// Stop taking entries and wait for the writer to write and flush the
+    /// ones already queued. Later calls return at once.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::audit::{AuditEntry, AuditLog};
+    /// use rustyclaw::config::AuditConfig;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-audit-{}", std::process::id()));
+    /// let config = AuditConfig { enabled: true, path: dir.join("audit.jsonl"), ..Default::default() };
+    /// let audit = AuditLog::start(&config);
+    /// let entry = AuditEntry {
+    ///     timestamp: "2024-05-01T09:30:00Z".into(),
+    ///     session: "main".into(),
+    ///     user: "Hi".into(),
+    ///     model: "llama3.2".into(),
+    ///     response: Some("Hello!".into()),
+    ///     error: None,
+    ///     prompt_tokens: Some(12),
+    ///     response_tokens: Some(3),
+    ///     latency_ms: 250,
+    /// };
+    /// for _ in 0..100 {
+    ///     audit.clone().record(entry.clone());
+    /// }
+    /// audit.shutdown().await;
+    /// assert_eq!(std::fs::read_to_string(&config.path).unwrap().lines().count(), 100);
+    ///
+    /// // Nothing more is written once it has stopped.
+    /// audit.record(entry);
+    /// audit.shutdown().await;
+    /// assert_eq!(std::fs::read_to_string(&config.path).unwrap().lines().count(), 100);
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn shutdown(&self) {
+        self.tx.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(writer) = writer {
+            if let Err(e) = writer.await {
+                warn!("Audit log writer failed: {}", e);
+            }
+        }
+    }
+}
+
+async fn open(path: &Path) -> Result<(BufWriter<File>, u64)> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .with_context(|| format!("Failed to open audit log {}", path.display()))?;
+    let size = file.metadata().await?.len();
+    Ok((BufWriter::new(file), size))
+}
+
+/// Write entries as they arrive, flushing once the queue is empty, until
+/// the sender is closed by `AuditLog::shutdown` or dropped.
+async fn write_entries(mut rx: mpsc::UnboundedReceiver<AuditEntry>, path: PathBuf, max_bytes: u64) {
+    let (mut writer, mut size) = match open(&path).await {
+        Ok(opened) => opened,
+        Err(e) => {
+            warn!("Audit log disabled: {:#}", e);
+            return;
+        }
+    };
+
+    while let Some(entry) = rx.recv().await {
+        let mut next = Some(entry);
+        while let Some(entry) = next {
+            let mut line = match serde_json::to_string(&entry) {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!("Failed to encode audit entry: {}", e);
+                    next = rx.try_recv().ok();
+                    continue;
+                }
+            };
+            line.push('\n');
+            if let Err(e) = writer.write_all(line.as_bytes()).await {
+                warn!("Failed to write audit log: {}", e);
+            }
+            size += line.len() as u64;
+
+            if size >= max_bytes {
+                match rotate(&mut writer, &path).await {
+                    Ok(opened) => (writer, size) = opened,
+                    Err(e) => {
+                        // Keep appending to the current file and try again
+                        // after another max_bytes, not on every entry.
+                        warn!("Failed to rotate audit log: {:#}", e);
+                        size = 0;
+                    }
+                }
+            }
+            next = rx.try_recv().ok();
+        }
+        if let Err(e) = writer.flush().await {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+}
+
+/// Move the full log to `<path>.1` and open a fresh one.
+async fn rotate(writer: &mut BufWriter<File>, path: &Path) -> Result<(BufWriter<File>, u64)> {
+    writer.flush().await?;
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    tokio::fs::rename(path, &rotated)
+        .await
+        .with_context(|| format!("Failed to move {} aside", path.display()))?;
+    open(path).await
+}
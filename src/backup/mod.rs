@@ -0,0 +1,244 @@
+//! One-file backups of everything the bot keeps: the database, the facts
+//! file, and the workspace.
+//!
+//! A backup is a gzipped tarball with a fixed layout, so it can be restored
+//! into a config whose paths differ from the one it was taken with:
+//!
+//! ```text
+//! rustyclaw.db     consistent snapshot of memory.database
+//! memory.md        memory.facts_file, if it exists
+//! workspace/...    everything under workspace.path, including .git
+//! ```
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+use crate::config::Config;
+use crate::memory::Memory;
+
+const DATABASE_ENTRY: &str = "rustyclaw.db";
+const FACTS_ENTRY: &str = "memory.md";
+const WORKSPACE_ENTRY: &str = "workspace";
+
+/// What went into (or came out of) a backup.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub facts: bool,
+    pub workspace_files: usize,
+}
+
+/// Write a backup of the state `config` points at to `dest`. The database
+/// is snapshotted with `VACUUM INTO`, so the bot may keep running.
+pub async fn create(config: &Config, dest: &Path) -> Result<Summary> {
+    let memory = Memory::connect(&config.memory).await?;
+    let snapshot = with_suffix(dest, ".db-snapshot");
+    if snapshot.exists() {
+        std::fs::remove_file(&snapshot)?;
+    }
+    memory.backup_to(&snapshot).await?;
+    memory.close().await;
+
+    let result = write_archive(config, dest, &snapshot);
+    std::fs::remove_file(&snapshot).ok();
+    let summary = result?;
+
+    info!("Backed up to {:?}", dest);
+    Ok(summary)
+}
+
+fn write_archive(config: &Config, dest: &Path, snapshot: &Path) -> Result<Summary> {
+    let file = File::create(dest).with_context(|| format!("Failed to create {}", dest.display()))?;
+    let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut summary = Summary::default();
+
+    archive.append_path_with_name(snapshot, DATABASE_ENTRY)?;
+
+    if config.memory.facts_file.is_file() {
+        archive.append_path_with_name(&config.memory.facts_file, FACTS_ENTRY)?;
+        summary.facts = true;
+    }
+
+    if config.workspace.path.is_dir() {
+        archive.append_dir_all(WORKSPACE_ENTRY, &config.workspace.path)?;
+        summary.workspace_files = count_files(&config.workspace.path);
+    }
+
+    archive.into_inner()?.finish()?;
+    Ok(summary)
+}
+
+fn count_files(dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                count_files(&path)
+            } else {
+                1
+            }
+        })
+        .sum()
+}
+
+/// Replace the database, facts file, and workspace `config` points at with
+/// the contents of the backup at `src`. The bot must not be running. The
+/// archive is checked, then unpacked next to each target, and only swapped
+/// in once all of it has been written, so a bad archive leaves the current
+/// state untouched.
+pub fn restore(config: &Config, src: &Path) -> Result<Summary> {
+    let mut has_database = false;
+    for entry in open_archive(src)?.entries()? {
+        let entry = entry?;
+        let path = entry.path()?;
+        let kind = entry.header().entry_type();
+        if !(kind.is_file() || kind.is_dir()) {
+            bail!("{} is not a RustyClaw backup: {} is not a file or directory", src.display(), path.display());
+        }
+        if path == Path::new(DATABASE_ENTRY) && kind.is_file() {
+            has_database = true;
+        } else if !is_known_entry(&path) {
+            bail!("{} is not a RustyClaw backup: unexpected entry {}", src.display(), path.display());
+        }
+    }
+    if !has_database {
+        bail!("{} is not a RustyClaw backup: no {}", src.display(), DATABASE_ENTRY);
+    }
+
+    let staged = Staged::new(config);
+    let summary = staged.unpack(src).inspect_err(|_| staged.discard())?;
+    staged.swap_in(config)?;
+
+    info!("Restored from {:?}", src);
+    Ok(summary)
+}
+
+/// Where `restore` unpacks a backup before swapping it in: beside each
+/// target, so the final renames stay on one filesystem.
+struct Staged {
+    database: PathBuf,
+    facts: PathBuf,
+    /// Holds `workspace/` as laid out in the archive.
+    workspace_root: PathBuf,
+}
+
+impl Staged {
+    fn new(config: &Config) -> Self {
+        Self {
+            database: with_suffix(&config.memory.database, ".restore"),
+            facts: with_suffix(&config.memory.facts_file, ".restore"),
+            workspace_root: with_suffix(&config.workspace.path, ".restore"),
+        }
+    }
+
+    fn workspace(&self) -> PathBuf {
+        self.workspace_root.join(WORKSPACE_ENTRY)
+    }
+
+    fn unpack(&self, src: &Path) -> Result<Summary> {
+        self.discard();
+        std::fs::create_dir_all(self.workspace())?;
+
+        let mut summary = Summary::default();
+        for entry in open_archive(src)?.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let target = if path == Path::new(DATABASE_ENTRY) {
+                &self.database
+            } else if path == Path::new(FACTS_ENTRY) {
+                summary.facts = true;
+                &self.facts
+            } else {
+                if entry.header().entry_type().is_file() {
+                    summary.workspace_files += 1;
+                }
+                // unpack_in refuses anything that would land outside the root.
+                entry
+                    .unpack_in(&self.workspace_root)
+                    .with_context(|| format!("Failed to restore {}", path.display()))?;
+                continue;
+            };
+
+            if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)?;
+            }
+            entry
+                .unpack(target)
+                .with_context(|| format!("Failed to restore {}", path.display()))?;
+        }
+        Ok(summary)
+    }
+
+    /// Move the unpacked files over the current ones.
+    fn swap_in(&self, config: &Config) -> Result<()> {
+        let database = &config.memory.database;
+        for suffix in ["-wal", "-shm"] {
+            let path = with_suffix(database, suffix);
+            if path.exists() {
+                std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        std::fs::rename(&self.database, database)
+            .with_context(|| format!("Failed to replace {}", database.display()))?;
+
+        let facts = &config.memory.facts_file;
+        if self.facts.exists() {
+            std::fs::rename(&self.facts, facts).with_context(|| format!("Failed to replace {}", facts.display()))?;
+        } else if facts.exists() {
+            std::fs::remove_file(facts)?;
+        }
+
+        let workspace = &config.workspace.path;
+        let old = with_suffix(workspace, ".old");
+        if old.exists() {
+            std::fs::remove_dir_all(&old)?;
+        }
+        if workspace.exists() {
+            std::fs::rename(workspace, &old)?;
+        }
+        std::fs::rename(self.workspace(), workspace)
+            .with_context(|| format!("Failed to replace {}", workspace.display()))?;
+        std::fs::remove_dir_all(&self.workspace_root).ok();
+        std::fs::remove_dir_all(&old).ok();
+        Ok(())
+    }
+
+    /// Remove whatever was unpacked.
+    fn discard(&self) {
+        std::fs::remove_file(&self.database).ok();
+        std::fs::remove_file(&self.facts).ok();
+        std::fs::remove_dir_all(&self.workspace_root).ok();
+    }
+}
+
+/// `path` with `suffix` added to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn open_archive(path: &Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(tar::Archive::new(GzDecoder::new(file)))
+}
+
+/// Whether `path` is one of the entries `create` writes, with no `..` or
+/// absolute components that could escape the restore targets.
+fn is_known_entry(path: &Path) -> bool {
+    if path == Path::new(FACTS_ENTRY) {
+        return true;
+    }
+    match path.strip_prefix(WORKSPACE_ENTRY) {
+        Ok(relative) => relative.components().all(|c| matches!(c, Component::Normal(_))),
+        Err(_) => false,
+    }
+}
@@ -1,20 +1,25 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use std::io::{IsTerminal, Read, Write};
 use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 
 use rustyclaw::{
-    agent::Agent,
+    agent::{Agent, GenerateOptions, Health},
+    api::ApiServer,
+    commands::CommandRegistry,
     config::Config,
-    memory::Memory,
-    scheduler::Scheduler,
+    memory::{Memory, Message},
     telegram::TelegramBot,
-    tui::run_tui,
-    workspace::Workspace,
-    VERSION,
+    tui::{restore_terminal, run_tui, save_transcript},
+    RustyClaw, VERSION,
 };
 
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+const PRUNE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
 #[derive(Parser, Debug)]
 #[command(name = "rustyclaw")]
 #[command(author, version, about, long_about = None)]
@@ -24,6 +29,33 @@ struct Args {
 
     #[arg(short, long, value_enum, default_value = "both")]
     mode: Mode,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Ask a single question, print the answer, and exit. Piped stdin is appended to the prompt.
+    Ask {
+        prompt: Option<String>,
+        /// Send the prompt as-is through Ollama's /api/generate, without soul.md or memory.
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Save the database, facts file, and workspace to a .tar.gz archive.
+    Backup { path: PathBuf },
+    /// Replace the database, facts file, and workspace with a backup. Stop the bot first.
+    Restore {
+        path: PathBuf,
+        /// Don't ask for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check the config, database, workspace, and Ollama, and try one chat.
+    Doctor,
+    /// Append messages from a JSON file to the active session's history.
+    Import { path: PathBuf },
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
@@ -31,96 +63,191 @@ enum Mode {
     Telegram,
     Tui,
     Both,
+    Api,
+    #[cfg(feature = "discord")]
+    Discord,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    if matches!(args.mode, Mode::Tui | Mode::Both) {
-        tracing_subscriber::fmt()
-            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-            .with_writer(|| std::fs::File::create("rustyclaw.log").unwrap())
-            .init();
-    } else {
-        tracing_subscriber::fmt::init();
+    match args.command {
+        Some(Command::Ask { prompt, raw }) => return ask(&args.config, prompt, raw).await,
+        Some(Command::Backup { path }) => return backup(&args.config, &path).await,
+        Some(Command::Restore { path, yes }) => return restore(&args.config, &path, yes),
+        Some(Command::Doctor) => return doctor(&args.config).await,
+        Some(Command::Import { path }) => return import(&args.config, &path).await,
+        None => {}
     }
 
+    let config = Config::load(&args.config)?;
+    init_logging(&config, !matches!(args.mode, Mode::Tui | Mode::Both))?;
+
     println!("🦀 RustyClaw v{} | Mode: {:?}", VERSION, args.mode);
     
     if matches!(args.mode, Mode::Tui | Mode::Both) {
-        println!("Logs: rustyclaw.log");
+        println!("Logs: {}", config.logging.directory.display());
     }
     println!();
 
-    let config = Config::load(&args.config)?;
+    if matches!(args.mode, Mode::Telegram | Mode::Both)
+        && (config.telegram.token.is_empty() || config.telegram.token == "YOUR_BOT_TOKEN_HERE")
+    {
+        eprintln!("Error: Telegram mode requires a valid bot token in config.yaml");
+        eprintln!("Set your token or use --mode tui to skip Telegram");
+        std::process::exit(1);
+    }
 
-    if matches!(args.mode, Mode::Telegram | Mode::Both) {
-        if config.telegram.token.is_empty() || config.telegram.token == "YOUR_BOT_TOKEN_HERE" {
-            eprintln!("Error: Telegram mode requires a valid bot token in config.yaml");
-            eprintln!("Set your token or use --mode tui to skip Telegram");
-            std::process::exit(1);
-        }
+    #[cfg(feature = "discord")]
+    if matches!(args.mode, Mode::Discord) && config.discord.token.is_empty() {
+        eprintln!("Error: Discord mode requires discord.token in config.yaml (or RUSTYCLAW_DISCORD_TOKEN)");
+        std::process::exit(1);
     }
 
-    let memory = Arc::new(Memory::connect(&config.memory.database).await?);
-    info!("Database connected: {:?}", config.memory.database);
+    let claw = RustyClaw::builder(config.clone()).build().await?;
+    let memory = claw.memory().clone();
+    let agent = claw.agent().clone();
+    let workspace = claw.workspace().clone();
+    let scheduler = claw.scheduler().clone();
 
-    let agent = Arc::new(Agent::new(config.ollama.clone(), config.system_prompt.clone()));
-    agent.warm_up().await?;
+    if let Some(days) = config.memory.retention_days {
+        let memory = memory.clone();
+        let keep_recent = config.memory.max_history;
+        tokio::spawn(async move {
+            loop {
+                match memory.prune_older_than(days, keep_recent).await {
+                    Ok(0) => {}
+                    Ok(n) => info!("Pruned {} message(s) older than {} days", n, days),
+                    Err(e) => warn!("History pruning failed: {}", e),
+                }
+                tokio::time::sleep(PRUNE_INTERVAL).await;
+            }
+        });
+    }
 
-    let workspace = Arc::new(Workspace::new(config.workspace.path.clone(), memory.as_ref().clone())?);
-    info!("Workspace: {:?}", workspace.path());
+    if config.memory.auto_summary.enabled {
+        tokio::spawn(rustyclaw::summary::run(
+            agent.clone(),
+            memory.clone(),
+            config.memory.auto_summary.clone(),
+            config.memory.max_history,
+        ));
+    }
 
-    let scheduler = Arc::new(Scheduler::new(memory.as_ref().clone()));
-    
-    if config.scheduler.enabled {
-        scheduler.load_jobs().await?;
+    if let Some(secs) = config.ollama.keepwarm_secs.filter(|&secs| secs > 0) {
+        tokio::spawn(rustyclaw::keepwarm::run(agent.clone(), Duration::from_secs(secs)));
     }
 
+    if let Some(port) = config.metrics.port {
+        #[cfg(feature = "metrics")]
+        tokio::spawn(async move {
+            if let Err(e) = rustyclaw::metrics::serve(port).await {
+                warn!("Metrics server stopped: {}", e);
+            }
+        });
+        #[cfg(not(feature = "metrics"))]
+        warn!("metrics.port is set to {} but this build lacks the `metrics` feature", port);
+    }
+
+    let commands = Arc::new(CommandRegistry::with_builtins().with_templates(config.templates.clone()));
+
     match args.mode {
         Mode::Telegram => {
-            let bot = TelegramBot::new(
-                config.clone(),
-                agent,
-                memory.clone(),
-                scheduler.clone(),
-                workspace,
+            let bot = Arc::new(
+                TelegramBot::new(
+                    config.clone(),
+                    agent.clone(),
+                    memory.clone(),
+                    scheduler.clone(),
+                    workspace,
+                )
+                .with_commands(commands),
             );
+            spawn_reload_handler(args.config.clone(), config.clone(), agent.clone(), Some(bot.clone()));
             
-            scheduler.set_send_callback(|msg: String| {
+            scheduler.add_send_callback(|msg: String| {
                 async move {
                     info!("Cron message: {}", msg);
                 }
             }).await;
 
-            bot.run().await?;
+            tokio::select! {
+                result = bot.run() => result?,
+                _ = shutdown_signal() => {}
+            }
+        }
+        #[cfg(feature = "discord")]
+        Mode::Discord => {
+            spawn_reload_handler(args.config.clone(), config.clone(), agent.clone(), None);
+            let context = rustyclaw::frontend::Context {
+                agent: agent.clone(),
+                memory: memory.clone(),
+                scheduler: scheduler.clone(),
+                workspace,
+                max_history: config.memory.max_history,
+                confirmations: Default::default(),
+                undo: Default::default(),
+                user: None,
+            };
+            let bot = rustyclaw::discord::DiscordBot::new(config.clone(), context).with_commands(commands);
+            tokio::select! {
+                result = bot.run() => result?,
+                _ = shutdown_signal() => {}
+            }
         }
         Mode::Tui => {
-            run_tui(config.clone(), agent, memory.clone(), scheduler.clone(), workspace).await?;
+            spawn_reload_handler(args.config.clone(), config.clone(), agent.clone(), None);
+            let started = chrono::Utc::now();
+            tokio::select! {
+                result = run_tui(config.clone(), agent.clone(), memory.clone(), scheduler.clone(), workspace, commands) => result?,
+                _ = shutdown_signal() => {
+                    restore_terminal();
+                    save_transcript(&config, &agent, &memory, started).await;
+                }
+            }
         }
-        Mode::Both => {
-            let bot = Arc::new(TelegramBot::new(
+        Mode::Api => {
+            spawn_reload_handler(args.config.clone(), config.clone(), agent.clone(), None);
+            let server = ApiServer::new(
                 config.clone(),
                 agent.clone(),
                 memory.clone(),
                 scheduler.clone(),
-                workspace.clone(),
-            ));
+                workspace,
+            );
+            tokio::select! {
+                result = server.run() => result?,
+                _ = shutdown_signal() => {}
+            }
+        }
+        Mode::Both => {
+            let bot = Arc::new(
+                TelegramBot::new(
+                    config.clone(),
+                    agent.clone(),
+                    memory.clone(),
+                    scheduler.clone(),
+                    workspace.clone(),
+                )
+                .with_commands(commands.clone()),
+            );
+
+            spawn_reload_handler(args.config.clone(), config.clone(), agent.clone(), Some(bot.clone()));
 
             let bot_clone = bot.clone();
             let agent_clone = agent.clone();
             let memory_clone = memory.clone();
 
-            scheduler.set_send_callback(move |msg: String| {
+            scheduler.add_send_callback(move |msg: String| {
                 let agent = agent_clone.clone();
                 let memory = memory_clone.clone();
                 async move {
                     info!("Cron message: {}", msg);
-                    memory.add_message("user", &msg).await.ok();
+                    memory.add_message("user", &msg, None).await.ok();
                     if let Ok(history) = memory.get_history(50).await {
                         if let Ok(response) = agent.chat(&history).await {
-                            let clean = Agent::clean_response(&response);
+                            let clean = Agent::clean_response(&agent.strip_thinking(&response).await);
                             info!("Cron response: {}", clean);
                         }
                     }
@@ -133,10 +260,13 @@ async fn main() -> Result<()> {
                 }
             });
 
+            let tui_config = config.clone();
+            let tui_agent = agent.clone();
             let tui_memory = memory.clone();
             let tui_scheduler = scheduler.clone();
+            let started = chrono::Utc::now();
             let tui_handle = tokio::spawn(async move {
-                if let Err(e) = run_tui(config.clone(), agent, tui_memory, tui_scheduler, workspace).await {
+                if let Err(e) = run_tui(tui_config, tui_agent, tui_memory, tui_scheduler, workspace, commands).await {
                     eprintln!("TUI error: {}", e);
                 }
             });
@@ -144,13 +274,340 @@ async fn main() -> Result<()> {
             tokio::select! {
                 _ = telegram_handle => {}
                 _ = tui_handle => {}
+                _ = shutdown_signal() => {
+                    restore_terminal();
+                    save_transcript(&config, &agent, &memory, started).await;
+                }
             }
         }
     }
 
-    scheduler.stop();
+    scheduler.stop().await;
+    if !agent.wait_idle(SHUTDOWN_GRACE).await {
+        warn!("Shutting down with an agent request still in flight");
+    }
+    if let Some(audit) = agent.audit() {
+        audit.shutdown().await;
+    }
     memory.close().await;
     info!("Goodbye! 🦀");
 
     Ok(())
 }
+
+/// Daily-rotated log files in `logging.directory`, plus stdout when no TUI
+/// owns the terminal. `RUST_LOG` takes precedence over `logging.level`.
+fn init_logging(config: &Config, stdout: bool) -> Result<()> {
+    use tracing_appender::rolling::{RollingFileAppender, Rotation};
+    use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("rustyclaw")
+        .filename_suffix("log")
+        .max_log_files(config.logging.max_files)
+        .build(&config.logging.directory)
+        .with_context(|| format!("Failed to open log directory: {}", config.logging.directory.display()))?;
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(&config.logging.level));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_ansi(false).with_writer(appender))
+        .with(stdout.then(fmt::layer))
+        .init();
+
+    Ok(())
+}
+
+async fn backup(config_path: &std::path::Path, path: &std::path::Path) -> Result<()> {
+    let config = Config::load(config_path)?;
+    let summary = rustyclaw::backup::create(&config, path).await?;
+    println!(
+        "💾 Backed up the database{}, and {} workspace file(s) to {}",
+        if summary.facts { ", memory" } else { "" },
+        summary.workspace_files,
+        path.display()
+    );
+    Ok(())
+}
+
+async fn import(config_path: &std::path::Path, path: &std::path::Path) -> Result<()> {
+    let config = Config::load(config_path)?;
+    let json = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let memory = Memory::connect(&config.memory).await?;
+    let session = memory.active_session().await?;
+    let summary = memory.import_history(&json).await?;
+    println!("📥 Imported {} message(s) into session '{}'", summary.imported, session);
+    if summary.skipped > 0 {
+        println!("⚠️ Skipped {} malformed entries (unknown role, empty content, or bad timestamp)", summary.skipped);
+    }
+    memory.close().await;
+    Ok(())
+}
+
+fn restore(config_path: &std::path::Path, path: &std::path::Path, yes: bool) -> Result<()> {
+    let config = Config::load(config_path)?;
+    if !path.is_file() {
+        bail!("Backup not found: {}", path.display());
+    }
+
+    if !yes {
+        if !std::io::stdin().is_terminal() {
+            bail!("Refusing to restore without confirmation; pass --yes");
+        }
+        println!("This replaces, with the contents of {}:", path.display());
+        println!("  {}", config.memory.database.display());
+        println!("  {}", config.memory.facts_file.display());
+        println!("  {} (everything in it)", config.workspace.path.display());
+        print!("Make sure RustyClaw is stopped. Continue? [y/N] ");
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Restore cancelled.");
+            return Ok(());
+        }
+    }
+
+    let summary = rustyclaw::backup::restore(&config, path)?;
+    println!(
+        "✅ Restored the database{}, and {} workspace file(s) from {}",
+        if summary.facts { ", memory" } else { "" },
+        summary.workspace_files,
+        path.display()
+    );
+    Ok(())
+}
+
+/// One-shot mode for scripts: no TUI, no Telegram, no history. Logs go to
+/// stderr so stdout carries only the answer.
+async fn ask(config_path: &std::path::Path, prompt: Option<String>, raw: bool) -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    let mut parts: Vec<String> = prompt.into_iter().collect();
+    if !std::io::stdin().is_terminal() {
+        let mut piped = String::new();
+        std::io::stdin().read_to_string(&mut piped)?;
+        if !piped.trim().is_empty() {
+            parts.push(piped);
+        }
+    }
+    if parts.is_empty() {
+        bail!("No prompt given. Usage: rustyclaw ask \"question\" (or pipe text on stdin)");
+    }
+
+    let config = Config::load(config_path)?;
+    if raw {
+        let agent = Agent::new(config.ollama.clone(), String::new());
+        let options = GenerateOptions {
+            raw: true,
+            ..Default::default()
+        };
+        println!("{}", agent.generate(&parts.join("\n\n"), &options).await?);
+        return Ok(());
+    }
+
+    let agent = Agent::new(config.ollama.clone(), config.system_prompt.clone())
+        .with_memory_file(config.memory.facts_file.clone())
+        .with_max_memory_lines(config.memory.max_memory_lines);
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: parts.join("\n\n"),
+        timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        model: None,
+    }];
+
+    let response = agent.try_chat(&messages).await?;
+    println!("{}", Agent::clean_response(&agent.strip_thinking(&response).await));
+
+    Ok(())
+}
+
+/// Run each setup check, print a line per check, and fail if any did. A
+/// broken config doesn't stop the rest: they fall back to the defaults.
+async fn doctor(config_path: &std::path::Path) -> Result<()> {
+    let mut failures = 0;
+    let mut report = |name: &str, result: Result<String>| match result {
+        Ok(detail) => println!("✅ {}: {}", name, detail),
+        Err(e) => {
+            failures += 1;
+            println!("❌ {}: {:#}", name, e);
+        }
+    };
+
+    let config = match Config::load(config_path) {
+        Ok(config) => {
+            report("Config", Ok(format!("{} is valid", config_path.display())));
+            config
+        }
+        Err(e) => {
+            report("Config", Err(e.into()));
+            println!("   (checking the rest with the built-in defaults)");
+            Config::default()
+        }
+    };
+
+    let database = match Memory::connect(&config.memory).await {
+        Ok(memory) => {
+            memory.close().await;
+            Ok(format!("{} opened and migrated", config.memory.database.display()))
+        }
+        Err(e) => Err(e.into()),
+    };
+    report("Database", database);
+
+    let workspace = &config.workspace.path;
+    let probe = workspace.join(".rustyclaw-doctor");
+    let writable = std::fs::create_dir_all(workspace)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map(|_| format!("{} is writable", workspace.display()))
+        .with_context(|| format!("{} is not writable", workspace.display()));
+    report("Workspace", writable);
+
+    let agent = Agent::new(config.ollama.clone(), config.system_prompt.clone())
+        .with_memory_file(config.memory.facts_file.clone());
+    let ollama = match agent.health_check().await {
+        Health::Ok { latency, .. } => Ok(format!(
+            "{} reachable ({} ms), model '{}' is pulled",
+            config.ollama.host,
+            latency.as_millis(),
+            config.ollama.model
+        )),
+        Health::ModelMissing { model, .. } => Err(anyhow::anyhow!(
+            "model '{}' is not pulled; run: ollama pull {}",
+            model,
+            model
+        )),
+        Health::Unreachable { host, error } => Err(anyhow::anyhow!("cannot reach {}: {}", host, error)),
+    };
+    report("Ollama", ollama);
+
+    let started = std::time::Instant::now();
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: "Reply with the single word OK.".to_string(),
+        timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        model: None,
+    }];
+    let chat = match agent.try_chat(&messages).await {
+        Ok(response) if response.trim().is_empty() => Err(anyhow::anyhow!("the model returned an empty reply")),
+        Ok(_) => Ok(format!("the model replied in {:.1} s", started.elapsed().as_secs_f64())),
+        Err(e) => Err(e.into()),
+    };
+    report("Chat", chat);
+
+    if failures > 0 {
+        bail!("{} check(s) failed", failures);
+    }
+    println!("All checks passed.");
+    Ok(())
+}
+
+/// Resolves on SIGINT or SIGTERM so service managers get a clean shutdown.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut term) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = term.recv() => {}
+                }
+            }
+            Err(e) => {
+                warn!("Could not install SIGTERM handler: {}", e);
+                tokio::signal::ctrl_c().await.ok();
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+
+    info!("Shutdown signal received");
+}
+
+/// Re-read the config file on SIGHUP and push hot-swappable settings into the
+/// running agent and Telegram bot. Anything else is logged as needing a restart.
+#[cfg(unix)]
+fn spawn_reload_handler(
+    path: PathBuf,
+    mut current: Config,
+    agent: Arc<Agent>,
+    bot: Option<Arc<TelegramBot>>,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Could not install SIGHUP handler: {}", e);
+                return;
+            }
+        };
+
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading {:?}", path);
+            let new = match Config::load(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    warn!("Config reload failed, keeping current settings: {:#}", e);
+                    continue;
+                }
+            };
+
+            agent.reload(new.ollama.clone(), new.system_prompt.clone()).await;
+            if let Some(bot) = &bot {
+                bot.set_allowed_users(new.telegram.allowed_users.clone()).await;
+            }
+
+            let restart_required = [
+                ("telegram.token", current.telegram.token != new.telegram.token),
+                ("ollama.max_concurrent", current.ollama.max_concurrent != new.ollama.max_concurrent),
+                ("ollama.keepwarm_secs", current.ollama.keepwarm_secs != new.ollama.keepwarm_secs),
+                ("telegram.max_message_bytes", current.telegram.max_message_bytes != new.telegram.max_message_bytes),
+                ("telegram.send_retries", current.telegram.send_retries != new.telegram.send_retries),
+                ("telegram.send_timeout_secs", current.telegram.send_timeout_secs != new.telegram.send_timeout_secs),
+                ("workspace", current.workspace.path != new.workspace.path
+                    || current.workspace.allowed_extensions != new.workspace.allowed_extensions
+                    || current.workspace.allow_hidden != new.workspace.allow_hidden
+                    || current.workspace.git != new.workspace.git
+                    || current.workspace.manifest != new.workspace.manifest),
+                ("scheduler.enabled", current.scheduler.enabled != new.scheduler.enabled),
+                ("scheduler.jitter_secs", current.scheduler.jitter_secs != new.scheduler.jitter_secs),
+                ("memory.database", current.memory.database != new.memory.database),
+                ("memory.pool_size", current.memory.pool_size != new.memory.pool_size),
+                ("memory.busy_timeout_ms", current.memory.busy_timeout_ms != new.memory.busy_timeout_ms),
+                ("memory.max_history", current.memory.max_history != new.memory.max_history),
+                ("memory.dedupe", current.memory.dedupe != new.memory.dedupe),
+                ("templates", current.templates != new.templates),
+                ("command_prefix", current.command_prefix != new.command_prefix),
+                ("audit", current.audit.enabled != new.audit.enabled
+                    || current.audit.path != new.audit.path
+                    || current.audit.max_bytes != new.audit.max_bytes),
+                ("memory.auto_summary", current.memory.auto_summary.enabled != new.memory.auto_summary.enabled
+                    || current.memory.auto_summary.idle_minutes != new.memory.auto_summary.idle_minutes
+                    || current.memory.auto_summary.schedule != new.memory.auto_summary.schedule),
+            ];
+            for (field, changed) in restart_required {
+                if changed {
+                    warn!("Config change to {} requires restart", field);
+                }
+            }
+
+            current = new;
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn spawn_reload_handler(_: PathBuf, _: Config, _: Arc<Agent>, _: Option<Arc<TelegramBot>>) {}
@@ -0,0 +1,888 @@
+//! Slash commands that every frontend understands.
+//!
+//! A `CommandRegistry` maps a command name to a `CommandHandler`. The
+//! built-in informational commands (`/status`, `/jobs`, `/ping`, `/models`,
+//! `/memory`, `/search_memory`, `/forget`, `/context`, `/next`, `/seed`, `/model`, `/temp`,
+//! `/persona`, `/session`, `/pin`, `/unpin`, `/pins`, `/clearworkspace`, `/regenerate`, `/undo`, `/diff`,
+//! `/copy_last_code`, and `/t` for the prompt templates passed to
+//! `with_templates`) are registered through it, and so can your own:
+//!
+//! ```no_run
+//! use rustyclaw::commands::{handler_fn, CommandRegistry};
+//!
+//! let mut commands = CommandRegistry::with_builtins();
+//! commands
+//!     .register(
+//!         "weather",
+//!         handler_fn("Ask about the weather", |args, ctx| async move {
+//!             let place = if args.is_empty() { "here".to_string() } else { args };
+//!             let model = ctx.agent.ollama_config().await.model;
+//!             format!("☀️ No forecast for {} yet, but {} is ready to chat.", place, model)
+//!         }),
+//!     )
+//!     .unwrap();
+//! ```
+//!
+//! Pass the registry to `TelegramBot::with_commands`, `DiscordBot::with_commands`
+//! and `run_tui` before they start; handlers can't be added once a frontend is
+//! running.
+
+use anyhow::{bail, Result};
+use futures::future::BoxFuture;
+use regex::{Captures, Regex};
+use similar::TextDiff;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::agent::{Agent, Health, MEMORY_HEADING, PINNED_HEADING};
+use crate::frontend::{Context, Reply, CONFIRM_WINDOW};
+use crate::memory::SessionSettings;
+use crate::scheduler;
+use crate::workspace::{extension_for, format_size};
+
+const DEFAULT_UPCOMING: usize = 5;
+/// Characters of each pin shown by `/pins`.
+const PIN_PREVIEW_CHARS: usize = 80;
+const MAX_UPCOMING: usize = 20;
+const MAX_SESSION_NAME: usize = 32;
+const SESSION_USAGE: &str = "Usage: /session new <name> | switch <name> | list";
+/// How many recent messages `/copy_last_code` looks through for code.
+const CODE_SEARCH_HISTORY: usize = 10;
+const MODEL_USAGE: &str = "Usage: /model <name> | reset";
+const TEMP_USAGE: &str = "Usage: /temp <0.0-2.0> | reset";
+const PERSONA_USAGE: &str = "Usage: /persona <description> | reset";
+
+/// A slash command. `args` is everything after the command name, trimmed;
+/// the returned string is shown to the user as-is.
+pub trait CommandHandler: Send + Sync {
+    /// One-line description for command menus and `/help`.
+    fn description(&self) -> &str;
+
+    fn handle<'a>(&'a self, args: &'a str, ctx: &'a Context) -> BoxFuture<'a, String>;
+}
+
+/// A `CommandHandler` built from a description and an async closure.
+pub struct FnHandler<F> {
+    description: String,
+    f: F,
+}
+
+/// Wrap an async closure taking `(args, context)` as a command handler.
+pub fn handler_fn<F, Fut>(description: &str, f: F) -> FnHandler<F>
+where
+    F: Fn(String, Context) -> Fut + Send + Sync,
+    Fut: Future<Output = String> + Send + 'static,
+{
+    FnHandler {
+        description: description.to_string(),
+        f,
+    }
+}
+
+impl<F, Fut> CommandHandler for FnHandler<F>
+where
+    F: Fn(String, Context) -> Fut + Send + Sync,
+    Fut: Future<Output = String> + Send + 'static,
+{
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn handle<'a>(&'a self, args: &'a str, ctx: &'a Context) -> BoxFuture<'a, String> {
+        Box::pin((self.f)(args.to_string(), ctx.clone()))
+    }
+}
+
+#[derive(Default)]
+pub struct CommandRegistry {
+    handlers: BTreeMap<String, Arc<dyn CommandHandler>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry holding the built-in commands.
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.insert("status", handler_fn("Show system status", |_, ctx| async move { status(&ctx).await }));
+        registry.insert("jobs", handler_fn("List scheduled tasks", |_, ctx| async move { jobs(&ctx).await }));
+        registry.insert("ping", handler_fn("Check Ollama connectivity", |_, ctx| async move { ping(&ctx).await }));
+        registry.insert("models", handler_fn("List available models", |_, ctx| async move { models(&ctx).await }));
+        registry.insert("memory", handler_fn("View saved memories", |_, ctx| async move { memory(&ctx).await }));
+        registry.insert("search_memory", handler_fn("Find saved memories", |args, ctx| async move {
+            search_memory(&ctx, &args).await
+        }));
+        registry.insert("forget", handler_fn("Clear all memories", |_, ctx| async move { forget(&ctx).await }));
+        registry.insert(
+            "context",
+            handler_fn("Show what the model sees", |args, ctx| async move { context(&ctx, args == "redact").await }),
+        );
+        registry.insert("seed", handler_fn("Set the sampling seed", |args, ctx| async move { seed(&ctx, &args).await }));
+        registry.insert("model", handler_fn("Set this session's model", |args, ctx| async move {
+            model(&ctx, &args).await
+        }));
+        registry.insert("temp", handler_fn("Set this session's sampling temperature", |args, ctx| async move {
+            temp(&ctx, &args).await
+        }));
+        registry.insert("persona", handler_fn("Set this session's persona", |args, ctx| async move {
+            persona(&ctx, &args).await
+        }));
+        registry.insert("session", handler_fn("Start, switch, or list chat sessions", |args, ctx| async move {
+            session(&ctx, &args).await
+        }));
+        registry.insert("pin", handler_fn("Keep the last message, or some text, in every prompt", |args, ctx| async move {
+            pin(&ctx, &args).await
+        }));
+        registry.insert("unpin", handler_fn("Remove a pin", |args, ctx| async move { unpin(&ctx, &args).await }));
+        registry.insert("pins", handler_fn("List this session's pins", |_, ctx| async move { pins(&ctx).await }));
+        registry.insert("clearworkspace", handler_fn("Delete all workspace files", |args, ctx| async move {
+            clear_workspace(&ctx, args == "confirm").await
+        }));
+        registry.insert("regenerate", handler_fn("Retry the last reply", |_, ctx| async move {
+            regenerate(&ctx).await
+        }));
+        registry.insert("undo", handler_fn("Undo the last reply's saves, jobs, and memories", |_, ctx| async move {
+            ctx.undo().await
+        }));
+        registry.insert("diff", handler_fn("Compare the last two versions of a file", |args, ctx| async move {
+            diff(&ctx, &args)
+        }));
+        registry.insert("copy_last_code", handler_fn("Save the last code block under a generated name", |_, ctx| async move {
+            copy_last_code(&ctx).await
+        }));
+        registry.insert("next", handler_fn("Preview when a cron schedule fires", |args, _| async move { next(&args) }));
+        registry
+    }
+
+    /// Add `/t <name> <args>`, which fills in one of `templates` (name →
+    /// text with `{placeholder}`s, see `expand_template`) and sends it as a
+    /// user message. Does nothing if `templates` is empty.
+    pub fn with_templates(mut self, templates: BTreeMap<String, String>) -> Self {
+        if !templates.is_empty() {
+            let templates = Arc::new(templates);
+            self.insert("t", handler_fn("Send a prompt template", move |args, ctx| {
+                let templates = templates.clone();
+                async move { template(&ctx, &templates, &args).await }
+            }));
+        }
+        self
+    }
+
+    /// Add or replace a command. Names follow Telegram's rules: 1–32
+    /// lowercase letters, digits, or underscores, without the leading `/`.
+    pub fn register(&mut self, name: &str, handler: impl CommandHandler + 'static) -> Result<()> {
+        let name = name.trim_start_matches('/');
+        let valid = (1..=32).contains(&name.len())
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid {
+            bail!("Invalid command name '{}': use 1-32 lowercase letters, digits, or underscores", name);
+        }
+        self.insert(name, handler);
+        Ok(())
+    }
+
+    fn insert(&mut self, name: &str, handler: impl CommandHandler + 'static) {
+        self.handlers.insert(name.to_string(), Arc::new(handler));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.handlers.contains_key(name)
+    }
+
+    /// `(name, description)` for every registered command, sorted by name.
+    pub fn list(&self) -> Vec<(String, String)> {
+        self.handlers
+            .iter()
+            .map(|(name, handler)| (name.clone(), handler.description().to_string()))
+            .collect()
+    }
+
+    /// Run `name` with `args`, or `None` if no such command is registered.
+    pub async fn dispatch(&self, name: &str, args: &str, ctx: &Context) -> Option<String> {
+        let handler = self.handlers.get(name)?;
+        Some(handler.handle(args.trim(), ctx).await)
+    }
+
+    /// Split "/name@bot args" into `("name", "args")` if `text` is a slash
+    /// command addressed to this registry.
+    pub fn parse<'a>(&self, text: &'a str) -> Option<(&'a str, &'a str)> {
+        let rest = text.strip_prefix('/')?;
+        let (word, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let name = word.split('@').next().unwrap_or(word);
+        self.contains(name).then_some((name, args.trim()))
+    }
+}
+
+async fn status(ctx: &Context) -> String {
+    let jobs = ctx.scheduler.list_jobs().await.unwrap_or_default();
+    let files = ctx.workspace.list_files();
+    let ollama = ctx.agent.effective_config().await;
+    let session = ctx.memory.active_session().await.unwrap_or_default();
+    let jobs = if ctx.scheduler.is_enabled() {
+        jobs.len().to_string()
+    } else {
+        "scheduler disabled".to_string()
+    };
+    format!(
+        "🦀 RustyClaw Status\n\n\
+        Model: {}\n\
+        Host: {}\n\
+        Context: {} tokens\n\
+        Session: {}\n\
+        Scheduled jobs: {}\n\
+        Workspace files: {}",
+        ollama.model,
+        ollama.host,
+        ollama.context_length,
+        session,
+        jobs,
+        files.len()
+    )
+}
+
+async fn jobs(ctx: &Context) -> String {
+    let jobs = ctx.scheduler.list_jobs().await.unwrap_or_default();
+    if !ctx.scheduler.is_enabled() {
+        "⏸️ Scheduler disabled. Set scheduler.enabled: true in config.yaml to use scheduled jobs.".to_string()
+    } else if jobs.is_empty() {
+        "No scheduled jobs. Ask me to schedule something!".to_string()
+    } else {
+        let mut lines = vec!["🕐 Scheduled Jobs\n".to_string()];
+        for job in jobs {
+            let mut line = format!("#{} — {}\n  Schedule: {}", job.id, job.task, job.schedule);
+            if job.once {
+                line.push_str(" (once)");
+            }
+            if let Some(chat_id) = job.chat_id {
+                line.push_str(&format!("\n  Chat: {}", chat_id));
+            }
+            if let Some(output) = &job.output {
+                line.push_str(&format!("\n  Saves to: {}", output));
+            }
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+}
+
+async fn ping(ctx: &Context) -> String {
+    match ctx.agent.health_check().await {
+        Health::Ok { latency, models } => format!(
+            "🏓 Ollama OK ({} ms)\n\nModels: {}",
+            latency.as_millis(),
+            models.join(", ")
+        ),
+        Health::ModelMissing { model, models } => format!(
+            "⚠️ Ollama is up but model '{}' is not pulled.\nRun: ollama pull {}\n\nAvailable: {}",
+            model,
+            model,
+            if models.is_empty() { "none".to_string() } else { models.join(", ") }
+        ),
+        Health::Unreachable { host, error } => {
+            format!("❌ Cannot reach Ollama at {}\n\n{}", host, error)
+        }
+    }
+}
+
+async fn models(ctx: &Context) -> String {
+    match ctx.agent.list_models().await {
+        Ok(models) if models.is_empty() => "No models pulled. Try: ollama pull tinyllama".to_string(),
+        Ok(models) => {
+            let current = ctx.agent.effective_config().await.model;
+            let mut lines = vec!["🧩 Available Models\n".to_string()];
+            for m in models {
+                let marker = if m.name == current || m.name == format!("{}:latest", current) { " ✅" } else { "" };
+                match m.size {
+                    Some(size) => lines.push(format!("{} ({:.1} GB){}", m.name, size as f64 / 1e9, marker)),
+                    None => lines.push(format!("{}{}", m.name, marker)),
+                }
+            }
+            lines.join("\n")
+        }
+        Err(e) => format!("❌ Could not list models: {}", e),
+    }
+}
+
+async fn memory(ctx: &Context) -> String {
+    let memory_content = ctx.agent.memory_content().await;
+    let (is_large, line_count) = ctx.agent.check_memory_size().await;
+    if memory_content.is_empty() {
+        "🧠 My Memory\n\nNo memories saved yet. Tell me something about yourself!".to_string()
+    } else {
+        let header = if is_large {
+            format!("🧠 My Memory ({} lines)\n\n⚠️ Memory is getting large!\n\n", line_count)
+        } else {
+            format!("🧠 My Memory ({} lines)\n\n", line_count)
+        };
+        format!("{}{}", header, memory_content)
+    }
+}
+
+/// Facts in memory.md that mention `query`.
+async fn search_memory(ctx: &Context, query: &str) -> String {
+    if query.is_empty() {
+        return "Usage: /search_memory <query>".to_string();
+    }
+    let facts = ctx.agent.search_memory(query).await;
+    if facts.is_empty() {
+        return format!("No matching memories for \"{}\".", query);
+    }
+    let mut lines = vec![format!("🧠 {} matching memor{}\n", facts.len(), if facts.len() == 1 { "y" } else { "ies" })];
+    lines.extend(facts.iter().map(|fact| format!("- {}", fact)));
+    lines.join("\n")
+}
+
+/// Clear memory.md, but only once the user has repeated the command.
+async fn forget(ctx: &Context) -> String {
+    let facts = ctx.agent.memory_content().await.lines().filter(|l| !l.trim().is_empty()).count();
+    if facts == 0 {
+        return "No memories to forget.".to_string();
+    }
+    if !ctx.confirm("forget").await {
+        return format!(
+            "⚠️ This forgets all {} saved memories.\n\nSend /forget again within {} seconds to confirm.",
+            facts,
+            CONFIRM_WINDOW.as_secs()
+        );
+    }
+
+    if ctx.agent.clear_memory().await.is_ok() {
+        "🧹 All memories have been forgotten.".to_string()
+    } else {
+        "❌ Failed to clear memory.".to_string()
+    }
+}
+
+/// The system prompt and history the next chat request would carry, with
+/// estimated token counts. `redact` hides memory facts and message text,
+/// keeping only roles and sizes.
+async fn context(ctx: &Context, redact: bool) -> String {
+    let history = ctx.history().await;
+    let messages = ctx.agent.context_preview(&history).await;
+    let context_length = ctx.agent.ollama_config().await.context_length;
+
+    let total: usize = messages.iter().map(|(_, content)| Agent::estimate_tokens(content)).sum();
+    let mut lines = vec![format!(
+        "🔍 Model Context ({} messages, ~{} of {} tokens)",
+        messages.len(),
+        total,
+        context_length
+    )];
+    let pinned = messages.get(1).is_some_and(|(role, content)| role == "system" && content.starts_with(PINNED_HEADING));
+    let left_out = (history.len() + 1 + usize::from(pinned)).saturating_sub(messages.len());
+    if left_out > 0 {
+        lines.push(format!("✂️ {} older message(s) left out to fit the context window.", left_out));
+    }
+    if total > context_length as usize {
+        lines.push("⚠️ The system prompt alone overflows the context window; Ollama will cut it.".to_string());
+    }
+
+    for (i, (role, content)) in messages.into_iter().enumerate() {
+        let tokens = Agent::estimate_tokens(&content);
+        let content = match (redact, role.as_str()) {
+            (false, _) => content,
+            (true, "system") if i == 1 && pinned => format!("{}\n[pinned text redacted]", PINNED_HEADING),
+            (true, "system") => match content.split_once(MEMORY_HEADING) {
+                Some((base, facts)) => format!(
+                    "{}{}\n[{} memory facts redacted]",
+                    base,
+                    MEMORY_HEADING,
+                    facts.lines().filter(|l| l.trim_start().starts_with("- ")).count()
+                ),
+                None => content,
+            },
+            (true, _) => format!("[{} characters redacted]", content.chars().count()),
+        };
+        lines.push(format!("\n[{}] ~{} tokens\n{}", role, tokens, content));
+    }
+
+    lines.join("\n")
+}
+
+/// `<cron> [count]`: the next fire times of a schedule, in local time.
+fn next(args: &str) -> String {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (schedule, count) = match words.len() {
+        5 => (words.join(" "), DEFAULT_UPCOMING),
+        6 => match words[5].parse::<usize>() {
+            Ok(count) if (1..=MAX_UPCOMING).contains(&count) => (words[..5].join(" "), count),
+            _ => return format!("❌ count must be a number from 1 to {}", MAX_UPCOMING),
+        },
+        _ => return "Usage: /next <min> <hour> <day> <month> <weekday> [count]\n\nExample: /next 0 9 * * 1-5 3".to_string(),
+    };
+
+    match scheduler::upcoming(&schedule, count) {
+        Ok(times) => {
+            let mut lines = vec![format!("🗓️ Next runs of {}\n", schedule)];
+            for time in times {
+                let local = time.with_timezone(&chrono::Local);
+                lines.push(local.format("%a %Y-%m-%d %H:%M %Z").to_string());
+            }
+            lines.join("\n")
+        }
+        Err(e) => format!("❌ {}", e),
+    }
+}
+
+/// `[n|off]`: show or change the sampling seed. A change applies to every
+/// session until the next restart or config reload (`Agent::set_seed`).
+async fn seed(ctx: &Context, args: &str) -> String {
+    let seed = match args {
+        "" => {
+            return match ctx.agent.ollama_config().await.seed {
+                Some(seed) => format!("🎲 Seed: {}\n\nUse /seed off for random sampling.", seed),
+                None => "🎲 No seed set (random sampling).\n\nUsage: /seed <n> | off".to_string(),
+            };
+        }
+        "off" => None,
+        n => match n.parse::<i64>() {
+            Ok(seed) => Some(seed),
+            Err(_) => return "Usage: /seed <n> | off".to_string(),
+        },
+    };
+
+    ctx.agent.set_seed(seed).await;
+    match seed {
+        Some(seed) => format!(
+            "🎲 Seed set to {} until restart. Combine with temperature 0 for repeatable replies; not every model honors it.",
+            seed
+        ),
+        None => "🎲 Seed cleared; sampling is random again.".to_string(),
+    }
+}
+
+/// Apply `change` to the active session's overrides and store them.
+async fn update_settings(ctx: &Context, change: impl FnOnce(&mut SessionSettings)) -> crate::error::Result<()> {
+    let mut settings = ctx.memory.session_settings().await?;
+    change(&mut settings);
+    ctx.memory.set_session_settings(&settings).await
+}
+
+/// Where a setting the session may override comes from, for replies.
+fn source(overridden: bool) -> &'static str {
+    if overridden {
+        "set for this session"
+    } else {
+        "global default"
+    }
+}
+
+/// `[name|reset]`: show or change the model this session chats with.
+async fn model(ctx: &Context, args: &str) -> String {
+    match args {
+        "" => {
+            let model = ctx.agent.effective_config().await.model;
+            let overridden = ctx.agent.session_settings().await.model.is_some();
+            format!(
+                "🧩 Model: {} ({})\n\n{}\nSee /models for the pulled ones.",
+                model,
+                source(overridden),
+                MODEL_USAGE
+            )
+        }
+        "reset" => match update_settings(ctx, |s| s.model = None).await {
+            Ok(()) => format!(
+                "🧩 This session uses the global model ({}) again.",
+                ctx.agent.ollama_config().await.model
+            ),
+            Err(e) => format!("❌ {}", e),
+        },
+        name => {
+            if let Ok(models) = ctx.agent.list_models().await {
+                if !models.iter().any(|m| m.name == name || m.name == format!("{}:latest", name)) {
+                    return format!("❌ {} isn't pulled on the Ollama host. See /models.", name);
+                }
+            }
+            match update_settings(ctx, |s| s.model = Some(name.to_string())).await {
+                Ok(()) => format!("🧩 This session now uses {}.", name),
+                Err(e) => format!("❌ {}", e),
+            }
+        }
+    }
+}
+
+/// `[0.0-2.0|reset]`: show or change this session's sampling temperature.
+/// Values outside the range are clamped to it.
+async fn temp(ctx: &Context, args: &str) -> String {
+    let requested = match args {
+        "" => {
+            let temperature = ctx.agent.effective_config().await.temperature;
+            let overridden = ctx.agent.session_settings().await.temperature.is_some();
+            return format!("🌡️ Temperature: {} ({})\n\n{}", temperature, source(overridden), TEMP_USAGE);
+        }
+        "reset" => {
+            return match update_settings(ctx, |s| s.temperature = None).await {
+                Ok(()) => format!(
+                    "🌡️ This session uses the global temperature ({}) again.",
+                    ctx.agent.ollama_config().await.temperature
+                ),
+                Err(e) => format!("❌ {}", e),
+            };
+        }
+        n => match n.parse::<f32>() {
+            Ok(t) if t.is_finite() => t,
+            _ => return TEMP_USAGE.to_string(),
+        },
+    };
+
+    let temperature = requested.clamp(0.0, 2.0);
+    if let Err(e) = update_settings(ctx, |s| s.temperature = Some(temperature)).await {
+        return format!("❌ {}", e);
+    }
+    if temperature == requested {
+        format!("🌡️ Temperature set to {} for this session.", temperature)
+    } else {
+        format!(
+            "⚠️ {} is outside 0.0–2.0; temperature set to {} for this session.",
+            requested, temperature
+        )
+    }
+}
+
+/// `[text|reset]`: show or change the persona that replaces the base system
+/// prompt in this session.
+async fn persona(ctx: &Context, args: &str) -> String {
+    let (persona, reply) = match args {
+        "" => {
+            return match ctx.agent.session_settings().await.persona {
+                Some(persona) => format!("🎭 Persona for this session:\n\n{}\n\n{}", persona, PERSONA_USAGE),
+                None => format!("🎭 No persona set; this session uses the default system prompt.\n\n{}", PERSONA_USAGE),
+            };
+        }
+        "reset" => (None, "🎭 Persona cleared; this session uses the default system prompt again."),
+        text => (
+            Some(text.to_string()),
+            "🎭 Persona set for this session. It replaces the default system prompt; memory is still included.",
+        ),
+    };
+
+    match update_settings(ctx, |s| s.persona = persona).await {
+        Ok(()) => reply.to_string(),
+        Err(e) => format!("❌ {}", e),
+    }
+}
+
+/// `new <name>`, `switch <name>`, or `list`: manage separate conversation threads.
+async fn session(ctx: &Context, args: &str) -> String {
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let result = match words.as_slice() {
+        [] | ["list"] => match ctx.memory.list_sessions().await {
+            Ok(sessions) => {
+                let mut lines = vec!["🧵 Sessions\n".to_string()];
+                for s in sessions {
+                    let marker = if s.active { " ✅" } else { "" };
+                    lines.push(format!("{} ({} messages){}", s.name, s.messages, marker));
+                }
+                Ok(lines.join("\n"))
+            }
+            Err(e) => Err(e),
+        },
+        ["new", name] if name.chars().count() > MAX_SESSION_NAME => {
+            return format!("❌ Session names can be at most {} characters.", MAX_SESSION_NAME);
+        }
+        ["new", name] => match ctx.memory.create_session(name).await {
+            Ok(true) => ctx
+                .memory
+                .switch_session(name)
+                .await
+                .map(|_| format!("🧵 Started session '{}'.", name)),
+            Ok(false) => Ok(format!("Session '{}' already exists. Use /session switch {}", name, name)),
+            Err(e) => Err(e),
+        },
+        ["switch", name] => match ctx.memory.switch_session(name).await {
+            Ok(true) => Ok(format!("🧵 Switched to session '{}'.", name)),
+            Ok(false) => Ok(format!("No session named '{}'. See /session list", name)),
+            Err(e) => Err(e),
+        },
+        _ => return SESSION_USAGE.to_string(),
+    };
+
+    result.unwrap_or_else(|e| format!("❌ Session error: {}", e))
+}
+
+/// Pin `text`, or the latest message when it's empty, to the active session.
+async fn pin(ctx: &Context, text: &str) -> String {
+    let content = if text.is_empty() {
+        match ctx.memory.get_history(1).await {
+            Ok(history) => match history.into_iter().last() {
+                Some(message) => message.content,
+                None => return "Nothing to pin yet. Usage: /pin [text]".to_string(),
+            },
+            Err(e) => return format!("❌ Error: {}", e),
+        }
+    } else {
+        text.to_string()
+    };
+
+    match ctx.memory.add_pin(content.trim()).await {
+        Ok(Some(id)) => format!("📌 Pinned #{}: {}", id, pin_preview(&content)),
+        Ok(None) => format!(
+            "❌ That would take this session's pins past {} characters. /unpin something first.",
+            ctx.memory.max_pinned_chars()
+        ),
+        Err(e) => format!("❌ Error: {}", e),
+    }
+}
+
+async fn unpin(ctx: &Context, args: &str) -> String {
+    let Ok(id) = args.trim_start_matches('#').parse::<i64>() else {
+        return "Usage: /unpin <id> (see /pins)".to_string();
+    };
+    match ctx.memory.remove_pin(id).await {
+        Ok(true) => format!("📌 Unpinned #{}.", id),
+        Ok(false) => format!("No pin #{} in this session. See /pins", id),
+        Err(e) => format!("❌ Error: {}", e),
+    }
+}
+
+async fn pins(ctx: &Context) -> String {
+    let pins = match ctx.memory.pins().await {
+        Ok(pins) => pins,
+        Err(e) => return format!("❌ Error: {}", e),
+    };
+    if pins.is_empty() {
+        return "No pins in this session. Use /pin [text] to add one.".to_string();
+    }
+    let used: usize = pins.iter().map(|p| p.content.chars().count()).sum();
+    let mut lines = vec![format!(
+        "📌 Pins ({} of {} characters)\n",
+        used,
+        ctx.memory.max_pinned_chars()
+    )];
+    for pin in &pins {
+        lines.push(format!("#{} {}", pin.id, pin_preview(&pin.content)));
+    }
+    lines.join("\n")
+}
+
+/// `text` on one line, cut to `PIN_PREVIEW_CHARS`.
+fn pin_preview(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > PIN_PREVIEW_CHARS {
+        format!("{}...", text.chars().take(PIN_PREVIEW_CHARS - 3).collect::<String>())
+    } else {
+        text
+    }
+}
+
+/// Delete every workspace file, but only once the user has seen how many and
+/// repeated the command with `confirm`.
+async fn clear_workspace(ctx: &Context, confirmed: bool) -> String {
+    let count = ctx.workspace.list_files().len();
+    if count == 0 {
+        return "Workspace is already empty.".to_string();
+    }
+    if !confirmed {
+        return format!(
+            "⚠️ This deletes all {} file(s) in the workspace.\n\nSend /clearworkspace confirm to go ahead.",
+            count
+        );
+    }
+
+    match ctx.workspace.clear().await {
+        Ok(n) => format!("🧹 Deleted {} file(s) from the workspace.", n),
+        Err(e) => format!("❌ Could not clear the workspace: {}", e),
+    }
+}
+
+/// `<file>`: a unified diff between the two newest saved versions of a
+/// workspace file, e.g. `main_1.py` and `main_2.py` for `main.py`.
+fn diff(ctx: &Context, args: &str) -> String {
+    if args.is_empty() {
+        return "Usage: /diff <file>\n\nExample: /diff main.py".to_string();
+    }
+
+    let versions = ctx.workspace.versions(args);
+    let (old, new) = match versions.as_slice() {
+        [] => return format!("❌ No file named {} in the workspace.", args),
+        [only] => return format!("ℹ️ {} has only one version so far.", only),
+        [.., old, new] => (old, new),
+    };
+    let (Some(old_text), Some(new_text)) = (ctx.workspace.read_file(old), ctx.workspace.read_file(new)) else {
+        return format!("❌ Could not read {} and {} as text.", old, new);
+    };
+    if old_text == new_text {
+        return format!("ℹ️ {} and {} are identical.", old, new);
+    }
+
+    let diff = TextDiff::from_lines(&old_text, &new_text)
+        .unified_diff()
+        .header(old, new)
+        .to_string();
+    format!("📝 {} → {}\n\n{}", old, new, diff.trim_end())
+}
+
+/// Save the last code block of the latest reply that has one, named after
+/// the time with an extension from its language tag (`.txt` if unknown).
+async fn copy_last_code(ctx: &Context) -> String {
+    let history = ctx.memory.get_history(CODE_SEARCH_HISTORY).await.unwrap_or_default();
+    let Some((lang, code)) = history
+        .iter()
+        .rev()
+        .filter(|m| m.role == "assistant")
+        .find_map(|m| Agent::extract_code_blocks(&m.content).pop())
+    else {
+        return "❌ No code blocks found in recent conversation.".to_string();
+    };
+
+    let ext = extension_for(&lang);
+    let filename = format!(
+        "snippet-{}.{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        ext.unwrap_or("txt")
+    );
+    let language = ext.map(|_| lang.as_str());
+    match ctx.workspace.save_file(&filename, &code, language, None).await {
+        Ok(saved) => format!("💾 Saved {} ({}) to workspace", saved.name(), format_size(saved.bytes)),
+        Err(e) => format!("❌ Error saving file: {}", e),
+    }
+}
+
+/// A fresh answer in place of the latest reply, with its actions first.
+async fn regenerate(ctx: &Context) -> String {
+    match ctx.regenerate().await {
+        Ok(reply) => reply_text(reply),
+        Err(e) => format!("ℹ️ {}", e),
+    }
+}
+
+/// A reply as one message: the actions taken, then the text.
+fn reply_text(reply: Reply) -> String {
+    let mut lines: Vec<String> = reply
+        .actions
+        .iter()
+        .map(|action| format!("{} {}", action.emoji(), action))
+        .collect();
+    if !reply.text.is_empty() {
+        lines.push(reply.text);
+    }
+    lines.join("\n\n")
+}
+
+/// Fill in the template `name` from `args` and send it as a user message.
+/// Without a name, list the templates.
+async fn template(ctx: &Context, templates: &BTreeMap<String, String>, args: &str) -> String {
+    let (name, args) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    if name.is_empty() {
+        let mut lines = vec!["📝 Templates\n".to_string()];
+        lines.extend(templates.iter().map(|(name, text)| format!("{} — {}", name, text)));
+        lines.push("\nUsage: /t <name> <args>".to_string());
+        return lines.join("\n");
+    }
+    let Some(text) = templates.get(name) else {
+        let names: Vec<&str> = templates.keys().map(String::as_str).collect();
+        return format!("Unknown template '{}'. Available: {}", name, names.join(", "));
+    };
+
+    match expand_template(text, args) {
+        Ok(prompt) => format!("📝 {}\n\n{}", prompt, reply_text(ctx.respond(&prompt).await)),
+        Err(e) => format!("❌ {}", e),
+    }
+}
+
+/// Fill the `{placeholder}`s in `template` from `args`. An argument
+/// `name=value` fills `{name}`; the others fill the remaining placeholders
+/// in order of first appearance. Double quotes keep spaces in an argument.
+/// Every placeholder must be filled and every argument used.
+///
+/// ```
+/// use rustyclaw::commands::expand_template;
+///
+/// let template = "Explain {topic} to a {level}. Keep {topic} simple.";
+/// assert_eq!(
+///     expand_template(template, r#""black holes" child"#).unwrap(),
+///     "Explain black holes to a child. Keep black holes simple."
+/// );
+/// assert_eq!(
+///     expand_template(template, r#"level="young child" recursion"#).unwrap(),
+///     "Explain recursion to a young child. Keep recursion simple."
+/// );
+/// assert_eq!(
+///     expand_template(template, "recursion").unwrap_err().to_string(),
+///     "Missing argument for {level}"
+/// );
+/// ```
+pub fn expand_template(template: &str, args: &str) -> Result<String> {
+    let re = Regex::new(r"\{(\w+)\}").unwrap();
+    let mut placeholders: Vec<&str> = Vec::new();
+    for caps in re.captures_iter(template) {
+        let name = caps.get(1).unwrap().as_str();
+        if !placeholders.contains(&name) {
+            placeholders.push(name);
+        }
+    }
+
+    let mut values: HashMap<&str, String> = HashMap::new();
+    let mut positional = Vec::new();
+    for arg in split_args(args) {
+        let named = arg
+            .split_once('=')
+            .and_then(|(name, value)| Some((*placeholders.iter().find(|p| **p == name)?, value)));
+        match named {
+            Some((name, value)) => {
+                values.insert(name, value.to_string());
+            }
+            None => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    for name in &placeholders {
+        if !values.contains_key(name) {
+            if let Some(value) = positional.next() {
+                values.insert(name, value);
+            }
+        }
+    }
+
+    let missing: Vec<String> = placeholders
+        .iter()
+        .filter(|name| !values.contains_key(*name))
+        .map(|name| format!("{{{}}}", name))
+        .collect();
+    if !missing.is_empty() {
+        bail!("Missing argument for {}", missing.join(", "));
+    }
+    let unused = positional.count();
+    if unused > 0 {
+        bail!(
+            "{} argument(s) too many: the template has {} placeholder(s)",
+            unused,
+            placeholders.len()
+        );
+    }
+
+    Ok(re.replace_all(template, |caps: &Captures| values[&caps[1]].clone()).into_owned())
+}
+
+/// Split `args` on whitespace, keeping double-quoted spans together.
+fn split_args(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quoted = false;
+    for c in args.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
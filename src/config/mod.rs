@@ -1,13 +1,41 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use crate::error::{self, RustyClawError};
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct TelegramConfig {
     #[serde(default)]
     pub token: String,
     #[serde(default)]
     pub allowed_users: Vec<i64>,
+    /// Longer replies are split into several messages of at most this many
+    /// bytes, from 64 up to Telegram's limit of 4096; the default leaves
+    /// some headroom.
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: usize,
+    /// How many times a send is retried after a network error, a timeout or
+    /// Telegram's flood control. Permanent errors are never retried.
+    #[serde(default = "default_send_retries")]
+    pub send_retries: u32,
+    /// Give up on a single send attempt after this many seconds.
+    #[serde(default = "default_send_timeout_secs")]
+    pub send_timeout_secs: u64,
+}
+
+fn default_max_message_bytes() -> usize {
+    4000
+}
+
+fn default_send_retries() -> u32 {
+    3
+}
+
+fn default_send_timeout_secs() -> u64 {
+    30
 }
 
 impl Default for TelegramConfig {
@@ -15,10 +43,22 @@ impl Default for TelegramConfig {
         Self {
             token: String::new(),
             allowed_users: Vec::new(),
+            max_message_bytes: default_max_message_bytes(),
+            send_retries: default_send_retries(),
+            send_timeout_secs: default_send_timeout_secs(),
         }
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct DiscordConfig {
+    #[serde(default)]
+    pub token: String,
+    /// Discord user IDs allowed to talk to the bot. Empty allows everyone.
+    #[serde(default)]
+    pub allowed_users: Vec<u64>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct OllamaConfig {
     #[serde(default = "default_ollama_host")]
@@ -31,6 +71,50 @@ pub struct OllamaConfig {
     pub context_length: u32,
     #[serde(default = "default_temperature")]
     pub temperature: f32,
+    /// Fixed sampling seed. With temperature 0 this makes replies repeatable
+    /// on models that honor it.
+    #[serde(default)]
+    pub seed: Option<i64>,
+    /// Inject only the memories most relevant to the latest message instead of all of them.
+    #[serde(default)]
+    pub semantic_memory: bool,
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+    #[serde(default = "default_memory_top_k")]
+    pub memory_top_k: usize,
+    /// Tags whose contents are the model's reasoning, e.g. `think` for
+    /// `<think>...</think>`. They are hidden from replies.
+    #[serde(default = "default_thinking_tags")]
+    pub thinking_tags: Vec<String>,
+    /// Store replies with their reasoning blocks intact, for debugging. They
+    /// are still hidden when shown, but count against the context window.
+    #[serde(default)]
+    pub keep_thinking: bool,
+    /// Cut replies longer than this many characters and mark them as
+    /// truncated. Action blocks in the cut part are still carried out.
+    #[serde(default)]
+    pub max_response_chars: Option<usize>,
+    /// Store the whole reply in the history even when it was cut for display.
+    #[serde(default)]
+    pub store_full_response: bool,
+    /// Pull `model` at startup when the Ollama host doesn't have it yet.
+    #[serde(default)]
+    pub auto_pull: bool,
+    /// Give up on an automatic pull after this many seconds.
+    #[serde(default = "default_pull_timeout_secs")]
+    pub pull_timeout_secs: u64,
+    /// Chat and completion requests sent to Ollama at once; more wait their
+    /// turn. 1 suits a single local GPU.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Append built-in instructions for the `cron`, `save:`, and `memory`
+    /// blocks to the system prompt. Turn off if your own prompt covers them.
+    #[serde(default = "default_action_instructions")]
+    pub action_instructions: bool,
+    /// Ping the model after this many idle seconds so Ollama keeps it
+    /// loaded, whatever `keep_alive` is. Unset sends no pings.
+    #[serde(default)]
+    pub keepwarm_secs: Option<u64>,
 }
 
 fn default_ollama_host() -> String {
@@ -53,6 +137,30 @@ fn default_temperature() -> f32 {
     0.7
 }
 
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+fn default_memory_top_k() -> usize {
+    5
+}
+
+fn default_pull_timeout_secs() -> u64 {
+    1800
+}
+
+fn default_max_concurrent() -> usize {
+    1
+}
+
+fn default_action_instructions() -> bool {
+    true
+}
+
+fn default_thinking_tags() -> Vec<String> {
+    vec!["think".to_string(), "reasoning".to_string()]
+}
+
 impl Default for OllamaConfig {
     fn default() -> Self {
         Self {
@@ -61,6 +169,19 @@ impl Default for OllamaConfig {
             keep_alive: default_keep_alive(),
             context_length: default_context_length(),
             temperature: default_temperature(),
+            seed: None,
+            semantic_memory: false,
+            embedding_model: default_embedding_model(),
+            memory_top_k: default_memory_top_k(),
+            thinking_tags: default_thinking_tags(),
+            keep_thinking: false,
+            max_response_chars: None,
+            store_full_response: false,
+            auto_pull: false,
+            pull_timeout_secs: default_pull_timeout_secs(),
+            max_concurrent: default_max_concurrent(),
+            action_instructions: default_action_instructions(),
+            keepwarm_secs: None,
         }
     }
 }
@@ -69,6 +190,18 @@ impl Default for OllamaConfig {
 pub struct WorkspaceConfig {
     #[serde(default = "default_workspace_path")]
     pub path: PathBuf,
+    /// File extensions the agent may save (e.g. `py`, `md`). Empty allows all.
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>,
+    /// Permit saving dotfiles such as `.env` or `.bashrc`.
+    #[serde(default)]
+    pub allow_hidden: bool,
+    /// Keep the workspace under git and commit every saved file.
+    #[serde(default)]
+    pub git: bool,
+    /// Tell the model which files already exist, refreshed every turn.
+    #[serde(default)]
+    pub manifest: bool,
 }
 
 fn default_workspace_path() -> PathBuf {
@@ -79,6 +212,10 @@ impl Default for WorkspaceConfig {
     fn default() -> Self {
         Self {
             path: default_workspace_path(),
+            allowed_extensions: Vec::new(),
+            allow_hidden: false,
+            git: false,
+            manifest: false,
         }
     }
 }
@@ -87,6 +224,10 @@ impl Default for WorkspaceConfig {
 pub struct SchedulerConfig {
     #[serde(default = "default_scheduler_enabled")]
     pub enabled: bool,
+    /// Delay each firing by a random 0..=jitter_secs seconds so jobs sharing
+    /// a schedule don't all hit the model at once.
+    #[serde(default)]
+    pub jitter_secs: u64,
 }
 
 fn default_scheduler_enabled() -> bool {
@@ -97,6 +238,7 @@ impl Default for SchedulerConfig {
     fn default() -> Self {
         Self {
             enabled: default_scheduler_enabled(),
+            jitter_secs: 0,
         }
     }
 }
@@ -107,6 +249,35 @@ pub struct MemoryConfig {
     pub database: PathBuf,
     #[serde(default = "default_max_history")]
     pub max_history: usize,
+    #[serde(default = "default_pool_size")]
+    pub pool_size: u32,
+    /// How long a write waits for another connection's lock before it is
+    /// retried; a write still locked after a few retries reports the
+    /// database as busy.
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+    /// Delete messages older than this many days (the newest `max_history` are always kept).
+    #[serde(default)]
+    pub retention_days: Option<u32>,
+    /// Markdown file holding long-term facts about the user.
+    #[serde(default = "default_facts_file")]
+    pub facts_file: PathBuf,
+    /// Once the facts file grows past this many lines, the model is asked to
+    /// merge and deduplicate it.
+    #[serde(default = "default_max_memory_lines")]
+    pub max_memory_lines: usize,
+    #[serde(default)]
+    pub auto_summary: AutoSummaryConfig,
+    /// Don't store a message identical in role and content to the one just
+    /// before it in the session, e.g. a double-sent message or a retried
+    /// insert.
+    #[serde(default)]
+    pub dedupe: bool,
+    /// Total characters of `/pin`ned text a session may hold. Pins are
+    /// added to every prompt, so they take this much context (about a
+    /// quarter as many tokens).
+    #[serde(default = "default_max_pinned_chars")]
+    pub max_pinned_chars: usize,
 }
 
 fn default_database_path() -> PathBuf {
@@ -117,20 +288,364 @@ fn default_max_history() -> usize {
     50
 }
 
+fn default_facts_file() -> PathBuf {
+    PathBuf::from("memory.md")
+}
+
+fn default_max_memory_lines() -> usize {
+    100
+}
+
+fn default_max_pinned_chars() -> usize {
+    2000
+}
+
+fn default_pool_size() -> u32 {
+    4
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
 impl Default for MemoryConfig {
     fn default() -> Self {
         Self {
             database: default_database_path(),
             max_history: default_max_history(),
+            pool_size: default_pool_size(),
+            busy_timeout_ms: default_busy_timeout_ms(),
+            retention_days: None,
+            facts_file: default_facts_file(),
+            max_memory_lines: default_max_memory_lines(),
+            auto_summary: AutoSummaryConfig::default(),
+            dedupe: false,
+            max_pinned_chars: default_max_pinned_chars(),
+        }
+    }
+}
+
+/// Background extraction of new facts from recent conversation into the
+/// facts file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutoSummaryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Summarize once the conversation has been quiet this long.
+    #[serde(default = "default_idle_minutes")]
+    pub idle_minutes: u64,
+    /// Also summarize when this 5-field cron expression fires, e.g.
+    /// "0 23 * * *" for the end of the day, even if the chat isn't idle.
+    #[serde(default)]
+    pub schedule: Option<String>,
+}
+
+fn default_idle_minutes() -> u64 {
+    30
+}
+
+impl Default for AutoSummaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: default_idle_minutes(),
+            schedule: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiConfig {
+    #[serde(default = "default_api_host")]
+    pub host: String,
+    #[serde(default = "default_api_port")]
+    pub port: u16,
+    /// Bearer token required on every request. Unset leaves the API open.
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+fn default_api_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_api_port() -> u16 {
+    8080
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            host: default_api_host(),
+            port: default_api_port(),
+            token: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct MetricsConfig {
+    /// Port for the Prometheus `/metrics` endpoint. Unset disables it.
+    #[serde(default)]
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TtsConfig {
+    /// OpenAI-compatible speech endpoint, e.g. "http://localhost:8880/v1/audio/speech".
+    /// Unset disables voice replies.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default = "default_tts_model")]
+    pub model: String,
+    #[serde(default = "default_tts_voice")]
+    pub voice: String,
+    /// Send only the voice note instead of the voice note plus text.
+    #[serde(default)]
+    pub replace_text: bool,
+}
+
+fn default_tts_model() -> String {
+    "tts-1".to_string()
+}
+
+fn default_tts_voice() -> String {
+    "alloy".to_string()
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            model: default_tts_model(),
+            voice: default_tts_voice(),
+            replace_text: false,
+        }
+    }
+}
+
+/// An operator's record of every exchange with the model, one JSON object per
+/// line, kept apart from the tracing logs and the conversation history.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_audit_path")]
+    pub path: PathBuf,
+    /// Once the file reaches this size it is renamed to `<path>.1`, replacing
+    /// the previous one, and a new file is started.
+    #[serde(default = "default_audit_max_bytes")]
+    pub max_bytes: u64,
+}
+
+fn default_audit_path() -> PathBuf {
+    PathBuf::from("audit.jsonl")
+}
+
+fn default_audit_max_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_audit_path(),
+            max_bytes: default_audit_max_bytes(),
         }
     }
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoggingConfig {
+    /// Directory for the daily-rotated `rustyclaw.YYYY-MM-DD.log` files.
+    #[serde(default = "default_log_directory")]
+    pub directory: PathBuf,
+    /// Number of daily log files to keep before the oldest is deleted.
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+    /// Filter used when `RUST_LOG` is unset, e.g. "info" or "rustyclaw=debug".
+    #[serde(default = "default_log_level")]
+    pub level: String,
+}
+
+fn default_log_directory() -> PathBuf {
+    PathBuf::from("logs")
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            directory: default_log_directory(),
+            max_files: default_log_max_files(),
+            level: default_log_level(),
+        }
+    }
+}
+
+/// Per-element color overrides applied on top of the theme preset. Accepts
+/// names ("cyan", "darkgray"), hex ("#ff8800"), or 256-color indices ("208").
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ThemeColors {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub assistant: Option<String>,
+    #[serde(default)]
+    pub busy: Option<String>,
+    #[serde(default)]
+    pub hint: Option<String>,
+    #[serde(default)]
+    pub highlight: Option<String>,
+    /// Background of fenced code blocks.
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+impl ThemeColors {
+    pub fn entries(&self) -> [(&'static str, Option<&str>); 7] {
+        [
+            ("title", self.title.as_deref()),
+            ("user", self.user.as_deref()),
+            ("assistant", self.assistant.as_deref()),
+            ("busy", self.busy.as_deref()),
+            ("hint", self.hint.as_deref()),
+            ("highlight", self.highlight.as_deref()),
+            ("code", self.code.as_deref()),
+        ]
+    }
+}
+
+/// Per-action key overrides for the TUI; actions left unset keep their
+/// default keys. Each key is a name like "enter", "pageup", or "f2", a single
+/// character, or either with "ctrl+", "alt+", or "shift+" in front.
 #[derive(Debug, Clone, Deserialize, Default)]
+pub struct KeyBindings {
+    #[serde(default)]
+    pub send: Option<Vec<String>>,
+    #[serde(default)]
+    pub newline: Option<Vec<String>>,
+    #[serde(default)]
+    pub quit: Option<Vec<String>>,
+    #[serde(default)]
+    pub clear_input: Option<Vec<String>>,
+    #[serde(default)]
+    pub scroll_up: Option<Vec<String>>,
+    #[serde(default)]
+    pub scroll_down: Option<Vec<String>>,
+    #[serde(default)]
+    pub history_prev: Option<Vec<String>>,
+    #[serde(default)]
+    pub history_next: Option<Vec<String>>,
+    #[serde(default)]
+    pub complete: Option<Vec<String>>,
+    #[serde(default)]
+    pub copy: Option<Vec<String>>,
+    #[serde(default)]
+    pub toggle_panel: Option<Vec<String>>,
+}
+
+impl KeyBindings {
+    pub fn entries(&self) -> [(&'static str, Option<&[String]>); 11] {
+        [
+            ("send", self.send.as_deref()),
+            ("newline", self.newline.as_deref()),
+            ("quit", self.quit.as_deref()),
+            ("clear_input", self.clear_input.as_deref()),
+            ("scroll_up", self.scroll_up.as_deref()),
+            ("scroll_down", self.scroll_down.as_deref()),
+            ("history_prev", self.history_prev.as_deref()),
+            ("history_next", self.history_next.as_deref()),
+            ("complete", self.complete.as_deref()),
+            ("copy", self.copy.as_deref()),
+            ("toggle_panel", self.toggle_panel.as_deref()),
+        ]
+    }
+}
+
+pub const THEMES: &[&str] = &["dark", "light", "mono"];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TuiConfig {
+    /// Color preset: one of `THEMES`.
+    #[serde(default = "default_theme")]
+    pub theme: String,
+    #[serde(default)]
+    pub colors: ThemeColors,
+    /// Shown at startup instead of "Welcome to RustyClaw!".
+    #[serde(default)]
+    pub greeting: Option<String>,
+    /// Also ask the model for a short greeting based on memory and recent chat.
+    #[serde(default)]
+    pub ai_greeting: bool,
+    /// While a reply is being generated, hold one new message and send it
+    /// next, instead of turning it away.
+    #[serde(default)]
+    pub queue_when_busy: bool,
+    /// Capture the mouse for wheel scrolling and click-to-select. Turn off
+    /// to keep the terminal's own text selection.
+    #[serde(default = "default_mouse")]
+    pub mouse: bool,
+    /// Color code blocks by their fenced language. Turn off on slow
+    /// terminals; blocks still get the `code` background.
+    #[serde(default = "default_syntax_highlighting")]
+    pub syntax_highlighting: bool,
+    #[serde(default)]
+    pub keybindings: KeyBindings,
+    /// On exit, write a transcript of the messages sent during the run to
+    /// this directory. Relative paths resolve against `data_dir`.
+    #[serde(default)]
+    pub transcript_dir: Option<PathBuf>,
+}
+
+fn default_mouse() -> bool {
+    true
+}
+
+fn default_syntax_highlighting() -> bool {
+    true
+}
+
+fn default_theme() -> String {
+    "dark".to_string()
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            theme: default_theme(),
+            colors: ThemeColors::default(),
+            greeting: None,
+            ai_greeting: false,
+            queue_when_busy: false,
+            mouse: default_mouse(),
+            syntax_highlighting: default_syntax_highlighting(),
+            keybindings: KeyBindings::default(),
+            transcript_dir: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Config {
+    /// Base directory for relative data paths (database, workspace, memory, logs).
+    #[serde(default)]
+    pub data_dir: Option<PathBuf>,
     #[serde(default)]
     pub telegram: TelegramConfig,
     #[serde(default)]
+    pub discord: DiscordConfig,
+    #[serde(default)]
     pub ollama: OllamaConfig,
     #[serde(default)]
     pub workspace: WorkspaceConfig,
@@ -139,11 +654,72 @@ pub struct Config {
     #[serde(default)]
     pub memory: MemoryConfig,
     #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub tts: TtsConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub tui: TuiConfig,
+    /// What starts a command in the TUI and in Telegram messages, such as
+    /// `!` where `/` is taken. Telegram's own `/` commands keep working.
+    #[serde(default = "default_command_prefix")]
+    pub command_prefix: String,
+    /// Prompt templates sent with `/t <name> <args>`, keyed by name. Each
+    /// `{placeholder}` is filled from the arguments.
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    #[serde(default)]
     pub system_prompt: String,
+    /// Read the system prompt from this file when `system_prompt` is empty.
+    /// It must exist.
+    #[serde(default)]
+    pub system_prompt_file: Option<PathBuf>,
+    /// Optional fallback prompt file when neither of the above is set
+    /// (default `soul.md` in the working directory).
+    #[serde(default)]
+    pub soul_file: Option<PathBuf>,
+}
+
+fn default_command_prefix() -> String {
+    "/".to_string()
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: None,
+            telegram: TelegramConfig::default(),
+            discord: DiscordConfig::default(),
+            ollama: OllamaConfig::default(),
+            workspace: WorkspaceConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            memory: MemoryConfig::default(),
+            api: ApiConfig::default(),
+            metrics: MetricsConfig::default(),
+            tts: TtsConfig::default(),
+            logging: LoggingConfig::default(),
+            audit: AuditConfig::default(),
+            tui: TuiConfig::default(),
+            command_prefix: default_command_prefix(),
+            templates: BTreeMap::new(),
+            system_prompt: String::new(),
+            system_prompt_file: None,
+            soul_file: None,
+        }
+    }
 }
 
 impl Config {
-    pub fn load(path: &Path) -> Result<Self> {
+    pub fn load(path: &Path) -> error::Result<Self> {
+        Self::read(path).map_err(RustyClawError::Config)
+    }
+
+    fn read(path: &Path) -> Result<Self> {
         if !path.exists() {
             anyhow::bail!(
                 "Config file not found: {}\nCopy config.example.yaml to config.yaml and edit it.",
@@ -151,24 +727,336 @@ impl Config {
             );
         }
 
-        let content = std::fs::read_to_string(path)
-            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
-
+        let yaml = load_yaml(path, &mut Vec::new())?;
         let mut config: Config =
-            serde_yaml::from_str(&content).with_context(|| "Failed to parse config YAML")?;
+            serde_yaml::from_value(yaml).with_context(|| "Failed to parse config YAML")?;
+
+        config.apply_env_overrides();
+        config.resolve_data_paths()?;
+
+        config.load_system_prompt()?;
+        config.check()?;
+
+        Ok(config)
+    }
+
+    /// Fill in an empty `system_prompt` from `system_prompt_file`, which must
+    /// exist, or else from the soul file if there is one.
+    fn load_system_prompt(&mut self) -> Result<()> {
+        if !self.system_prompt.is_empty() {
+            return Ok(());
+        }
 
-        if config.system_prompt.is_empty() {
-            let soul_path = Path::new("soul.md");
-            if soul_path.exists() {
-                config.system_prompt =
-                    std::fs::read_to_string(soul_path).with_context(|| "Failed to read soul.md")?;
+        if let Some(path) = &self.system_prompt_file {
+            if !path.is_file() {
+                bail!("system_prompt_file not found: {}", path.display());
             }
+            self.system_prompt = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read system_prompt_file {}", path.display()))?;
+            return Ok(());
         }
 
-        Ok(config)
+        let soul_path = self.soul_file.clone().unwrap_or_else(|| PathBuf::from("soul.md"));
+        if soul_path.exists() {
+            self.system_prompt = std::fs::read_to_string(&soul_path)
+                .with_context(|| format!("Failed to read {}", soul_path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Resolve `path` against `data_dir` when it is relative; absolute paths
+    /// and configs without a `data_dir` are returned unchanged.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.data_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    fn resolve_data_paths(&mut self) -> Result<()> {
+        let Some(dir) = &self.data_dir else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create data directory: {}", dir.display()))?;
+
+        self.workspace.path = self.resolve(&self.workspace.path);
+        self.memory.database = self.resolve(&self.memory.database);
+        self.memory.facts_file = self.resolve(&self.memory.facts_file);
+        self.logging.directory = self.resolve(&self.logging.directory);
+        self.audit.path = self.resolve(&self.audit.path);
+        self.system_prompt_file = self.system_prompt_file.as_deref().map(|p| self.resolve(p));
+        self.soul_file = self.soul_file.as_deref().map(|p| self.resolve(p));
+        self.tui.transcript_dir = self.tui.transcript_dir.as_deref().map(|p| self.resolve(p));
+        Ok(())
     }
 
-    pub fn load_from_default() -> Result<Self> {
+    /// Check every value for sanity and report all problems at once.
+    ///
+    /// ```
+    /// use rustyclaw::Config;
+    ///
+    /// assert!(Config::default().validate().is_ok());
+    ///
+    /// let mut config = Config::default();
+    /// config.ollama.host = "localhost:11434".to_string();
+    /// config.ollama.temperature = 3.0;
+    /// config.memory.max_history = 0;
+    /// config.command_prefix = "go".to_string();
+    /// let error = config.validate().unwrap_err().to_string();
+    /// assert!(error.contains("ollama.host 'localhost:11434' must start with http://"), "{}", error);
+    /// assert!(error.contains("ollama.temperature 3 must be between 0.0 and 2.0"), "{}", error);
+    /// assert!(error.contains("memory.max_history must be at least 1"), "{}", error);
+    /// assert!(error.contains("command_prefix 'go' must be 1-3 symbols"), "{}", error);
+    /// assert_eq!(error.matches("\n  - ").count(), 4, "{}", error);
+    /// ```
+    pub fn validate(&self) -> error::Result<()> {
+        self.check().map_err(RustyClawError::Config)
+    }
+
+    fn check(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.ollama.host.trim().is_empty() {
+            problems.push("ollama.host must not be empty".to_string());
+        } else if !self.ollama.host.starts_with("http://") && !self.ollama.host.starts_with("https://") {
+            problems.push(format!(
+                "ollama.host '{}' must start with http:// or https://",
+                self.ollama.host
+            ));
+        }
+        if self.ollama.model.trim().is_empty() {
+            problems.push("ollama.model must not be empty (e.g. \"tinyllama\")".to_string());
+        }
+        if self.ollama.context_length == 0 {
+            problems.push("ollama.context_length must be greater than 0".to_string());
+        }
+        if self.ollama.max_response_chars == Some(0) {
+            problems.push("ollama.max_response_chars must be greater than 0 (or unset for no limit)".to_string());
+        }
+        if !(0.0..=2.0).contains(&self.ollama.temperature) {
+            problems.push(format!(
+                "ollama.temperature {} must be between 0.0 and 2.0",
+                self.ollama.temperature
+            ));
+        }
+        if self.ollama.semantic_memory && self.ollama.embedding_model.trim().is_empty() {
+            problems.push("ollama.embedding_model must be set when semantic_memory is enabled".to_string());
+        }
+        if self.ollama.semantic_memory && self.ollama.memory_top_k == 0 {
+            problems.push("ollama.memory_top_k must be at least 1".to_string());
+        }
+        if self.ollama.auto_pull && self.ollama.pull_timeout_secs == 0 {
+            problems.push("ollama.pull_timeout_secs must be at least 1".to_string());
+        }
+        if self.ollama.max_concurrent == 0 {
+            problems.push("ollama.max_concurrent must be at least 1".to_string());
+        }
+        if !(64..=4096).contains(&self.telegram.max_message_bytes) {
+            problems.push(format!(
+                "telegram.max_message_bytes {} must be between 64 and 4096",
+                self.telegram.max_message_bytes
+            ));
+        }
+        if self.telegram.send_timeout_secs == 0 {
+            problems.push("telegram.send_timeout_secs must be at least 1".to_string());
+        }
+        if self.ollama.thinking_tags.iter().any(|t| t.trim().is_empty() || t.contains(['<', '>', '/'])) {
+            problems.push("ollama.thinking_tags must be bare tag names like \"think\"".to_string());
+        }
+        if self.workspace.path.as_os_str().is_empty() {
+            problems.push("workspace.path must not be empty".to_string());
+        }
+        if self.workspace.allowed_extensions.iter().any(|e| e.trim_start_matches('.').trim().is_empty()) {
+            problems.push("workspace.allowed_extensions must not contain empty entries".to_string());
+        }
+        if self.memory.database.as_os_str().is_empty() {
+            problems.push("memory.database must not be empty".to_string());
+        }
+        if self.memory.max_history == 0 {
+            problems.push("memory.max_history must be at least 1".to_string());
+        }
+        if self.memory.retention_days == Some(0) {
+            problems.push("memory.retention_days must be at least 1 (or omitted to keep everything)".to_string());
+        }
+        if self.memory.pool_size == 0 {
+            problems.push("memory.pool_size must be at least 1".to_string());
+        }
+        if self.memory.max_memory_lines == 0 {
+            problems.push("memory.max_memory_lines must be at least 1".to_string());
+        }
+        if self.memory.auto_summary.idle_minutes == 0 {
+            problems.push("memory.auto_summary.idle_minutes must be at least 1".to_string());
+        }
+        if let Some(schedule) = &self.memory.auto_summary.schedule {
+            if let Err(e) = crate::scheduler::upcoming(schedule, 1) {
+                problems.push(format!("memory.auto_summary.schedule '{}' is invalid: {}", schedule, e));
+            }
+        }
+        if let Some(url) = &self.tts.url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                problems.push(format!("tts.url '{}' must start with http:// or https://", url));
+            }
+        }
+        if self.logging.max_files == 0 {
+            problems.push("logging.max_files must be at least 1".to_string());
+        }
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&self.logging.level) {
+            problems.push(format!("logging.level '{}' is not a valid filter: {}", self.logging.level, e));
+        }
+        if self.audit.enabled && self.audit.path.as_os_str().is_empty() {
+            problems.push("audit.path must not be empty".to_string());
+        }
+        if self.audit.enabled && self.audit.max_bytes == 0 {
+            problems.push("audit.max_bytes must be greater than 0".to_string());
+        }
+        if !THEMES.contains(&self.tui.theme.as_str()) {
+            problems.push(format!(
+                "tui.theme '{}' must be one of: {}",
+                self.tui.theme,
+                THEMES.join(", ")
+            ));
+        }
+        for (name, color) in self.tui.colors.entries() {
+            if let Some(color) = color {
+                if color.parse::<ratatui::style::Color>().is_err() {
+                    problems.push(format!("tui.colors.{} '{}' is not a valid color", name, color));
+                }
+            }
+        }
+        if let Err(errors) = crate::tui::Keymap::from_config(&self.tui.keybindings) {
+            problems.extend(errors);
+        }
+        if self.command_prefix.is_empty()
+            || self.command_prefix.chars().count() > 3
+            || self.command_prefix.contains(|c: char| c.is_alphanumeric() || c.is_whitespace())
+        {
+            problems.push(format!(
+                "command_prefix '{}' must be 1-3 symbols, such as \"/\" or \"!\"",
+                self.command_prefix
+            ));
+        }
+        for (name, template) in &self.templates {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                problems.push(format!("templates: name '{}' must be a single word", name));
+            }
+            if template.trim().is_empty() {
+                problems.push(format!("templates.{} must not be empty", name));
+            }
+        }
+
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "Invalid configuration:\n  - {}",
+                problems.join("\n  - ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Overlay `RUSTYCLAW_*` environment variables on top of the file values.
+    /// Precedence: environment > config.yaml > built-in defaults.
+    fn apply_env_overrides(&mut self) {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        if let Some(token) = var("RUSTYCLAW_TELEGRAM_TOKEN") {
+            self.telegram.token = token;
+        }
+        if let Some(token) = var("RUSTYCLAW_DISCORD_TOKEN") {
+            self.discord.token = token;
+        }
+        if let Some(host) = var("RUSTYCLAW_OLLAMA_HOST") {
+            self.ollama.host = host;
+        }
+        if let Some(model) = var("RUSTYCLAW_OLLAMA_MODEL") {
+            self.ollama.model = model;
+        }
+        if let Some(path) = var("RUSTYCLAW_WORKSPACE_PATH") {
+            self.workspace.path = PathBuf::from(path);
+        }
+        if let Some(dir) = var("RUSTYCLAW_DATA_DIR") {
+            self.data_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(path) = var("RUSTYCLAW_DATABASE") {
+            self.memory.database = PathBuf::from(path);
+        }
+        if let Some(token) = var("RUSTYCLAW_API_TOKEN") {
+            self.api.token = Some(token);
+        }
+        if let Some(path) = var("RUSTYCLAW_SYSTEM_PROMPT_FILE") {
+            self.system_prompt_file = Some(PathBuf::from(path));
+        }
+    }
+
+    pub fn load_from_default() -> error::Result<Self> {
         Self::load(Path::new("config.yaml"))
     }
 }
+
+/// Read `path` as YAML with the files in its top-level `include:` list merged
+/// in. Includes are resolved relative to the file naming them and applied in
+/// order, then `path` itself, each overriding keys set before it. `stack`
+/// holds the files being loaded, to reject include cycles.
+fn load_yaml(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    if let Some(start) = stack.iter().position(|p| *p == canonical) {
+        let cycle: Vec<String> = stack[start..]
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        bail!("Config include cycle: {}", cycle.join(" -> "));
+    }
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut value: Value = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse config YAML in {}", path.display()))?;
+    if value.is_null() {
+        value = Value::Mapping(Default::default());
+    }
+
+    let includes = match value.as_mapping_mut().and_then(|m| m.remove("include")) {
+        None => Vec::new(),
+        Some(Value::Sequence(items)) => items,
+        Some(_) => bail!("include: in {} must be a list of file paths", path.display()),
+    };
+    if includes.is_empty() {
+        return Ok(value);
+    }
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let mut merged = Value::Mapping(Default::default());
+    for include in includes {
+        let Some(file) = include.as_str() else {
+            bail!("include: in {} must be a list of file paths", path.display());
+        };
+        merge_yaml(&mut merged, load_yaml(&base_dir.join(file), stack)?);
+    }
+    stack.pop();
+
+    merge_yaml(&mut merged, value);
+    Ok(merged)
+}
+
+/// Merge `overlay` into `base`: mappings merge key by key, anything else in
+/// `overlay` replaces what was there.
+fn merge_yaml(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_yaml(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
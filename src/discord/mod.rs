@@ -0,0 +1,266 @@
+use anyhow::Result;
+use regex::Regex;
+use serenity::all::{
+    ChannelId, Command, CommandInteraction, CommandOptionType, Context as DiscordContext,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, EventHandler,
+    GatewayIntents, Interaction, Message, Ready,
+};
+use serenity::{async_trait, Client};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{error, info, warn};
+
+use crate::commands::CommandRegistry;
+use crate::config::Config;
+use crate::frontend::{chunk_text, Context};
+use crate::scheduler::task_label;
+
+/// Discord rejects messages longer than 2000 characters.
+const MAX_MESSAGE_LEN: usize = 1900;
+
+type LastChannel = Arc<RwLock<Option<ChannelId>>>;
+
+pub struct DiscordBot {
+    config: Config,
+    context: Context,
+    commands: Arc<CommandRegistry>,
+    channel: LastChannel,
+}
+
+impl DiscordBot {
+    pub fn new(config: Config, context: Context) -> Self {
+        Self {
+            config,
+            context,
+            commands: Arc::new(CommandRegistry::with_builtins()),
+            channel: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Replace the built-in slash commands with `commands`.
+    pub fn with_commands(mut self, commands: Arc<CommandRegistry>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        let intents = GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::DIRECT_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT;
+
+        let handler = Handler {
+            allowed_users: self.config.discord.allowed_users.clone(),
+            context: self.context.clone(),
+            commands: self.commands.clone(),
+            channel: self.channel.clone(),
+        };
+        let mut client = Client::builder(&self.config.discord.token, intents)
+            .event_handler(handler)
+            .await?;
+
+        // Cron messages go to whichever channel last talked to the bot.
+        let http = client.http.clone();
+        let channel = self.channel.clone();
+        self.context
+            .scheduler
+            .add_send_callback(move |msg: String| {
+                let http = http.clone();
+                let channel = channel.clone();
+                async move {
+                    let Some(channel_id) = *channel.read().await else {
+                        warn!("No Discord channel yet — cron message not delivered");
+                        return;
+                    };
+                    for text in chunk_text(&msg, MAX_MESSAGE_LEN) {
+                        if let Err(e) = channel_id.say(&http, text).await {
+                            error!("Failed to send message to Discord: {}", e);
+                        }
+                    }
+                }
+            })
+            .await;
+
+        info!("🦀 Discord bot is starting...");
+        client.start().await?;
+        Ok(())
+    }
+}
+
+struct Handler {
+    allowed_users: Vec<u64>,
+    context: Context,
+    commands: Arc<CommandRegistry>,
+    channel: LastChannel,
+}
+
+impl Handler {
+    fn is_allowed(&self, user_id: u64) -> bool {
+        if self.allowed_users.is_empty() || self.allowed_users.contains(&user_id) {
+            return true;
+        }
+        warn!("Rejected Discord message from unauthorized user {}", user_id);
+        false
+    }
+
+    async fn command_text(&self, command: &CommandInteraction) -> String {
+        let option = |name: &str| {
+            command
+                .data
+                .options
+                .iter()
+                .find(|o| o.name == name)
+                .and_then(|o| o.value.as_str())
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+        let context = &self.context;
+
+        match command.data.name.as_str() {
+            "schedule" => {
+                let schedule = option("cron");
+                let message = option("message");
+                let task = task_label(&message);
+                match context.scheduler.add_job(&schedule, &task, &message).await {
+                    Ok(job_id) => format!(
+                        "✅ Scheduled job #{}: {}\nSchedule: {}\nMessage: {}",
+                        job_id, task, schedule, message
+                    ),
+                    Err(e) => format!("❌ Could not schedule: {}", e),
+                }
+            }
+            "workspace" => {
+                let files = context.workspace.list_files_with_metadata().await;
+                if files.is_empty() {
+                    "Workspace is empty. Ask me to write some code!".to_string()
+                } else {
+                    let mut lines = vec!["📁 Workspace Files\n".to_string()];
+                    for f in files {
+                        let size_kb = f.size as f64 / 1024.0;
+                        let mut line = match &f.language {
+                            Some(lang) => format!("{} [{}] ({:.1} KB)", f.name, lang, size_kb),
+                            None => format!("{} ({:.1} KB)", f.name, size_kb),
+                        };
+                        if let Some(desc) = &f.description {
+                            line.push_str(&format!("\n  {}", desc));
+                        }
+                        lines.push(line);
+                    }
+                    lines.join("\n")
+                }
+            }
+            "clear" => context.clear_history().await,
+            other => match self.commands.dispatch(other, &option("args"), context).await {
+                Some(output) => output,
+                None => format!("Unknown command: /{}", other),
+            },
+        }
+    }
+
+    async fn reply(&self, ctx: &DiscordContext, channel_id: ChannelId, text: &str) {
+        for chunk in chunk_text(text, MAX_MESSAGE_LEN) {
+            if let Err(e) = channel_id.say(&ctx.http, chunk).await {
+                error!("Failed to send message to Discord: {}", e);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: DiscordContext, ready: Ready) {
+        let mut commands = vec![
+            CreateCommand::new("schedule")
+                .description("Create a cron job")
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "cron", "minute hour day month weekday")
+                        .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "message", "What to send when it fires")
+                        .required(true),
+                ),
+            CreateCommand::new("workspace").description("List generated files"),
+            CreateCommand::new("clear").description("Clear chat history"),
+        ];
+        for (name, description) in self.commands.list() {
+            if matches!(name.as_str(), "schedule" | "workspace" | "clear") {
+                continue;
+            }
+            commands.push(
+                CreateCommand::new(name).description(description).add_option(
+                    CreateCommandOption::new(CommandOptionType::String, "args", "Arguments").required(false),
+                ),
+            );
+        }
+        if let Err(e) = Command::set_global_commands(&ctx.http, commands).await {
+            error!("Failed to register Discord commands: {}", e);
+        }
+        info!("🦀 Discord bot is ready as {}", ready.user.name);
+    }
+
+    async fn interaction_create(&self, ctx: DiscordContext, interaction: Interaction) {
+        let Interaction::Command(command) = interaction else {
+            return;
+        };
+        if !self.is_allowed(command.user.id.get()) {
+            return;
+        }
+        *self.channel.write().await = Some(command.channel_id);
+
+        let text = self.command_text(&command).await;
+        let mut chunks = chunk_text(&text, MAX_MESSAGE_LEN).into_iter();
+
+        let first = CreateInteractionResponseMessage::new().content(chunks.next().unwrap_or_default());
+        if let Err(e) = command
+            .create_response(&ctx.http, CreateInteractionResponse::Message(first))
+            .await
+        {
+            error!("Failed to answer Discord command: {}", e);
+            return;
+        }
+        for chunk in chunks {
+            let followup = CreateInteractionResponseFollowup::new().content(chunk);
+            if let Err(e) = command.create_followup(&ctx.http, followup).await {
+                error!("Failed to send message to Discord: {}", e);
+            }
+        }
+    }
+
+    /// Chat happens in DMs, or in servers when the bot is mentioned.
+    async fn message(&self, ctx: DiscordContext, msg: Message) {
+        if msg.author.bot || !self.is_allowed(msg.author.id.get()) {
+            return;
+        }
+        if msg.guild_id.is_some() && !msg.mentions_me(&ctx).await.unwrap_or(false) {
+            return;
+        }
+
+        let mention = Regex::new(r"<@!?\d+>").unwrap();
+        let user_text = mention.replace_all(&msg.content, "").trim().to_string();
+        if user_text.is_empty() {
+            return;
+        }
+
+        *self.channel.write().await = Some(msg.channel_id);
+        info!("Discord message received: {}...", user_text.chars().take(80).collect::<String>());
+
+        if let Err(e) = msg.channel_id.broadcast_typing(&ctx.http).await {
+            warn!("Failed to send typing indicator: {}", e);
+        }
+
+        let context = Context {
+            user: Some(msg.author.display_name().to_string()),
+            ..self.context.clone()
+        };
+        let reply = context.respond(&user_text).await;
+
+        for action in &reply.actions {
+            self.reply(&ctx, msg.channel_id, &format!("{} {}", action.emoji(), action)).await;
+        }
+        if !reply.text.is_empty() {
+            self.reply(&ctx, msg.channel_id, &reply.text).await;
+        }
+    }
+}
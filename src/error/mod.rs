@@ -0,0 +1,62 @@
+//! The error type returned at the library's public boundary.
+//!
+//! Internally errors are `anyhow::Error`s with context attached; the public
+//! methods on `Config`, `Agent`, `Memory`, `Scheduler`, and `Workspace` wrap
+//! them in a `RustyClawError` naming the subsystem that failed, so embedding
+//! code can tell "Ollama is down" from "bad cron expression" without parsing
+//! messages. Display is unchanged from the wrapped error.
+
+use thiserror::Error;
+
+use crate::memory::DatabaseBusy;
+
+pub type Result<T, E = RustyClawError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum RustyClawError {
+    /// The config file is missing, malformed, or fails validation.
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// Ollama couldn't be reached or returned an error.
+    #[error(transparent)]
+    Ollama(anyhow::Error),
+    /// The SQLite database, or the memory file beside it, failed.
+    #[error(transparent)]
+    Database(anyhow::Error),
+    /// A job couldn't be scheduled, e.g. an invalid cron expression or a
+    /// disabled scheduler.
+    #[error(transparent)]
+    Scheduler(anyhow::Error),
+    /// A workspace file operation was refused or failed.
+    #[error(transparent)]
+    Workspace(anyhow::Error),
+}
+
+impl RustyClawError {
+    /// Whether this is a write that gave up because the database stayed locked.
+    /// Its message is meant to be shown to the user as-is.
+    pub fn is_busy(&self) -> bool {
+        matches!(self, RustyClawError::Database(e) if e.is::<DatabaseBusy>())
+    }
+
+    /// Wrap an internal error as `kind`, unless it already is a
+    /// `RustyClawError` (e.g. a database failure inside a workspace call).
+    pub(crate) fn wrap(e: anyhow::Error, kind: fn(anyhow::Error) -> Self) -> Self {
+        match e.downcast::<RustyClawError>() {
+            Ok(e) => e,
+            Err(e) => kind(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for RustyClawError {
+    fn from(e: sqlx::Error) -> Self {
+        RustyClawError::Database(e.into())
+    }
+}
+
+impl From<reqwest::Error> for RustyClawError {
+    fn from(e: reqwest::Error) -> Self {
+        RustyClawError::Ollama(e.into())
+    }
+}
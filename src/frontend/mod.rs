@@ -0,0 +1,603 @@
+//! The response pipeline shared by every frontend.
+//!
+//! Telegram, the TUI, and the REST API all store the user's message, ask the
+//! model, act on its `cron`/`save:`/`memory` blocks, and store the reply.
+//! `respond` does all of that once; each frontend only decides how to show
+//! the resulting `Reply`.
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::agent::{ActionBlock, Agent, BlockDetector};
+use crate::error::RustyClawError;
+use crate::memory::{Memory, Message};
+use crate::metrics;
+use crate::scheduler::{JobOptions, Scheduler};
+use crate::workspace::{detect_language, format_size, Workspace};
+
+/// Appended to a reply cut at `ollama.max_response_chars`.
+pub const TRUNCATED: &str = "…(truncated)";
+
+/// Something the pipeline did (or failed to do) on the model's behalf.
+#[derive(Debug, Clone)]
+pub enum Action {
+    CronError(String),
+    Scheduled { id: i64, task: String, schedule: String },
+    ScheduleFailed(String),
+    Saved { name: String, bytes: u64 },
+    SaveFailed(String),
+    Remembered(String),
+}
+
+impl Action {
+    pub fn emoji(&self) -> &'static str {
+        match self {
+            Action::CronError(_) => "⚠️",
+            Action::Scheduled { .. } => "✅",
+            Action::ScheduleFailed(_) | Action::SaveFailed(_) => "❌",
+            Action::Saved { .. } => "💾",
+            Action::Remembered(_) => "🧠",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::CronError(e) => write!(f, "Cron error: {}", e),
+            Action::Scheduled { id, task, schedule } => {
+                write!(f, "Scheduled job #{}: {} ({})", id, task, schedule)
+            }
+            Action::ScheduleFailed(e) => write!(f, "Error scheduling: {}", e),
+            Action::Saved { name, bytes } => {
+                write!(f, "Saved {} ({}) to workspace", name, format_size(*bytes))
+            }
+            Action::SaveFailed(e) => write!(f, "Error saving file: {}", e),
+            Action::Remembered(fact) => write!(f, "Remembered: {}", fact),
+        }
+    }
+}
+
+/// The model's answer to one user message.
+#[derive(Debug, Clone)]
+pub struct Reply {
+    /// Text to show the user, with action blocks stripped. May be empty.
+    pub text: String,
+    /// Actions taken, in the order they should be reported (before `text`).
+    pub actions: Vec<Action>,
+}
+
+/// A workspace file attached to the next message with `/use`. It is shown to
+/// the model for that one turn but never stored in the history.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub content: String,
+    /// Whether `content` was cut short to fit the context window.
+    pub truncated: bool,
+}
+
+impl Attachment {
+    /// `message` prefixed with the file, as the model should see it.
+    fn wrap(&self, message: &str) -> String {
+        format!(
+            "Contents of {}{}:\n```{}\n{}\n```\n\n{}",
+            self.name,
+            if self.truncated { " (truncated)" } else { "" },
+            detect_language(&self.name).unwrap_or_default(),
+            self.content,
+            message
+        )
+    }
+}
+
+/// How long a destructive command waits to be repeated.
+pub const CONFIRM_WINDOW: Duration = Duration::from_secs(30);
+
+/// Destructive commands that were asked for once and are waiting to be
+/// repeated, by command and session.
+#[derive(Debug, Default)]
+pub struct Confirmations {
+    pending: Mutex<HashMap<String, Instant>>,
+}
+
+impl Confirmations {
+    /// Whether `key` was already asked for within `CONFIRM_WINDOW`, which
+    /// confirms it. Otherwise the window starts now and this returns false.
+    pub fn confirm(&self, key: &str) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, at| at.elapsed() < CONFIRM_WINDOW);
+        if pending.remove(key).is_some() {
+            return true;
+        }
+        pending.insert(key.to_string(), Instant::now());
+        false
+    }
+}
+
+/// What the latest turn did that `/undo` can reverse, by session.
+///
+/// ```
+/// use rustyclaw::frontend::{Action, UndoLog};
+///
+/// let undo = UndoLog::default();
+/// undo.record("default", &[
+///     Action::Remembered("Likes tea".to_string()),
+///     Action::SaveFailed("disk full".to_string()),
+/// ]);
+/// assert_eq!(undo.take("default").len(), 1);
+/// assert!(undo.take("default").is_empty());
+/// ```
+#[derive(Debug, Default)]
+pub struct UndoLog {
+    last: Mutex<HashMap<String, Vec<Action>>>,
+}
+
+impl UndoLog {
+    /// Replace what `session` can undo with the scheduled jobs, saved files,
+    /// and remembered facts among `actions`.
+    pub fn record(&self, session: &str, actions: &[Action]) {
+        let undoable: Vec<Action> = actions
+            .iter()
+            .filter(|a| matches!(a, Action::Scheduled { .. } | Action::Saved { .. } | Action::Remembered(_)))
+            .cloned()
+            .collect();
+        let mut last = self.last.lock().unwrap();
+        if undoable.is_empty() {
+            last.remove(session);
+        } else {
+            last.insert(session.to_string(), undoable);
+        }
+    }
+
+    /// Take what `session` can undo, leaving nothing behind.
+    pub fn take(&self, session: &str) -> Vec<Action> {
+        self.last.lock().unwrap().remove(session).unwrap_or_default()
+    }
+}
+
+/// Everything a frontend needs to answer a message.
+#[derive(Clone)]
+pub struct Context {
+    pub agent: Arc<Agent>,
+    pub memory: Arc<Memory>,
+    pub scheduler: Arc<Scheduler>,
+    pub workspace: Arc<Workspace>,
+    pub max_history: usize,
+    pub confirmations: Arc<Confirmations>,
+    pub undo: Arc<UndoLog>,
+    /// Name of the person chatting, for `{user}` in the system prompt.
+    pub user: Option<String>,
+}
+
+impl Context {
+    /// Whether `command` is being repeated within `CONFIRM_WINDOW` in the
+    /// active session. The first call returns false and starts the window.
+    pub async fn confirm(&self, command: &str) -> bool {
+        let session = self.memory.active_session().await.unwrap_or_default();
+        self.confirmations.confirm(&format!("{}:{}", command, session))
+    }
+
+    /// Clear the active session's history once `/clear` has been sent twice.
+    pub async fn clear_history(&self) -> String {
+        let session = self.memory.active_session().await.unwrap_or_default();
+        if !self.confirm("clear").await {
+            return format!(
+                "⚠️ This deletes the conversation history of session '{}'.\n\nSend /clear again within {} seconds to confirm.",
+                session,
+                CONFIRM_WINDOW.as_secs()
+            );
+        }
+        match self.memory.clear_history().await {
+            Ok(()) => "🧹 Conversation history cleared.".to_string(),
+            Err(e) => format!("❌ Error: {}", e),
+        }
+    }
+
+    /// Reverse what the latest turn in the active session did: cancel the
+    /// jobs it scheduled, delete the files it saved, and forget the facts it
+    /// remembered. Only works once per turn.
+    pub async fn undo(&self) -> String {
+        let session = self.memory.active_session().await.unwrap_or_default();
+        let actions = self.undo.take(&session);
+        if actions.is_empty() {
+            return "Nothing to undo.".to_string();
+        }
+
+        let mut lines = Vec::new();
+        for action in actions.iter().rev() {
+            let line = match action {
+                Action::Scheduled { id, task, .. } => match self.scheduler.cancel_job(*id).await {
+                    Ok(true) => format!("↩️ Cancelled job #{}: {}", id, task),
+                    Ok(false) => format!("Job #{} was already cancelled.", id),
+                    Err(e) => format!("❌ Error cancelling job #{}: {}", id, e),
+                },
+                Action::Saved { name, .. } => match self.workspace.delete_file(name).await {
+                    Ok(()) => format!("↩️ Deleted {}", name),
+                    Err(e) => format!("❌ Error deleting {}: {}", name, e),
+                },
+                Action::Remembered(fact) => match self.agent.forget_fact(fact).await {
+                    Ok(true) => format!("↩️ Forgot: {}", fact),
+                    Ok(false) => format!("\"{}\" is no longer in memory.", fact),
+                    Err(e) => format!("❌ Error forgetting \"{}\": {}", fact, e),
+                },
+                _ => continue,
+            };
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    /// Load a workspace text file to attach to the next message, cut to about
+    /// half the model's context window so the history still fits.
+    pub async fn attach(&self, filename: &str) -> Result<Attachment> {
+        let content = self
+            .workspace
+            .read_file(filename)
+            .ok_or_else(|| anyhow!("{} not found or not a text file", filename))?;
+        // About four characters per token, as in `Agent::estimate_tokens`.
+        let max_chars = self.agent.ollama_config().await.context_length as usize * 2;
+
+        let truncated = content.chars().count() > max_chars;
+        let content = if truncated { content.chars().take(max_chars).collect() } else { content };
+        Ok(Attachment {
+            name: filename.to_string(),
+            content,
+            truncated,
+        })
+    }
+
+    /// The recent history the model is shown, with the workspace manifest (if
+    /// enabled) just before the latest message. The manifest is rebuilt on
+    /// every call and never stored.
+    pub async fn history(&self) -> Vec<Message> {
+        let mut history = self.memory.get_history(self.max_history).await.unwrap_or_default();
+        self.insert_manifest(&mut history);
+        history
+    }
+
+    fn insert_manifest(&self, history: &mut Vec<Message>) {
+        if let Some(manifest) = self.workspace.manifest() {
+            let at = history.len().saturating_sub(1);
+            history.insert(
+                at,
+                Message {
+                    role: "system".to_string(),
+                    content: manifest,
+                    timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                    model: None,
+                },
+            );
+        }
+    }
+
+    pub async fn respond(&self, user_text: &str) -> Reply {
+        self.respond_with(user_text, None).await
+    }
+
+    /// Record `user_text`, get the model's reply, carry out its action blocks,
+    /// and record the raw reply. Model errors come back as an apology in
+    /// `text` that is left out of the history, as does a database too busy to
+    /// record the message, so this never fails. An `attachment` is shown to
+    /// the model ahead of `user_text` but not recorded.
+    ///
+    /// A failed turn leaves only the user's message behind:
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::{Config, RustyClaw};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-respond-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let mut config = Config::default();
+    /// config.ollama.host = "http://127.0.0.1:9".to_string(); // nothing listens here
+    /// config.memory.database = dir.join("respond.db");
+    /// config.memory.facts_file = dir.join("memory.md");
+    /// config.workspace.path = dir.join("workspace");
+    /// let claw = RustyClaw::builder(config).with_warm_up(false).build().await.unwrap();
+    /// let memory = claw.memory();
+    /// memory.add_message("user", "Hi", None).await.unwrap();
+    /// memory.add_message("assistant", "Hello!", None).await.unwrap();
+    ///
+    /// let reply = claw.context().respond_with("Still there?", None).await;
+    /// assert!(reply.text.starts_with("Sorry, I had trouble thinking about that."));
+    ///
+    /// let history: Vec<(String, String)> = memory.get_history(10).await.unwrap()
+    ///     .into_iter().map(|m| (m.role, m.content)).collect();
+    /// assert_eq!(history, [
+    ///     ("user".to_string(), "Hi".to_string()),
+    ///     ("assistant".to_string(), "Hello!".to_string()),
+    ///     ("user".to_string(), "Still there?".to_string()),
+    /// ]);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn respond_with(&self, user_text: &str, attachment: Option<&Attachment>) -> Reply {
+        self.answer(user_text, attachment, None).await
+    }
+
+    /// Like `respond`, also sending the raw reply to `deltas` piece by piece
+    /// as the model writes it. Each action block is carried out as soon as
+    /// its closing fence arrives. The returned `Reply` is what to show in the
+    /// end: reasoning and action blocks are only removed from it.
+    pub async fn respond_streaming(&self, user_text: &str, deltas: mpsc::UnboundedSender<String>) -> Reply {
+        self.answer(user_text, None, Some(deltas)).await
+    }
+
+    async fn answer(
+        &self,
+        user_text: &str,
+        attachment: Option<&Attachment>,
+        deltas: Option<mpsc::UnboundedSender<String>>,
+    ) -> Reply {
+        metrics::record_message();
+        let session = self.memory.active_session().await.unwrap_or_default();
+        self.undo.take(&session);
+        if let Err(e) = self.memory.add_message("user", user_text, None).await {
+            if e.is_busy() {
+                return Reply {
+                    text: format!("⏳ {}", e),
+                    actions: Vec::new(),
+                };
+            }
+            warn!("Failed to store message: {}", e);
+        }
+
+        let mut history = self.history().await;
+        if let (Some(attachment), Some(last)) = (attachment, history.last_mut()) {
+            if last.role == "user" {
+                last.content = attachment.wrap(&last.content);
+            }
+        }
+        let model = self.agent.effective_config().await.model;
+        let user = self.user.as_deref();
+        let (response, acted) = match deltas {
+            Some(deltas) => {
+                let (response, actions) = self.chat_acting(&history, user, deltas).await;
+                (response, Some(actions))
+            }
+            None => (self.agent.chat_as(&history, user).await, None),
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                // Blocks carried out before the stream broke still happened.
+                let actions = acted.unwrap_or_default();
+                self.undo.record(&session, &actions);
+                return Reply { actions, ..model_error(e) };
+            }
+        };
+
+        self.act_on(&response, &model, acted).await
+    }
+
+    /// Stream a reply to `deltas`, carrying out each action block the moment
+    /// it is complete. Returns the raw reply and what its blocks did.
+    async fn chat_acting(
+        &self,
+        history: &[Message],
+        user: Option<&str>,
+        deltas: mpsc::UnboundedSender<String>,
+    ) -> (Result<String, RustyClawError>, Vec<Action>) {
+        let tags = self.agent.ollama_config().await.thinking_tags;
+        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let act = async {
+            let mut detector = BlockDetector::new().with_thinking_tags(tags);
+            let mut actions = Vec::new();
+            while let Some(delta) = rx.recv().await {
+                deltas.send(delta.clone()).ok();
+                for block in detector.push(&delta) {
+                    actions.extend(self.carry_out(block).await);
+                }
+            }
+            actions
+        };
+        tokio::join!(self.agent.chat_stream(history, user, tx), act)
+    }
+
+    /// Ask the model again for the latest reply and put the new answer in its
+    /// place. Fails if the latest message isn't a reply. The old reply is
+    /// only deleted once a new one has arrived.
+    pub async fn regenerate(&self) -> Result<Reply> {
+        let mut history = self.memory.get_history(self.max_history).await?;
+        if history.last().map(|m| m.role.as_str()) != Some("assistant") {
+            bail!("There's no reply to regenerate yet.");
+        }
+        history.pop();
+        self.insert_manifest(&mut history);
+
+        let model = self.agent.effective_config().await.model;
+        let response = match self.agent.chat_as(&history, self.user.as_deref()).await {
+            Ok(response) => response,
+            Err(e) => return Ok(model_error(e)),
+        };
+
+        self.memory.remove_last_reply().await?;
+        Ok(self.act_on(&response, &model, None).await)
+    }
+
+    /// Carry out the action blocks in `response` and record it as `model`'s
+    /// reply. Blocks inside the model's reasoning are ignored. `acted` holds
+    /// what the blocks already did when they were carried out while
+    /// streaming.
+    async fn act_on(&self, raw: &str, model: &str, acted: Option<Vec<Action>>) -> Reply {
+        let config = self.agent.ollama_config().await;
+        let response = &Agent::strip_thinking_tags(raw, &config.thinking_tags);
+        let actions = match acted {
+            Some(actions) => actions,
+            None => {
+                let mut actions = Vec::new();
+                for block in action_blocks(response) {
+                    actions.extend(self.carry_out(block).await);
+                }
+                actions
+            }
+        };
+
+        let stored = if config.keep_thinking { raw } else { response };
+        let stored = match config.max_response_chars {
+            Some(max) if !config.store_full_response => truncate_reply(stored, max),
+            _ => stored.to_string(),
+        };
+        self.memory.add_message("assistant", &stored, Some(model)).await.ok();
+        let session = self.memory.active_session().await.unwrap_or_default();
+        self.undo.record(&session, &actions);
+
+        let text = Agent::clean_response(response);
+        Reply {
+            text: match config.max_response_chars {
+                Some(max) => truncate_reply(&text, max),
+                None => text,
+            },
+            actions,
+        }
+    }
+
+    /// Do what one action block asks. `None` for a memory the facts file
+    /// already has.
+    async fn carry_out(&self, block: ActionBlock) -> Option<Action> {
+        let action = match block {
+            ActionBlock::CronError(e) => Action::CronError(e),
+            ActionBlock::Cron(job) => {
+                let options = JobOptions {
+                    once: job.once,
+                    chat_id: job.chat_id,
+                    output: job.output,
+                };
+                match self.scheduler.add_job_with(&job.schedule, &job.task, &job.message, options).await {
+                    Ok(id) => Action::Scheduled {
+                        id,
+                        task: job.task,
+                        schedule: job.schedule,
+                    },
+                    Err(e) => Action::ScheduleFailed(e.to_string()),
+                }
+            }
+            ActionBlock::Save(block) => match self
+                .workspace
+                .save_file(&block.filename, &block.content, block.language.as_deref(), block.description.as_deref())
+                .await
+            {
+                Ok(saved) => Action::Saved {
+                    name: saved.name().to_string(),
+                    bytes: saved.bytes,
+                },
+                Err(e) => Action::SaveFailed(e.to_string()),
+            },
+            ActionBlock::Memory(fact) => {
+                if !self.agent.save_to_memory(&fact).await.unwrap_or(false) {
+                    return None;
+                }
+                Action::Remembered(fact)
+            }
+        };
+        Some(action)
+    }
+}
+
+/// The action blocks in a whole reply: cron errors and jobs, then saves,
+/// then memories.
+fn action_blocks(response: &str) -> Vec<ActionBlock> {
+    let (jobs, errors) = Agent::parse_cron_blocks(response);
+    let mut blocks: Vec<ActionBlock> = errors.into_iter().map(ActionBlock::CronError).collect();
+    blocks.extend(jobs.into_iter().map(ActionBlock::Cron));
+    blocks.extend(Agent::parse_save_blocks(response).into_iter().map(ActionBlock::Save));
+    blocks.extend(Agent::parse_memory_blocks(response).into_iter().map(ActionBlock::Memory));
+    blocks
+}
+
+/// The apology shown when the model fails. It is never stored, so the failure
+/// doesn't end up in future context.
+fn model_error(e: RustyClawError) -> Reply {
+    Reply {
+        text: format!("Sorry, I had trouble thinking about that. Error: {}", e),
+        actions: Vec::new(),
+    }
+}
+
+/// `text` cut to its first `max` characters, with `TRUNCATED` appended, if
+/// it is longer; otherwise `text` unchanged.
+///
+/// ```
+/// use rustyclaw::frontend::{truncate_reply, TRUNCATED};
+///
+/// // Cut at a character count, never inside a multi-byte character.
+/// assert_eq!(truncate_reply("héllo wörld", 7), format!("héllo w{}", TRUNCATED));
+/// assert_eq!(truncate_reply("🦀🦀🦀", 2), format!("🦀🦀{}", TRUNCATED));
+/// assert_eq!(truncate_reply("🦀🦀", 2), "🦀🦀");
+/// ```
+pub fn truncate_reply(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}{}", text[..end].trim_end(), TRUNCATED),
+        None => text.to_string(),
+    }
+}
+
+/// If `text` is a command typed with `prefix` (`command_prefix` in the
+/// config), the same command written with `/`, which is how the frontends
+/// parse commands. `None` for an ordinary message.
+///
+/// ```
+/// use rustyclaw::frontend::as_command;
+///
+/// assert_eq!(as_command("!save main.rs", "!").as_deref(), Some("/save main.rs"));
+/// assert_eq!(as_command("/status", "/").as_deref(), Some("/status"));
+/// assert_eq!(as_command("/status", "!"), None);
+/// assert_eq!(as_command("! not a command", "!"), None);
+/// ```
+pub fn as_command(text: &str, prefix: &str) -> Option<String> {
+    let rest = text.strip_prefix(prefix)?;
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(format!("/{}", rest))
+}
+
+/// Split `text` into pieces of at most `limit` bytes without breaking a UTF-8
+/// character, preferring to break at newlines.
+///
+/// ```
+/// use rustyclaw::frontend::chunk_text;
+///
+/// // One byte over the limit, with a two-byte character across the cut.
+/// let text = format!("{}é", "a".repeat(4095));
+/// assert_eq!(text.len(), 4097);
+/// let chunks = chunk_text(&text, 4096);
+/// assert_eq!(chunks, [&"a".repeat(4095)[..], "é"]);
+///
+/// assert_eq!(chunk_text("first line\nsecond", 15), ["first line\n", "second"]);
+/// assert_eq!(chunk_text(&"a".repeat(4096), 4096).len(), 1);
+///
+/// // A limit smaller than a character still moves on, one character at a time.
+/// assert_eq!(chunk_text("🦀🦀", 2), ["🦀", "🦀"]);
+/// ```
+pub fn chunk_text(text: &str, limit: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while rest.len() > limit {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        if let Some(newline) = rest[..end].rfind('\n').filter(|&i| i > 0) {
+            end = newline + 1;
+        }
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    if !rest.is_empty() {
+        chunks.push(rest);
+    }
+
+    chunks
+}
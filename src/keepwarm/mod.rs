@@ -0,0 +1,30 @@
+//! Background keep-warm: with `ollama.keepwarm_secs` set, the model is
+//! pinged whenever it has gone that long without a request, so Ollama keeps
+//! it loaded even with a short `keep_alive` and the next reply starts fast.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::agent::Agent;
+
+/// Ping `agent`'s model after every `interval` without a request. Runs
+/// until the task is dropped; a failed ping is logged and tried again an
+/// `interval` later.
+pub async fn run(agent: Arc<Agent>, interval: Duration) {
+    loop {
+        let wait = match agent.idle_for() {
+            Some(idle) if idle >= interval => {
+                match agent.ping().await {
+                    Ok(()) => debug!("Keep-warm ping sent"),
+                    Err(e) => warn!("Keep-warm ping failed: {}", e),
+                }
+                interval
+            }
+            Some(idle) => interval - idle,
+            // A request is running, which keeps the model loaded by itself.
+            None => interval,
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
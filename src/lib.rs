@@ -1,15 +1,231 @@
+pub mod error;
 pub mod config;
 pub mod memory;
 pub mod agent;
 pub mod scheduler;
 pub mod workspace;
 pub mod telegram;
+#[cfg(feature = "discord")]
+pub mod discord;
 pub mod tui;
+pub mod api;
+pub mod frontend;
+pub mod commands;
+pub mod metrics;
+pub mod tts;
+pub mod backup;
+pub mod summary;
+pub mod keepwarm;
+pub mod audit;
 
 pub use config::Config;
+pub use error::RustyClawError;
 pub use memory::Memory;
 pub use agent::Agent;
 pub use scheduler::Scheduler;
 pub use workspace::Workspace;
 
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+use error::Result;
+use futures::future::BoxFuture;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use audit::AuditLog;
+use frontend::{Context, Reply};
+
+type SendCallback = Arc<dyn Fn(String) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Memory, agent, workspace, and scheduler built from one `Config` and wired
+/// together, for using RustyClaw as a library.
+///
+/// ```no_run
+/// use rustyclaw::{Config, RustyClaw};
+///
+/// # async fn run() -> anyhow::Result<()> {
+/// let claw = RustyClaw::builder(Config::load_from_default()?)
+///     .with_send_callback(|msg| async move { println!("⏰ {}", msg) })
+///     .build()
+///     .await?;
+///
+/// let reply = claw.respond("Remind me to stretch every hour").await;
+/// println!("{}", reply.text);
+/// # Ok(())
+/// # }
+/// ```
+pub struct RustyClaw {
+    config: Config,
+    memory: Arc<Memory>,
+    agent: Arc<Agent>,
+    workspace: Arc<Workspace>,
+    scheduler: Arc<Scheduler>,
+    confirmations: Arc<frontend::Confirmations>,
+    undo: Arc<frontend::UndoLog>,
+}
+
+pub struct RustyClawBuilder {
+    config: Config,
+    warm_up: bool,
+    callbacks: Vec<SendCallback>,
+}
+
+impl RustyClaw {
+    pub fn builder(config: Config) -> RustyClawBuilder {
+        RustyClawBuilder {
+            config,
+            warm_up: true,
+            callbacks: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn memory(&self) -> &Arc<Memory> {
+        &self.memory
+    }
+
+    pub fn agent(&self) -> &Arc<Agent> {
+        &self.agent
+    }
+
+    pub fn workspace(&self) -> &Arc<Workspace> {
+        &self.workspace
+    }
+
+    pub fn scheduler(&self) -> &Arc<Scheduler> {
+        &self.scheduler
+    }
+
+    /// The components bundled for `frontend::Context::respond` and command handlers.
+    pub fn context(&self) -> Context {
+        Context {
+            agent: self.agent.clone(),
+            memory: self.memory.clone(),
+            scheduler: self.scheduler.clone(),
+            workspace: self.workspace.clone(),
+            max_history: self.config.memory.max_history,
+            confirmations: self.confirmations.clone(),
+            undo: self.undo.clone(),
+            user: None,
+        }
+    }
+
+    /// Answer one message through the same pipeline the frontends use.
+    pub async fn respond(&self, user_text: &str) -> Reply {
+        self.context().respond(user_text).await
+    }
+}
+
+impl RustyClawBuilder {
+    /// Call `callback` with the message of every cron job that fires. May be
+    /// given more than once; more can be added later through `Scheduler`.
+    pub fn with_send_callback<F, Fut>(mut self, callback: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.callbacks.push(Arc::new(move |msg| Box::pin(callback(msg))));
+        self
+    }
+
+    /// Skip loading the model into memory at build time (on by default).
+    pub fn with_warm_up(mut self, warm_up: bool) -> Self {
+        self.warm_up = warm_up;
+        self
+    }
+
+    /// Connect the database, load memory and cron jobs, and start the scheduler.
+    pub async fn build(self) -> Result<RustyClaw> {
+        let config = self.config;
+
+        let memory = Arc::new(Memory::connect(&config.memory).await?);
+        info!("Database connected: {:?}", config.memory.database);
+
+        match memory.backfill_models(&config.ollama.model).await {
+            Ok(0) => {}
+            Ok(n) => info!("Attributed {} earlier replies to {}", n, config.ollama.model),
+            Err(e) => warn!("Failed to record models for earlier replies: {}", e),
+        }
+
+        let mut agent = Agent::new(config.ollama.clone(), config.system_prompt.clone())
+            .with_memory_file(config.memory.facts_file.clone())
+            .with_max_memory_lines(config.memory.max_memory_lines)
+            .with_store(memory.as_ref().clone())
+            .with_scheduling(config.scheduler.enabled);
+        if config.audit.enabled {
+            agent = agent.with_audit(AuditLog::start(&config.audit));
+        }
+        let agent = Arc::new(agent);
+        if self.warm_up {
+            agent.warm_up().await?;
+        }
+
+        let workspace = Arc::new(Workspace::new(config.workspace.clone(), memory.as_ref().clone())?);
+        info!("Workspace: {:?}", workspace.path());
+
+        let scheduler = Arc::new(
+            Scheduler::new(memory.as_ref().clone(), config.scheduler.enabled)
+                .with_jitter(Duration::from_secs(config.scheduler.jitter_secs)),
+        );
+        for callback in self.callbacks {
+            scheduler.add_send_callback(move |msg| callback(msg)).await;
+        }
+        let reply_agent = agent.clone();
+        scheduler
+            .set_reply_callback(move |job| {
+                let agent = reply_agent.clone();
+                async move { job_reply(&agent, &job).await }
+            })
+            .await;
+        let output_workspace = workspace.clone();
+        scheduler
+            .set_output_callback(move |job, filename, reply| {
+                let workspace = output_workspace.clone();
+                async move { save_job_output(&workspace, &job, &filename, &reply).await }
+            })
+            .await;
+        scheduler.load_jobs().await?;
+
+        Ok(RustyClaw {
+            config,
+            memory,
+            agent,
+            workspace,
+            scheduler,
+            confirmations: Default::default(),
+            undo: Default::default(),
+        })
+    }
+}
+
+/// Ask the model for `job`'s message on its own, outside the conversation,
+/// and return the cleaned reply.
+async fn job_reply(agent: &Agent, job: &memory::CronJob) -> Option<String> {
+    let prompt = memory::Message {
+        role: "user".to_string(),
+        content: job.message.clone(),
+        timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        model: None,
+    };
+    match agent.chat(&[prompt]).await {
+        Ok(response) => Some(Agent::clean_response(&agent.strip_thinking(&response).await)),
+        Err(e) => {
+            warn!("Job #{} got no reply, output not saved: {}", job.id, e);
+            None
+        }
+    }
+}
+
+/// Save the reply to a run of `job` to the workspace as `filename`.
+async fn save_job_output(workspace: &Workspace, job: &memory::CronJob, filename: &str, reply: &str) {
+    let description = format!("Output of job #{}: {}", job.id, job.task);
+    match workspace.save_file(filename, reply, None, Some(&description)).await {
+        Ok(saved) => info!("Saved job #{} output to {}", job.id, saved.name()),
+        Err(e) => warn!("Failed to save job #{} output to {}: {}", job.id, filename, e),
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use sqlx::sqlite::SqlitePool;
+use sqlx::{Row, Sqlite, Transaction};
+use tracing::info;
+
+/// A single schema change. Every step must be safe to re-run against a
+/// database that already has it, since pre-versioning databases start at 0.
+enum Step {
+    Sql(&'static str),
+    AddColumn {
+        table: &'static str,
+        column: &'static str,
+        definition: &'static str,
+    },
+}
+
+struct Migration {
+    version: i64,
+    name: &'static str,
+    steps: &'static [Step],
+}
+
+/// Ordered list of migrations. Append new entries; never edit applied ones.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS conversations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS cron_jobs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id INTEGER NOT NULL,
+                schedule TEXT NOT NULL,
+                task TEXT NOT NULL,
+                message TEXT NOT NULL,
+                enabled INTEGER DEFAULT 1,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE TABLE IF NOT EXISTS workspace_files (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                filename TEXT NOT NULL,
+                description TEXT,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+            "#,
+        )],
+    },
+    Migration {
+        version: 2,
+        name: "workspace file language",
+        steps: &[Step::AddColumn {
+            table: "workspace_files",
+            column: "language",
+            definition: "TEXT",
+        }],
+    },
+    Migration {
+        version: 3,
+        name: "memory embeddings",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS memory_embeddings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                fact TEXT NOT NULL,
+                model TEXT NOT NULL,
+                embedding TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (fact, model)
+            );
+            "#,
+        )],
+    },
+    Migration {
+        version: 4,
+        name: "chat settings",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS chat_settings (
+                chat_id INTEGER PRIMARY KEY,
+                voice_replies INTEGER NOT NULL DEFAULT 0
+            );
+            "#,
+        )],
+    },
+    Migration {
+        version: 5,
+        name: "message model",
+        steps: &[Step::AddColumn {
+            table: "conversations",
+            column: "model",
+            definition: "TEXT",
+        }],
+    },
+    Migration {
+        version: 6,
+        name: "chat sessions",
+        steps: &[
+            Step::Sql(
+                r#"
+                CREATE TABLE IF NOT EXISTS sessions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    name TEXT NOT NULL UNIQUE,
+                    active INTEGER NOT NULL DEFAULT 0,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+
+                INSERT OR IGNORE INTO sessions (id, name, active) VALUES (1, 'main', 1);
+                "#,
+            ),
+            Step::AddColumn {
+                table: "conversations",
+                column: "session_id",
+                definition: "INTEGER NOT NULL DEFAULT 1",
+            },
+            Step::Sql("CREATE INDEX IF NOT EXISTS idx_conversations_session ON conversations (session_id, id)"),
+        ],
+    },
+    Migration {
+        version: 7,
+        name: "one-time and targeted jobs",
+        steps: &[
+            Step::AddColumn {
+                table: "cron_jobs",
+                column: "once",
+                definition: "INTEGER NOT NULL DEFAULT 0",
+            },
+            Step::AddColumn {
+                table: "cron_jobs",
+                column: "chat_id",
+                definition: "INTEGER",
+            },
+        ],
+    },
+    Migration {
+        version: 8,
+        name: "session settings",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS session_settings (
+                session_id INTEGER PRIMARY KEY,
+                model TEXT,
+                temperature REAL,
+                persona TEXT
+            );
+            "#,
+        )],
+    },
+    Migration {
+        version: 9,
+        name: "job output files",
+        steps: &[Step::AddColumn {
+            table: "cron_jobs",
+            column: "output",
+            definition: "TEXT",
+        }],
+    },
+    Migration {
+        version: 10,
+        name: "pinned messages",
+        steps: &[Step::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS pinned (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER NOT NULL,
+                content TEXT NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_pinned_session ON pinned(session_id);
+            "#,
+        )],
+    },
+];
+
+/// Bring the database up to the latest schema version, one transaction per migration.
+pub async fn run(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    let current: i64 = sqlx::query("SELECT COALESCE(MAX(version), 0) AS version FROM schema_version")
+        .fetch_one(pool)
+        .await?
+        .get("version");
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut tx = pool.begin().await?;
+        for step in migration.steps {
+            apply(&mut tx, step)
+                .await
+                .with_context(|| format!("Migration {} ({}) failed", migration.version, migration.name))?;
+        }
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        info!("Applied migration {}: {}", migration.version, migration.name);
+    }
+
+    Ok(())
+}
+
+async fn apply(tx: &mut Transaction<'_, Sqlite>, step: &Step) -> Result<()> {
+    match step {
+        Step::Sql(sql) => {
+            sqlx::query(sql).execute(&mut **tx).await?;
+        }
+        Step::AddColumn { table, column, definition } => {
+            let columns = sqlx::query(&format!("PRAGMA table_info({})", table))
+                .fetch_all(&mut **tx)
+                .await?;
+
+            if !columns.iter().any(|row| row.get::<String, _>("name") == *column) {
+                sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+                    .execute(&mut **tx)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
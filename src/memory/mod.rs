@@ -1,14 +1,177 @@
-use anyhow::Result;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use anyhow::anyhow;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use sqlx::query::Query;
+use sqlx::sqlite::{
+    Sqlite, SqliteArguments, SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions,
+    SqliteQueryResult,
+};
 use sqlx::Row;
-use std::path::Path;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::MemoryConfig;
+use crate::error::{Result, RustyClawError};
+
+mod migrations;
 
 const USER_ID: i64 = 1;
+const EXPORT_LIMIT: usize = 10_000;
+/// Extra attempts for a write that hits `SQLITE_BUSY` after the busy timeout.
+const BUSY_RETRIES: u32 = 3;
+const BUSY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A write that stayed locked through every retry. Its message is meant to be
+/// shown to the user as-is.
+#[derive(Debug)]
+pub struct DatabaseBusy;
+
+impl fmt::Display for DatabaseBusy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The database is busy right now, please try again in a moment.")
+    }
+}
+
+impl std::error::Error for DatabaseBusy {}
+
+fn is_busy(e: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db) = e else {
+        return false;
+    };
+    // SQLITE_BUSY (5) and SQLITE_LOCKED (6), including their extended codes.
+    let code = db.code().and_then(|c| c.parse::<i32>().ok()).unwrap_or_default();
+    matches!(code & 0xff, 5 | 6)
+}
+
+/// Run a write, retrying with backoff while SQLite reports the database as
+/// locked, and turn a lock that never clears into `DatabaseBusy`.
+///
+/// Each attempt runs in its own transaction. A statement that fails with
+/// `SQLITE_BUSY` while committing leaves its autocommit transaction open on
+/// the pooled connection, so without one the next attempt would commit the
+/// failed insert along with its own.
+async fn retry_busy<'q, F>(pool: &SqlitePool, mut write: F) -> anyhow::Result<SqliteQueryResult>
+where
+    F: FnMut() -> Query<'q, Sqlite, SqliteArguments<'q>>,
+{
+    let mut attempt = 0;
+    loop {
+        let result = async {
+            let mut tx = pool.begin().await?;
+            let result = write().execute(&mut *tx).await?;
+            tx.commit().await?;
+            Ok::<_, sqlx::Error>(result)
+        }
+        .await;
+        match result {
+            Ok(value) => return Ok(value),
+            Err(e) if is_busy(&e) && attempt < BUSY_RETRIES => {
+                attempt += 1;
+                warn!("Database busy, retrying write ({}/{})", attempt, BUSY_RETRIES);
+                tokio::time::sleep(BUSY_BACKOFF * attempt).await;
+            }
+            Err(e) if is_busy(&e) => {
+                warn!("Database still busy after {} retries: {}", BUSY_RETRIES, e);
+                return Err(DatabaseBusy.into());
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Message {
     pub role: String,
     pub content: String,
+    /// SQLite `CURRENT_TIMESTAMP` format, in UTC: `YYYY-MM-DD HH:MM:SS`.
+    pub timestamp: String,
+    /// Model that wrote an assistant message; `None` for user messages.
+    pub model: Option<String>,
+}
+
+impl Message {
+    /// The stored UTC timestamp converted to local time, if it parses.
+    pub fn local_time(&self) -> Option<DateTime<Local>> {
+        NaiveDateTime::parse_from_str(&self.timestamp, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .map(|t| t.and_utc().with_timezone(&Local))
+    }
+}
+
+/// A message found by `Memory::search_history`.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub message: Message,
+    /// Name of the session it was said in.
+    pub session: String,
+    /// The message before it in that session, for context.
+    pub previous: Option<Message>,
+}
+
+impl fmt::Display for SearchHit {
+    /// `[session MM-DD HH:MM] role: content`, then the message before it.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self
+            .message
+            .local_time()
+            .map(|t| t.format("%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| self.message.timestamp.clone());
+        let preview: String = self.message.content.chars().take(200).collect();
+        write!(f, "[{} {}] {}: {}", self.session, time, self.message.role, preview)?;
+        if let Some(previous) = &self.previous {
+            let preview: String = previous.content.chars().take(80).collect();
+            write!(f, "\n    after {}: {}", previous.role, preview)?;
+        }
+        Ok(())
+    }
+}
+
+fn message_from_row(row: &sqlx::sqlite::SqliteRow) -> Message {
+    Message {
+        role: row.get("role"),
+        content: row.get("content"),
+        timestamp: row.get("timestamp"),
+        model: row.get("model"),
+    }
+}
+
+/// What `Memory::import_history` added to the history.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    /// Entries left out because they were malformed.
+    pub skipped: usize,
+}
+
+/// An imported entry's timestamp in the stored UTC format, from RFC 3339 or
+/// from `YYYY-MM-DD HH:MM:SS` (taken as UTC).
+fn import_timestamp(value: &str) -> Option<String> {
+    let utc = DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc).naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S"))
+        .ok()?;
+    Some(utc.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// `messages` as plain text, one `[timestamp] role (model): content` entry
+/// per message, with timestamps in local time.
+pub fn format_transcript(messages: &[Message]) -> String {
+    let entries: Vec<String> = messages
+        .iter()
+        .map(|m| {
+            let time = m
+                .local_time()
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| m.timestamp.clone());
+            match &m.model {
+                Some(model) => format!("[{}] {} ({}): {}", time, m.role, model, m.content),
+                None => format!("[{}] {}: {}", time, m.role, m.content),
+            }
+        })
+        .collect();
+
+    entries.join("\n\n")
 }
 
 #[derive(Debug, Clone)]
@@ -18,80 +181,191 @@ pub struct CronJob {
     pub task: String,
     pub message: String,
     pub enabled: bool,
+    /// Cancelled after it first fires.
+    pub once: bool,
+    /// Telegram chat the message goes to, instead of every send callback.
+    pub chat_id: Option<i64>,
+    /// Workspace file name template the reply to each run is saved under.
+    pub output: Option<String>,
+}
+
+/// A named conversation thread. History commands act on the active one.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub name: String,
+    pub messages: i64,
+    pub active: bool,
+}
+
+/// Per-session overrides of the global Ollama config. `None` falls back to it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionSettings {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    /// Replaces the base system prompt (`soul.md`); memory is still added.
+    pub persona: Option<String>,
+}
+
+/// Text kept in the active session's prompt with `/pin`.
+#[derive(Debug, Clone)]
+pub struct Pin {
+    pub id: i64,
+    pub content: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct WorkspaceFile {
     pub filename: String,
     pub description: Option<String>,
+    pub language: Option<String>,
     pub created_at: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct Memory {
     pool: SqlitePool,
+    path: PathBuf,
+    fts: bool,
+    dedupe: bool,
+    max_pinned_chars: usize,
 }
 
 impl Memory {
-    pub async fn connect(db_path: &Path) -> Result<Self> {
-        let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
-        
+    pub async fn connect(config: &MemoryConfig) -> Result<Self> {
+        // WAL lets readers proceed alongside the single writer, so the pool can
+        // be shared by Telegram, the TUI, and the scheduler without serializing.
+        let options = SqliteConnectOptions::new()
+            .filename(&config.database)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(Duration::from_millis(config.busy_timeout_ms));
+
         let pool = SqlitePoolOptions::new()
-            .max_connections(1)
-            .connect(&db_url)
+            .max_connections(config.pool_size)
+            .connect_with(options)
             .await?;
 
+        migrations::run(&pool).await.map_err(RustyClawError::Database)?;
+
+        let mut memory = Self {
+            pool,
+            path: config.database.clone(),
+            fts: false,
+            dedupe: config.dedupe,
+            max_pinned_chars: config.max_pinned_chars,
+        };
+
+        memory.fts = match memory.create_fts_index().await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Full-text search unavailable, falling back to LIKE: {}", e);
+                false
+            }
+        };
+
+        Ok(memory)
+    }
+
+    /// Mirror `conversations.content` into an FTS5 index kept in sync by triggers.
+    async fn create_fts_index(&self) -> anyhow::Result<()> {
+        let exists = sqlx::query("SELECT 1 FROM sqlite_master WHERE name = 'conversations_fts'")
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS conversations (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id INTEGER NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                timestamp DATETIME DEFAULT CURRENT_TIMESTAMP
-            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS conversations_fts
+                USING fts5(content, content='conversations', content_rowid='id');
 
-            CREATE TABLE IF NOT EXISTS cron_jobs (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                user_id INTEGER NOT NULL,
-                schedule TEXT NOT NULL,
-                task TEXT NOT NULL,
-                message TEXT NOT NULL,
-                enabled INTEGER DEFAULT 1,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            );
+            CREATE TRIGGER IF NOT EXISTS conversations_fts_insert AFTER INSERT ON conversations BEGIN
+                INSERT INTO conversations_fts(rowid, content) VALUES (new.id, new.content);
+            END;
 
-            CREATE TABLE IF NOT EXISTS workspace_files (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                filename TEXT NOT NULL,
-                description TEXT,
-                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
-            );
+            CREATE TRIGGER IF NOT EXISTS conversations_fts_delete AFTER DELETE ON conversations BEGIN
+                INSERT INTO conversations_fts(conversations_fts, rowid, content)
+                    VALUES ('delete', old.id, old.content);
+            END;
             "#,
         )
-        .execute(&pool)
+        .execute(&self.pool)
         .await?;
 
-        Ok(Self { pool })
+        if !exists {
+            sqlx::query("INSERT INTO conversations_fts(conversations_fts) VALUES ('rebuild')")
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
     }
 
-    pub async fn add_message(&self, role: &str, content: &str) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO conversations (user_id, role, content) VALUES (?, ?, ?)",
-        )
-        .bind(USER_ID)
-        .bind(role)
-        .bind(content)
-        .execute(&self.pool)
-        .await?;
+    /// Store a message. `model` is the model that generated an assistant reply.
+    /// With `memory.dedupe`, a message identical in role and content to the
+    /// latest one in the session is skipped.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::MemoryConfig;
+    /// use rustyclaw::memory::Memory;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-dedupe-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("dedupe.db"), dedupe: true, ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    ///
+    /// memory.add_message("user", "Remind me at 9", None).await.unwrap();
+    /// memory.add_message("user", "Remind me at 9", None).await.unwrap();
+    /// assert_eq!(memory.get_history(10).await.unwrap().len(), 1);
+    ///
+    /// // Only an identical message right before it counts.
+    /// memory.add_message("assistant", "Remind me at 9", None).await.unwrap();
+    /// memory.add_message("user", "Remind me at 9", None).await.unwrap();
+    /// assert_eq!(memory.get_history(10).await.unwrap().len(), 3);
+    /// # memory.close().await;
+    ///
+    /// // Without memory.dedupe, every message is stored.
+    /// let config = MemoryConfig { database: dir.join("plain.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    /// memory.add_message("user", "Remind me at 9", None).await.unwrap();
+    /// memory.add_message("user", "Remind me at 9", None).await.unwrap();
+    /// assert_eq!(memory.get_history(10).await.unwrap().len(), 2);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn add_message(&self, role: &str, content: &str, model: Option<&str>) -> Result<()> {
+        let result = retry_busy(&self.pool, || {
+            sqlx::query(
+                "INSERT INTO conversations (user_id, role, content, model, session_id) \
+                 SELECT ?, ?, ?, ?, (SELECT id FROM sessions WHERE active = 1) \
+                 WHERE NOT (? AND EXISTS (SELECT 1 FROM (SELECT role, content FROM conversations \
+                     WHERE user_id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1) \
+                     ORDER BY id DESC LIMIT 1) WHERE role = ? AND content = ?))",
+            )
+                .bind(USER_ID)
+                .bind(role)
+                .bind(content)
+                .bind(model)
+                .bind(self.dedupe)
+                .bind(USER_ID)
+                .bind(role)
+                .bind(content)
+        })
+        .await
+        .map_err(RustyClawError::Database)?;
 
+        if result.rows_affected() == 0 {
+            debug!("Skipped a {} message identical to the previous one", role);
+        }
         Ok(())
     }
 
     pub async fn get_history(&self, limit: usize) -> Result<Vec<Message>> {
         let rows = sqlx::query(
-            "SELECT role, content FROM conversations \
-             WHERE user_id = ? ORDER BY id DESC LIMIT ?",
+            "SELECT role, content, timestamp, model FROM conversations \
+             WHERE user_id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1) \
+             ORDER BY id DESC LIMIT ?",
         )
         .bind(USER_ID)
         .bind(limit as i64)
@@ -103,6 +377,8 @@ impl Memory {
             .map(|row| Message {
                 role: row.get("role"),
                 content: row.get("content"),
+                timestamp: row.get("timestamp"),
+                model: row.get("model"),
             })
             .collect();
 
@@ -110,32 +386,524 @@ impl Memory {
         Ok(messages)
     }
 
-    pub async fn clear_history(&self) -> Result<()> {
-        sqlx::query("DELETE FROM conversations WHERE user_id = ?")
+    /// Messages in the active session stored at or after `since`, a UTC
+    /// timestamp in the stored `YYYY-MM-DD HH:MM:SS` format, oldest first.
+    pub async fn history_since(&self, since: &str) -> Result<Vec<Message>> {
+        let rows = sqlx::query(
+            "SELECT role, content, timestamp, model FROM conversations \
+             WHERE user_id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1) \
+             AND timestamp >= ? ORDER BY id",
+        )
+        .bind(USER_ID)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Message {
+                role: row.get("role"),
+                content: row.get("content"),
+                timestamp: row.get("timestamp"),
+                model: row.get("model"),
+            })
+            .collect())
+    }
+
+    /// The conversation as plain text, in the format of `format_transcript`.
+    pub async fn export_transcript(&self) -> Result<String> {
+        let messages = self.get_history(EXPORT_LIMIT).await?;
+        Ok(format_transcript(&messages))
+    }
+
+    /// Append the messages in `json` to the active session, in order and in
+    /// one transaction. `json` is an array of `{"role", "content",
+    /// "timestamp"}` objects, or an object with such an array under
+    /// `"messages"` as in an Ollama chat request. `timestamp` (RFC 3339 or
+    /// `YYYY-MM-DD HH:MM:SS` UTC) and `model` are optional. Entries with a
+    /// role other than user, assistant, or system, empty content, or a bad
+    /// timestamp are skipped and counted.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::MemoryConfig;
+    /// use rustyclaw::memory::Memory;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-import-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("import.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    ///
+    /// let summary = memory.import_history(r#"[
+    ///     {"role": "user", "content": "Hi!", "timestamp": "2024-05-01 09:30:00"},
+    ///     {"role": "assistant", "content": "Hello.", "timestamp": "2024-05-01T09:30:05Z"},
+    ///     {"role": "narrator", "content": "Unknown role"},
+    ///     {"role": "user"}
+    /// ]"#).await.unwrap();
+    /// assert_eq!((summary.imported, summary.skipped), (2, 2));
+    ///
+    /// let history = memory.get_history(10).await.unwrap();
+    /// assert_eq!(history.len(), 2);
+    /// assert_eq!(history[1].timestamp, "2024-05-01 09:30:05");
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn import_history(&self, json: &str) -> Result<ImportSummary> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| RustyClawError::Database(anyhow!("Not valid JSON: {}", e)))?;
+        let entries = match &value {
+            serde_json::Value::Array(entries) => entries,
+            serde_json::Value::Object(object) => match object.get("messages") {
+                Some(serde_json::Value::Array(entries)) => entries,
+                _ => return Err(RustyClawError::Database(anyhow!("Expected a \"messages\" array"))),
+            },
+            _ => return Err(RustyClawError::Database(anyhow!("Expected an array of messages"))),
+        };
+
+        let mut summary = ImportSummary::default();
+        let mut tx = self.pool.begin().await?;
+        for entry in entries {
+            let role = entry["role"].as_str().unwrap_or_default();
+            let content = entry["content"].as_str().unwrap_or_default();
+            let timestamp = entry["timestamp"].as_str().map(import_timestamp);
+            if !matches!(role, "user" | "assistant" | "system") || content.trim().is_empty() || timestamp == Some(None) {
+                summary.skipped += 1;
+                continue;
+            }
+
+            sqlx::query(
+                "INSERT INTO conversations (user_id, role, content, model, session_id, timestamp) \
+                 VALUES (?, ?, ?, ?, (SELECT id FROM sessions WHERE active = 1), COALESCE(?, CURRENT_TIMESTAMP))",
+            )
             .bind(USER_ID)
-            .execute(&self.pool)
+            .bind(role)
+            .bind(content)
+            .bind(entry["model"].as_str())
+            .bind(timestamp.flatten())
+            .execute(&mut *tx)
+            .await?;
+            summary.imported += 1;
+        }
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+
+    /// Attribute assistant messages stored before models were recorded to
+    /// `model`. Returns how many were updated.
+    pub async fn backfill_models(&self, model: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "UPDATE conversations SET model = ? WHERE user_id = ? AND role = 'assistant' AND model IS NULL",
+        )
+        .bind(model)
+        .bind(USER_ID)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Messages in any session whose content holds every word in `query`,
+    /// in any order and ignoring case, newest first. Each comes with its
+    /// session and the message before it.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::MemoryConfig;
+    /// use rustyclaw::memory::Memory;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-search-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("search.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    ///
+    /// memory.add_message("user", "How do I restart a compose stack?", None).await.unwrap();
+    /// memory.add_message("assistant", "Run `docker compose restart`.", None).await.unwrap();
+    /// memory.create_session("work").await.unwrap();
+    /// memory.switch_session("work").await.unwrap();
+    /// memory.add_message("user", "Compose files for Docker live in ops/, 100% of them", None).await.unwrap();
+    ///
+    /// // Every word must match, in any order, whether or not FTS5 is available.
+    /// let hits = memory.search_history("docker compose", 10).await.unwrap();
+    /// assert_eq!(hits.len(), 2);
+    /// assert_eq!(hits[0].session, "work");
+    /// assert!(hits[0].previous.is_none());
+    /// assert_eq!(hits[1].session, "main");
+    /// assert_eq!(hits[1].message.content, "Run `docker compose restart`.");
+    /// assert_eq!(hits[1].previous.as_ref().unwrap().content, "How do I restart a compose stack?");
+    /// assert!(!hits[1].message.timestamp.is_empty());
+    ///
+    /// assert_eq!(memory.search_history("docker kubernetes", 10).await.unwrap().len(), 0);
+    /// // Quotes, operators and wildcards are words like any other.
+    /// assert_eq!(memory.search_history("\"docker\" OR *", 10).await.unwrap().len(), 0);
+    /// assert_eq!(memory.search_history("100%", 10).await.unwrap().len(), 1);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn search_history(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let words: Vec<&str> = query.split_whitespace().collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let rows = if self.fts {
+            // Quote each word so FTS operators and punctuation are matched literally.
+            let fts_query = words
+                .iter()
+                .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            sqlx::query(
+                "SELECT c.id, c.session_id, s.name AS session, c.role, c.content, c.timestamp, c.model \
+                 FROM conversations_fts f \
+                 JOIN conversations c ON c.id = f.rowid \
+                 JOIN sessions s ON s.id = c.session_id \
+                 WHERE conversations_fts MATCH ? AND c.user_id = ? \
+                 ORDER BY c.id DESC LIMIT ?",
+            )
+            .bind(fts_query)
+            .bind(USER_ID)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            // One LIKE per word, so words match in any order as with FTS.
+            let sql = format!(
+                "SELECT c.id, c.session_id, s.name AS session, c.role, c.content, c.timestamp, c.model \
+                 FROM conversations c JOIN sessions s ON s.id = c.session_id \
+                 WHERE c.user_id = ? AND {} \
+                 ORDER BY c.id DESC LIMIT ?",
+                vec!["c.content LIKE ? ESCAPE '\\'"; words.len()].join(" AND ")
+            );
+            let mut search = sqlx::query(&sql).bind(USER_ID);
+            for word in &words {
+                search = search.bind(format!(
+                    "%{}%",
+                    word.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+                ));
+            }
+            search.bind(limit as i64).fetch_all(&self.pool).await?
+        };
+
+        let mut hits = Vec::with_capacity(rows.len());
+        for row in rows {
+            let previous = sqlx::query(
+                "SELECT role, content, timestamp, model FROM conversations \
+                 WHERE user_id = ? AND session_id = ? AND id < ? \
+                 ORDER BY id DESC LIMIT 1",
+            )
+            .bind(USER_ID)
+            .bind(row.get::<i64, _>("session_id"))
+            .bind(row.get::<i64, _>("id"))
+            .fetch_optional(&self.pool)
             .await?;
 
+            hits.push(SearchHit {
+                session: row.get("session"),
+                message: message_from_row(&row),
+                previous: previous.as_ref().map(message_from_row),
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Delete messages older than `days`, always keeping the newest `keep_recent`
+    /// of each session so a quiet spell doesn't wipe its working context.
+    /// Returns rows deleted.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::MemoryConfig;
+    /// use rustyclaw::memory::Memory;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-prune-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("prune.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    ///
+    /// memory.import_history(r#"[
+    ///     {"role": "user", "content": "Old 1", "timestamp": "2020-01-01 09:00:00"},
+    ///     {"role": "assistant", "content": "Old 2", "timestamp": "2020-01-01 09:00:05"},
+    ///     {"role": "user", "content": "Old 3", "timestamp": "2020-01-02 09:00:00"}
+    /// ]"#).await.unwrap();
+    /// memory.add_message("assistant", "New", None).await.unwrap();
+    ///
+    /// // A session nobody has used in years keeps its newest messages too.
+    /// memory.create_session("quiet").await.unwrap();
+    /// memory.switch_session("quiet").await.unwrap();
+    /// memory.import_history(r#"[
+    ///     {"role": "user", "content": "Quiet 1", "timestamp": "2020-01-03 09:00:00"},
+    ///     {"role": "assistant", "content": "Quiet 2", "timestamp": "2020-01-03 09:00:05"}
+    /// ]"#).await.unwrap();
+    ///
+    /// assert_eq!(memory.prune_older_than(30, 2).await.unwrap(), 2);
+    /// let contents = |history: Vec<rustyclaw::memory::Message>| {
+    ///     history.into_iter().map(|m| m.content).collect::<Vec<_>>()
+    /// };
+    /// assert_eq!(contents(memory.get_history(10).await.unwrap()), ["Quiet 1", "Quiet 2"]);
+    /// memory.switch_session("main").await.unwrap();
+    /// assert_eq!(contents(memory.get_history(10).await.unwrap()), ["Old 3", "New"]);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn prune_older_than(&self, days: u32, keep_recent: usize) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM conversations \
+             WHERE user_id = ? AND timestamp < datetime('now', ?) \
+             AND id NOT IN (SELECT id FROM (\
+                 SELECT id, ROW_NUMBER() OVER (PARTITION BY session_id ORDER BY id DESC) AS recent \
+                 FROM conversations WHERE user_id = ?\
+             ) WHERE recent <= ?)",
+        )
+        .bind(USER_ID)
+        .bind(format!("-{} days", days))
+        .bind(USER_ID)
+        .bind(keep_recent as i64)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn clear_history(&self) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM conversations \
+             WHERE user_id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1)",
+        )
+        .bind(USER_ID)
+        .execute(&self.pool)
+        .await?;
+
         Ok(())
     }
 
-    pub async fn add_cron_job(&self, schedule: &str, task: &str, message: &str) -> Result<i64> {
+    /// Delete the newest message of the active session if it is an assistant
+    /// reply. Returns whether one was deleted.
+    pub async fn remove_last_reply(&self) -> Result<bool> {
         let result = sqlx::query(
-            "INSERT INTO cron_jobs (user_id, schedule, task, message) VALUES (?, ?, ?, ?)",
+            "DELETE FROM conversations WHERE role = 'assistant' AND id = \
+             (SELECT id FROM conversations \
+              WHERE user_id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1) \
+              ORDER BY id DESC LIMIT 1)",
         )
         .bind(USER_ID)
-        .bind(schedule)
-        .bind(task)
-        .bind(message)
         .execute(&self.pool)
         .await?;
 
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Name of the session new messages go to.
+    pub async fn active_session(&self) -> Result<String> {
+        let row = sqlx::query("SELECT name FROM sessions WHERE active = 1")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get("name"))
+    }
+
+    /// Create an empty session. Returns `false` if the name is taken.
+    pub async fn create_session(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query("INSERT OR IGNORE INTO sessions (name) VALUES (?)")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Make `name` the active session. Returns `false` if it doesn't exist.
+    pub async fn switch_session(&self, name: &str) -> Result<bool> {
+        let exists = sqlx::query("SELECT 1 FROM sessions WHERE name = ?")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?
+            .is_some();
+
+        if exists {
+            sqlx::query("UPDATE sessions SET active = (name = ?)")
+                .bind(name)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(exists)
+    }
+
+    /// The active session's overrides; all `None` if it has none.
+    pub async fn session_settings(&self) -> Result<SessionSettings> {
+        let row = sqlx::query(
+            "SELECT model, temperature, persona FROM session_settings \
+             WHERE session_id = (SELECT id FROM sessions WHERE active = 1)",
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row
+            .map(|row| SessionSettings {
+                model: row.get("model"),
+                temperature: row.get::<Option<f64>, _>("temperature").map(|t| t as f32),
+                persona: row.get("persona"),
+            })
+            .unwrap_or_default())
+    }
+
+    /// Replace the active session's overrides with `settings`.
+    pub async fn set_session_settings(&self, settings: &SessionSettings) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO session_settings (session_id, model, temperature, persona) \
+             VALUES ((SELECT id FROM sessions WHERE active = 1), ?, ?, ?) \
+             ON CONFLICT(session_id) DO UPDATE SET model = excluded.model, \
+             temperature = excluded.temperature, persona = excluded.persona",
+        )
+        .bind(&settings.model)
+        .bind(settings.temperature.map(f64::from))
+        .bind(&settings.persona)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Pin `content` to the active session. Returns its id, or `None` if it
+    /// would take the session's pins past `memory.max_pinned_chars`.
+    ///
+    /// Pins are sent right after the system prompt, and the history is
+    /// trimmed to fit around them, so they are never dropped:
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::{MemoryConfig, OllamaConfig};
+    /// use rustyclaw::memory::Memory;
+    /// use rustyclaw::Agent;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-pin-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("pin.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    ///
+    /// memory.add_message("user", "Always answer in French.", None).await.unwrap();
+    /// memory.add_pin("Always answer in French.").await.unwrap();
+    /// for i in 0..50 {
+    ///     memory.add_message("user", &format!("Message {} {}", i, "padding ".repeat(20)), None).await.unwrap();
+    /// }
+    ///
+    /// let ollama = OllamaConfig { context_length: 512, ..Default::default() };
+    /// let agent = Agent::new(ollama, "You are helpful.".into())
+    ///     .with_memory_file(dir.join("memory.md"))
+    ///     .with_store(memory.clone());
+    /// let history = memory.get_history(100).await.unwrap();
+    /// let preview = agent.context_preview(&history).await;
+    /// assert!(preview.len() < history.len());
+    /// assert!(preview[1].1.contains("Always answer in French."));
+    /// assert!(preview[2..].iter().all(|(_, content)| !content.contains("French")));
+    ///
+    /// assert!(memory.add_pin(&"x".repeat(config.max_pinned_chars)).await.unwrap().is_none());
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn add_pin(&self, content: &str) -> Result<Option<i64>> {
+        let pinned: usize = self.pins().await?.iter().map(|p| p.content.chars().count()).sum();
+        if pinned + content.chars().count() > self.max_pinned_chars {
+            return Ok(None);
+        }
+
+        let result = sqlx::query("INSERT INTO pinned (session_id, content) VALUES ((SELECT id FROM sessions WHERE active = 1), ?)")
+            .bind(content)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(Some(result.last_insert_rowid()))
+    }
+
+    /// The active session's pins, oldest first.
+    pub async fn pins(&self) -> Result<Vec<Pin>> {
+        let rows = sqlx::query(
+            "SELECT id, content FROM pinned \
+             WHERE session_id = (SELECT id FROM sessions WHERE active = 1) ORDER BY id",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Pin {
+                id: row.get("id"),
+                content: row.get("content"),
+            })
+            .collect())
+    }
+
+    /// Unpin `id` from the active session. Returns `false` if it has no such pin.
+    pub async fn remove_pin(&self, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM pinned WHERE id = ? AND session_id = (SELECT id FROM sessions WHERE active = 1)")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Characters of pinned text a session may hold (`memory.max_pinned_chars`).
+    pub fn max_pinned_chars(&self) -> usize {
+        self.max_pinned_chars
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<Session>> {
+        let rows = sqlx::query(
+            "SELECT s.name, s.active, COUNT(c.id) AS messages FROM sessions s \
+             LEFT JOIN conversations c ON c.session_id = s.id AND c.user_id = ? \
+             GROUP BY s.id ORDER BY s.id",
+        )
+        .bind(USER_ID)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Session {
+                name: row.get("name"),
+                messages: row.get("messages"),
+                active: row.get::<i64, _>("active") == 1,
+            })
+            .collect())
+    }
+
+    pub async fn add_cron_job(
+        &self,
+        schedule: &str,
+        task: &str,
+        message: &str,
+        once: bool,
+        chat_id: Option<i64>,
+        output: Option<&str>,
+    ) -> Result<i64> {
+        let result = retry_busy(&self.pool, || {
+            sqlx::query(
+                "INSERT INTO cron_jobs (user_id, schedule, task, message, once, chat_id, output) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(USER_ID)
+            .bind(schedule)
+            .bind(task)
+            .bind(message)
+            .bind(once)
+            .bind(chat_id)
+            .bind(output)
+        })
+        .await
+        .map_err(RustyClawError::Database)?;
+
         Ok(result.last_insert_rowid())
     }
 
     pub async fn get_cron_jobs(&self) -> Result<Vec<CronJob>> {
         let rows = sqlx::query(
-            "SELECT id, user_id, schedule, task, message, enabled \
+            "SELECT id, user_id, schedule, task, message, enabled, once, chat_id, output \
              FROM cron_jobs WHERE user_id = ? AND enabled = 1",
         )
         .bind(USER_ID)
@@ -150,6 +918,9 @@ impl Memory {
                 task: row.get("task"),
                 message: row.get("message"),
                 enabled: row.get::<i64, _>("enabled") == 1,
+                once: row.get::<i64, _>("once") == 1,
+                chat_id: row.get("chat_id"),
+                output: row.get("output"),
             })
             .collect();
 
@@ -165,21 +936,64 @@ impl Memory {
         Ok(result.rows_affected() > 0)
     }
 
-    pub async fn log_file(&self, filename: &str, description: Option<&str>) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO workspace_files (filename, description) VALUES (?, ?)",
-        )
-        .bind(filename)
-        .bind(description)
-        .execute(&self.pool)
-        .await?;
+    pub async fn disable_all_cron_jobs(&self) -> Result<usize> {
+        let result = sqlx::query("UPDATE cron_jobs SET enabled = 0 WHERE user_id = ? AND enabled = 1")
+            .bind(USER_ID)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() as usize)
+    }
+
+    pub async fn log_file(
+        &self,
+        filename: &str,
+        description: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<()> {
+        retry_busy(&self.pool, || {
+            sqlx::query("INSERT INTO workspace_files (filename, description, language) VALUES (?, ?, ?)")
+                .bind(filename)
+                .bind(description)
+                .bind(language)
+        })
+        .await
+        .map_err(RustyClawError::Database)?;
 
         Ok(())
     }
 
+    pub async fn clear_workspace_files(&self) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM workspace_files")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn rename_file(&self, old: &str, new: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE workspace_files SET filename = ? WHERE filename = ?")
+            .bind(new)
+            .bind(old)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_file(&self, filename: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM workspace_files WHERE filename = ?")
+            .bind(filename)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     pub async fn get_workspace_files(&self) -> Result<Vec<WorkspaceFile>> {
         let rows = sqlx::query(
-            "SELECT filename, description, created_at FROM workspace_files ORDER BY created_at DESC",
+            "SELECT filename, description, language, created_at FROM workspace_files \
+             ORDER BY created_at DESC, id DESC",
         )
         .fetch_all(&self.pool)
         .await?;
@@ -189,6 +1003,7 @@ impl Memory {
             .map(|row| WorkspaceFile {
                 filename: row.get("filename"),
                 description: row.get("description"),
+                language: row.get("language"),
                 created_at: row.get("created_at"),
             })
             .collect();
@@ -196,6 +1011,112 @@ impl Memory {
         Ok(files)
     }
 
+    pub async fn save_embedding(&self, fact: &str, model: &str, embedding: &[f32]) -> Result<()> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO memory_embeddings (fact, model, embedding) VALUES (?, ?, ?)",
+        )
+        .bind(fact)
+        .bind(model)
+        .bind(serde_json::to_string(embedding).map_err(|e| RustyClawError::Database(e.into()))?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Stored `(fact, embedding)` pairs produced by `model`.
+    pub async fn get_embeddings(&self, model: &str) -> Result<Vec<(String, Vec<f32>)>> {
+        let rows = sqlx::query("SELECT fact, embedding FROM memory_embeddings WHERE model = ?")
+            .bind(model)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut embeddings = Vec::with_capacity(rows.len());
+        for row in rows {
+            let vector: Vec<f32> = serde_json::from_str(row.get::<&str, _>("embedding"))
+                .map_err(|e| RustyClawError::Database(e.into()))?;
+            embeddings.push((row.get("fact"), vector));
+        }
+
+        Ok(embeddings)
+    }
+
+    pub async fn delete_embedding(&self, fact: &str) -> Result<()> {
+        sqlx::query("DELETE FROM memory_embeddings WHERE fact = ?")
+            .bind(fact)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn clear_embeddings(&self) -> Result<()> {
+        sqlx::query("DELETE FROM memory_embeddings")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_voice_replies(&self, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO chat_settings (chat_id, voice_replies) VALUES (?, ?) \
+             ON CONFLICT(chat_id) DO UPDATE SET voice_replies = excluded.voice_replies",
+        )
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn voice_replies(&self, chat_id: i64) -> Result<bool> {
+        let row = sqlx::query("SELECT voice_replies FROM chat_settings WHERE chat_id = ?")
+            .bind(chat_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|r| r.get::<bool, _>("voice_replies")).unwrap_or(false))
+    }
+
+    /// Size of the database file plus its WAL, in bytes.
+    pub fn file_size(&self) -> u64 {
+        let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+        let mut wal = self.path.clone().into_os_string();
+        wal.push("-wal");
+        size(&self.path) + size(Path::new(&wal))
+    }
+
+    /// Reclaim space from deleted rows and refresh query statistics.
+    /// Returns the database size before and after, in bytes.
+    pub async fn vacuum(&self) -> Result<(u64, u64)> {
+        let before = self.file_size();
+
+        // VACUUM needs the whole database to itself; run everything on one
+        // connection and let the busy timeout wait out any in-progress writes.
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut *conn).await?;
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        sqlx::query("ANALYZE").execute(&mut *conn).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)").execute(&mut *conn).await?;
+        drop(conn);
+
+        let after = self.file_size();
+        info!("Database vacuumed: {} -> {} bytes", before, after);
+        Ok((before, after))
+    }
+
+    /// Write a consistent copy of the database to `dest`, which must not
+    /// exist yet. Safe to run while other connections are writing.
+    pub async fn backup_to(&self, dest: &Path) -> Result<()> {
+        let dest = dest
+            .to_str()
+            .ok_or_else(|| RustyClawError::Database(anyhow!("Backup path is not valid UTF-8: {:?}", dest)))?;
+        sqlx::query("VACUUM INTO ?").bind(dest).execute(&self.pool).await?;
+        Ok(())
+    }
+
     pub async fn close(&self) {
         self.pool.close().await;
     }
@@ -0,0 +1,133 @@
+//! Process-wide counters exported in Prometheus text format.
+//!
+//! The recording functions are always available so call sites don't need
+//! `cfg` guards; without the `metrics` feature they compile to nothing.
+
+#[cfg(feature = "metrics")]
+mod imp {
+    use anyhow::Result;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tracing::{info, warn};
+
+    const LATENCY_BUCKETS: [f64; 9] = [0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+    static MESSAGES: AtomicU64 = AtomicU64::new(0);
+    static ERRORS: AtomicU64 = AtomicU64::new(0);
+    static JOB_TRIGGERS: AtomicU64 = AtomicU64::new(0);
+    static LATENCY_COUNTS: [AtomicU64; 9] = [const { AtomicU64::new(0) }; 9];
+    static LATENCY_COUNT: AtomicU64 = AtomicU64::new(0);
+    static LATENCY_SUM_MICROS: AtomicU64 = AtomicU64::new(0);
+
+    pub fn record_message() {
+        MESSAGES.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_error() {
+        ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_job_trigger() {
+        JOB_TRIGGERS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_ollama_latency(elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        for (bucket, count) in LATENCY_BUCKETS.iter().zip(LATENCY_COUNTS.iter()) {
+            if secs <= *bucket {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        LATENCY_COUNT.fetch_add(1, Ordering::Relaxed);
+        LATENCY_SUM_MICROS.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn render() -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP rustyclaw_messages_total Chat messages handled.\n");
+        out.push_str("# TYPE rustyclaw_messages_total counter\n");
+        out.push_str(&format!("rustyclaw_messages_total {}\n", MESSAGES.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP rustyclaw_errors_total Failed Ollama requests.\n");
+        out.push_str("# TYPE rustyclaw_errors_total counter\n");
+        out.push_str(&format!("rustyclaw_errors_total {}\n", ERRORS.load(Ordering::Relaxed)));
+
+        out.push_str("# HELP rustyclaw_scheduler_triggers_total Cron jobs fired.\n");
+        out.push_str("# TYPE rustyclaw_scheduler_triggers_total counter\n");
+        out.push_str(&format!(
+            "rustyclaw_scheduler_triggers_total {}\n",
+            JOB_TRIGGERS.load(Ordering::Relaxed)
+        ));
+
+        out.push_str("# HELP rustyclaw_ollama_request_seconds Ollama chat request latency.\n");
+        out.push_str("# TYPE rustyclaw_ollama_request_seconds histogram\n");
+        for (bucket, count) in LATENCY_BUCKETS.iter().zip(LATENCY_COUNTS.iter()) {
+            out.push_str(&format!(
+                "rustyclaw_ollama_request_seconds_bucket{{le=\"{}\"}} {}\n",
+                bucket,
+                count.load(Ordering::Relaxed)
+            ));
+        }
+        let total = LATENCY_COUNT.load(Ordering::Relaxed);
+        out.push_str(&format!("rustyclaw_ollama_request_seconds_bucket{{le=\"+Inf\"}} {}\n", total));
+        out.push_str(&format!(
+            "rustyclaw_ollama_request_seconds_sum {}\n",
+            LATENCY_SUM_MICROS.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        ));
+        out.push_str(&format!("rustyclaw_ollama_request_seconds_count {}\n", total));
+
+        out
+    }
+
+    /// Serve `GET /metrics` on `port` until the task is dropped.
+    pub async fn serve(port: u16) -> Result<()> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+        info!("Metrics available at http://0.0.0.0:{}/metrics", port);
+
+        loop {
+            let (mut stream, _) = listener.accept().await?;
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                let n = match stream.read(&mut buf).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        warn!("Metrics connection error: {}", e);
+                        return;
+                    }
+                };
+
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let response = if request.starts_with("GET /metrics ") {
+                    let body = render();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+                };
+
+                stream.write_all(response.as_bytes()).await.ok();
+            });
+        }
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod imp {
+    use std::time::Duration;
+
+    pub fn record_message() {}
+
+    pub fn record_error() {}
+
+    pub fn record_job_trigger() {}
+
+    pub fn observe_ollama_latency(_elapsed: Duration) {}
+}
+
+pub use imp::*;
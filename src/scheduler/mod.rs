@@ -1,41 +1,351 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
+use chrono::{DateTime, Local, Utc};
 use cron::Schedule;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
 use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+use crate::error::{self, RustyClawError};
 use crate::memory::{CronJob, Memory};
+use crate::metrics;
+
+/// Parse a standard 5-field cron expression. The `cron` crate expects a
+/// leading seconds field, so one is added.
+fn parse_schedule(schedule: &str) -> Result<Schedule> {
+    Ok(Schedule::from_str(&format!("0 {}", schedule.trim()))?)
+}
+
+/// Why a cron expression was rejected by `check_schedule`.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ScheduleError {
+    #[error("Invalid cron format '{0}' - needs 5 fields (minute hour day month weekday)")]
+    FieldCount(String),
+    /// One of the five fields holds a value outside its range or an
+    /// unknown name. `value` is the offending part of a list or range.
+    #[error("Invalid {field} '{value}' in cron schedule - allowed: {allowed}")]
+    Field {
+        field: &'static str,
+        value: String,
+        allowed: &'static str,
+    },
+    /// Every field looked valid on its own, but the `cron` crate still
+    /// rejected the expression, e.g. a backwards range.
+    #[error("Invalid cron schedule '{schedule}': {reason}")]
+    Unparsable { schedule: String, reason: String },
+}
+
+/// One of the five cron fields and the values it accepts. These follow the
+/// `cron` crate, which numbers weekdays from 1 (Sunday).
+struct CronField {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+    /// Whether `?` ("no specific value") is allowed.
+    question: bool,
+    allowed: &'static str,
+}
+
+const SCHEDULE_FIELDS: [CronField; 5] = [
+    CronField { name: "minute", min: 0, max: 59, names: &[], question: false, allowed: "0-59" },
+    CronField { name: "hour", min: 0, max: 23, names: &[], question: false, allowed: "0-23" },
+    CronField { name: "day", min: 1, max: 31, names: &[], question: true, allowed: "1-31" },
+    CronField {
+        name: "month",
+        min: 1,
+        max: 12,
+        names: &[
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec", "january", "february",
+            "march", "april", "june", "july", "august", "september", "october", "november", "december",
+        ],
+        question: false,
+        allowed: "1-12 or JAN-DEC",
+    },
+    CronField {
+        name: "weekday",
+        min: 1,
+        max: 7,
+        names: &[
+            "sun", "mon", "tue", "wed", "thu", "fri", "sat", "sunday", "monday", "tues", "tuesday", "wednesday",
+            "thurs", "thursday", "friday", "saturday",
+        ],
+        question: true,
+        allowed: "1-7 (1 is Sunday) or SUN-SAT",
+    },
+];
+
+impl CronField {
+    /// The first part of `value` this field can't take, if any.
+    fn invalid_part<'a>(&self, value: &'a str) -> Option<&'a str> {
+        for element in value.split(',') {
+            let (base, step) = match element.split_once('/') {
+                Some((base, step)) => (base, Some(step)),
+                None => (element, None),
+            };
+            if step.is_some_and(|s| !s.parse::<u32>().is_ok_and(|s| s > 0)) {
+                return Some(element);
+            }
+            if base == "*" || (base == "?" && self.question) {
+                continue;
+            }
+            let (start, end) = base.split_once('-').unwrap_or((base, base));
+            if let Some(bad) = [start, end].into_iter().find(|v| !self.accepts(v)) {
+                return Some(bad);
+            }
+        }
+        None
+    }
+
+    fn accepts(&self, value: &str) -> bool {
+        match value.parse::<u32>() {
+            Ok(n) => (self.min..=self.max).contains(&n),
+            Err(_) => self.names.contains(&value.to_lowercase().as_str()),
+        }
+    }
+}
+
+/// Check a standard 5-field cron expression, naming the field at fault if
+/// it can't be used.
+///
+/// ```
+/// use rustyclaw::scheduler::{check_schedule, ScheduleError};
+///
+/// assert!(check_schedule("*/15 9-17 * * MON-FRI").is_ok());
+///
+/// let hour = check_schedule("0 25 * * *").unwrap_err();
+/// assert!(matches!(&hour, ScheduleError::Field { field: "hour", value, .. } if value == "25"));
+/// assert_eq!(hour.to_string(), "Invalid hour '25' in cron schedule - allowed: 0-23");
+///
+/// let weekday = check_schedule("0 9 * * MON,FUNDAY").unwrap_err();
+/// assert!(matches!(&weekday, ScheduleError::Field { field: "weekday", value, .. } if value == "FUNDAY"));
+///
+/// assert!(matches!(check_schedule("0 9 * *"), Err(ScheduleError::FieldCount(_))));
+/// ```
+pub fn check_schedule(schedule: &str) -> std::result::Result<Schedule, ScheduleError> {
+    let parts: Vec<&str> = schedule.split_whitespace().collect();
+    if parts.len() != SCHEDULE_FIELDS.len() {
+        return Err(ScheduleError::FieldCount(schedule.to_string()));
+    }
+    for (field, value) in SCHEDULE_FIELDS.iter().zip(&parts) {
+        if let Some(bad) = field.invalid_part(value) {
+            return Err(ScheduleError::Field {
+                field: field.name,
+                value: bad.to_string(),
+                allowed: field.allowed,
+            });
+        }
+    }
+
+    parse_schedule(schedule).map_err(|e| ScheduleError::Unparsable {
+        schedule: schedule.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Check that `schedule` has the five cron fields and parses.
+fn validate_cron(schedule: &str) -> Result<Schedule> {
+    Ok(check_schedule(schedule)?)
+}
+
+/// The next `count` times `schedule` would fire, without creating a job.
+/// Errors if the expression is invalid or never fires again.
+pub fn upcoming(schedule: &str, count: usize) -> error::Result<Vec<DateTime<Utc>>> {
+    let times: Vec<DateTime<Utc>> = validate_cron(schedule)
+        .map_err(RustyClawError::Scheduler)?
+        .upcoming(Utc)
+        .take(count)
+        .collect();
+    if times.is_empty() {
+        return Err(RustyClawError::Scheduler(anyhow!(
+            "'{}' never fires (no upcoming occurrence)",
+            schedule
+        )));
+    }
+    Ok(times)
+}
+
+/// Split the words after `/schedule` into the five cron fields and the message.
+/// Returns `None` when there aren't enough words for both.
+pub fn parse_schedule_args(args: &[&str]) -> Option<(String, String)> {
+    if args.len() < 6 {
+        return None;
+    }
+    Some((args[..5].join(" "), args[5..].join(" ")))
+}
+
+/// A random delay between zero and `max`, inclusive, at millisecond
+/// granularity.
+///
+/// ```
+/// use rustyclaw::scheduler::random_jitter;
+/// use std::time::Duration;
+///
+/// let jitter_secs = 2;
+/// let delays: Vec<Duration> = (0..1000).map(|_| random_jitter(Duration::from_secs(jitter_secs))).collect();
+/// assert!(delays.iter().all(|d| *d <= Duration::from_secs(jitter_secs)));
+/// // Spread out, not stuck at either end.
+/// assert!(delays.iter().any(|d| *d < Duration::from_secs(1)));
+/// assert!(delays.iter().any(|d| *d > Duration::from_secs(1)));
+///
+/// assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+/// assert_eq!(random_jitter(Duration::from_micros(999)), Duration::ZERO);
+/// assert!(random_jitter(Duration::from_millis(1)) <= Duration::from_millis(1));
+/// ```
+pub fn random_jitter(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    // RandomState is seeded randomly per instance; that's plenty for spreading load.
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    Duration::from_millis(hasher.finish() % (max_ms + 1))
+}
+
+/// Short job label derived from its message, truncated to 50 characters.
+pub fn task_label(message: &str) -> String {
+    if message.chars().count() > 50 {
+        format!("{}...", message.chars().take(47).collect::<String>())
+    } else {
+        message.to_string()
+    }
+}
+
+/// The workspace file name for a run of job `job_id` at `at`, from an output
+/// `template`: `{date}` becomes `2024-05-01`, `{time}` becomes `093000`,
+/// and `{id}` the job id. Workspace files have no directories, so neither
+/// may a template.
+///
+/// ```
+/// use chrono::{Local, TimeZone};
+/// use rustyclaw::scheduler::output_filename;
+///
+/// let at = Local.with_ymd_and_hms(2024, 5, 1, 9, 30, 0).unwrap();
+/// assert_eq!(output_filename("daily-{date}.md", 7, at), "daily-2024-05-01.md");
+/// assert_eq!(output_filename("job{id}-{date}-{time}.txt", 7, at), "job7-2024-05-01-093000.txt");
+/// ```
+pub fn output_filename(template: &str, job_id: i64, at: DateTime<Local>) -> String {
+    template
+        .replace("{date}", &at.format("%Y-%m-%d").to_string())
+        .replace("{time}", &at.format("%H%M%S").to_string())
+        .replace("{id}", &job_id.to_string())
+}
 
 type SendCallback = Arc<dyn Fn(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+type ChatCallback = Arc<dyn Fn(i64, String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+type ReplyCallback = Arc<dyn Fn(CronJob) -> Pin<Box<dyn Future<Output = Option<String>> + Send>> + Send + Sync>;
+type OutputCallback = Arc<dyn Fn(CronJob, String, String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// How a job fires beyond its schedule.
+#[derive(Debug, Clone, Default)]
+pub struct JobOptions {
+    /// Fire at the next matching time only, then cancel the job.
+    pub once: bool,
+    /// Deliver to this Telegram chat through the chat callback instead of
+    /// every send callback.
+    pub chat_id: Option<i64>,
+    /// Have the reply callback answer each run, deliver its reply instead of
+    /// the message, and hand it to the output callback with a file name made
+    /// from this template by `output_filename`.
+    pub output: Option<String>,
+}
+
+/// Where each run of a job goes. Every job's task shares one, so callbacks
+/// set later still reach jobs that are already scheduled.
+#[derive(Clone, Default)]
+struct Delivery {
+    callbacks: Arc<RwLock<Vec<SendCallback>>>,
+    chat_callback: Arc<RwLock<Option<ChatCallback>>>,
+    reply_callback: Arc<RwLock<Option<ReplyCallback>>>,
+    output_callback: Arc<RwLock<Option<OutputCallback>>>,
+}
+
+impl Delivery {
+    /// Deliver one run of `job`, and save its reply if it has an output template.
+    async fn run(&self, job: &CronJob) {
+        info!("Cron job #{} triggered: {}", job.id, job.message);
+        metrics::record_job_trigger();
+
+        let mut message = job.message.clone();
+        let mut reply = None;
+        if job.output.is_some() {
+            match self.reply_callback.read().await.clone() {
+                Some(callback) => reply = callback(job.clone()).await,
+                None => warn!("No reply callback registered — job #{} output not saved", job.id),
+            }
+            if let Some(reply) = &reply {
+                message = reply.clone();
+            }
+        }
+
+        let chat = self.chat_callback.read().await.clone();
+        match (job.chat_id, chat) {
+            (Some(chat_id), Some(callback)) => callback(chat_id, message).await,
+            (chat_id, _) => {
+                if chat_id.is_some() {
+                    warn!("No chat callback registered — job #{} delivered to every frontend", job.id);
+                }
+                let cbs = self.callbacks.read().await;
+                if cbs.is_empty() {
+                    warn!("No send callbacks registered — cron message dropped");
+                } else {
+                    for callback in cbs.iter() {
+                        callback(message.clone()).await;
+                    }
+                }
+            }
+        }
+
+        if let (Some(template), Some(reply)) = (&job.output, reply) {
+            let filename = output_filename(template, job.id, Local::now());
+            match self.output_callback.read().await.clone() {
+                Some(callback) => callback(job.clone(), filename, reply).await,
+                None => warn!("No output callback registered — job #{} output not saved", job.id),
+            }
+        }
+    }
+}
 
 pub struct Scheduler {
     memory: Memory,
+    enabled: bool,
     jobs: Arc<RwLock<HashMap<i64, tokio::task::JoinHandle<()>>>>,
-    callbacks: Arc<RwLock<Vec<SendCallback>>>,
+    delivery: Delivery,
+    jitter: Duration,
 }
 
 impl Scheduler {
-    pub fn new(memory: Memory) -> Self {
+    pub fn new(memory: Memory, enabled: bool) -> Self {
         Self {
             memory,
+            enabled,
             jobs: Arc::new(RwLock::new(HashMap::new())),
-            callbacks: Arc::new(RwLock::new(Vec::new())),
+            delivery: Delivery::default(),
+            jitter: Duration::ZERO,
         }
     }
 
+    /// Delay every firing by a random amount up to `jitter`.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
     pub async fn set_send_callback<F, Fut>(&self, callback: F)
     where
         F: Fn(String) -> Fut + Send + Sync + 'static,
         Fut: Future<Output = ()> + Send + 'static,
     {
         let cb: SendCallback = Arc::new(move |msg| Box::pin(callback(msg)));
-        let mut callbacks = self.callbacks.write().await;
+        let mut callbacks = self.delivery.callbacks.write().await;
         *callbacks = vec![cb];
     }
 
@@ -45,15 +355,60 @@ impl Scheduler {
         Fut: Future<Output = ()> + Send + 'static,
     {
         let cb: SendCallback = Arc::new(move |msg| Box::pin(callback(msg)));
-        let mut callbacks = self.callbacks.write().await;
+        let mut callbacks = self.delivery.callbacks.write().await;
         if !callbacks.iter().any(|c| Arc::ptr_eq(c, &cb)) {
             callbacks.push(cb);
         }
     }
 
-    pub async fn load_jobs(&self) -> Result<()> {
+    /// Deliver messages of jobs with a `chat_id` through `callback`. Without
+    /// one, those messages go to the send callbacks like any other.
+    pub async fn set_chat_callback<F, Fut>(&self, callback: F)
+    where
+        F: Fn(i64, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cb: ChatCallback = Arc::new(move |chat_id, msg| Box::pin(callback(chat_id, msg)));
+        *self.delivery.chat_callback.write().await = Some(cb);
+    }
+
+    /// Answer each run of a job with an `output` template through `callback`.
+    /// Its reply is delivered in place of the message; `None` delivers the
+    /// message and saves nothing.
+    pub async fn set_reply_callback<F, Fut>(&self, callback: F)
+    where
+        F: Fn(CronJob) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        let cb: ReplyCallback = Arc::new(move |job| Box::pin(callback(job)));
+        *self.delivery.reply_callback.write().await = Some(cb);
+    }
+
+    /// Save the reply to each run of a job with an `output` template through
+    /// `callback`, which gets the job, the file name for this run and the
+    /// reply. It runs after the reply is delivered.
+    pub async fn set_output_callback<F, Fut>(&self, callback: F)
+    where
+        F: Fn(CronJob, String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let cb: OutputCallback = Arc::new(move |job, filename, reply| Box::pin(callback(job, filename, reply)));
+        *self.delivery.output_callback.write().await = Some(cb);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub async fn load_jobs(&self) -> error::Result<()> {
         let jobs = self.memory.get_cron_jobs().await?;
         let count = jobs.len();
+
+        if !self.enabled {
+            info!("Scheduler disabled; {} stored cron job(s) not loaded", count);
+            return Ok(());
+        }
+
         for job in jobs {
             let job_id = job.id;
             if let Err(e) = self.schedule_job(job).await {
@@ -64,10 +419,22 @@ impl Scheduler {
         Ok(())
     }
 
-    pub async fn add_job(&self, schedule: &str, task: &str, message: &str) -> Result<i64> {
-        self.validate_cron(schedule)?;
+    pub async fn add_job(&self, schedule: &str, task: &str, message: &str) -> error::Result<i64> {
+        self.add_job_with(schedule, task, message, JobOptions::default()).await
+    }
+
+    pub async fn add_job_with(&self, schedule: &str, task: &str, message: &str, options: JobOptions) -> error::Result<i64> {
+        if !self.enabled {
+            return Err(RustyClawError::Scheduler(anyhow!(
+                "Scheduler is disabled (set scheduler.enabled: true in config.yaml)"
+            )));
+        }
+        validate_cron(schedule).map_err(RustyClawError::Scheduler)?;
         
-        let job_id = self.memory.add_cron_job(schedule, task, message).await?;
+        let job_id = self
+            .memory
+            .add_cron_job(schedule, task, message, options.once, options.chat_id, options.output.as_deref())
+            .await?;
         
         let job = CronJob {
             id: job_id,
@@ -75,14 +442,17 @@ impl Scheduler {
             task: task.to_string(),
             message: message.to_string(),
             enabled: true,
+            once: options.once,
+            chat_id: options.chat_id,
+            output: options.output,
         };
         
-        self.schedule_job(job).await?;
+        self.schedule_job(job).await.map_err(RustyClawError::Scheduler)?;
         info!("Added cron job #{}: '{}' ({})", job_id, task, schedule);
         Ok(job_id)
     }
 
-    pub async fn cancel_job(&self, job_id: i64) -> Result<bool> {
+    pub async fn cancel_job(&self, job_id: i64) -> error::Result<bool> {
         let success = self.memory.disable_cron_job(job_id).await?;
         
         if success {
@@ -96,49 +466,101 @@ impl Scheduler {
         Ok(success)
     }
 
-    pub async fn list_jobs(&self) -> Result<Vec<CronJob>> {
+    /// Disable every enabled job and abort its task. Returns how many were cancelled.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::MemoryConfig;
+    /// use rustyclaw::memory::Memory;
+    /// use rustyclaw::Scheduler;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-cancelall-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let config = MemoryConfig { database: dir.join("cancelall.db"), ..Default::default() };
+    /// let memory = Memory::connect(&config).await.unwrap();
+    /// let scheduler = Scheduler::new(memory.clone(), true);
+    ///
+    /// for minute in 0..3 {
+    ///     scheduler.add_job(&format!("{} 9 * * *", minute), "Stretch", "Time to stretch").await.unwrap();
+    /// }
+    /// assert_eq!(scheduler.running_jobs().await, 3);
+    ///
+    /// assert_eq!(scheduler.cancel_all().await.unwrap(), 3);
+    /// assert!(scheduler.list_jobs().await.unwrap().is_empty());
+    /// assert_eq!(scheduler.running_jobs().await, 0);
+    ///
+    /// // Nothing left to cancel is not an error.
+    /// assert_eq!(scheduler.cancel_all().await.unwrap(), 0);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn cancel_all(&self) -> error::Result<usize> {
+        let count = self.memory.disable_all_cron_jobs().await?;
+
+        let mut jobs = self.jobs.write().await;
+        for (_, handle) in jobs.drain() {
+            handle.abort();
+        }
+
+        if count > 0 {
+            info!("Cancelled all {} cron job(s)", count);
+        }
+        Ok(count)
+    }
+
+    /// How many jobs have a task waiting to fire.
+    pub async fn running_jobs(&self) -> usize {
+        self.jobs.read().await.len()
+    }
+
+    pub async fn list_jobs(&self) -> error::Result<Vec<CronJob>> {
         self.memory.get_cron_jobs().await
     }
 
-    fn validate_cron(&self, schedule: &str) -> Result<()> {
-        let parts: Vec<&str> = schedule.split_whitespace().collect();
-        if parts.len() != 5 {
-            return Err(anyhow!(
-                "Invalid cron format - needs 5 fields (minute hour day month weekday)"
-            ));
-        }
-        
-        Schedule::from_str(schedule)?;
-        Ok(())
+    /// Run job `job_id` now, as if its time had come, without touching its
+    /// schedule. Returns false if there is no such enabled job.
+    pub async fn trigger(&self, job_id: i64) -> error::Result<bool> {
+        let Some(job) = self.list_jobs().await?.into_iter().find(|j| j.id == job_id) else {
+            return Ok(false);
+        };
+        self.delivery.run(&job).await;
+        Ok(true)
     }
 
     async fn schedule_job(&self, job: CronJob) -> Result<()> {
-        let schedule = Schedule::from_str(&job.schedule)?;
-        let callbacks = self.callbacks.clone();
-        let message = job.message.clone();
+        let schedule = parse_schedule(&job.schedule)?;
+        let delivery = self.delivery.clone();
+        let memory = self.memory.clone();
         let job_id = job.id;
         let jobs = self.jobs.clone();
+        let jitter = self.jitter;
 
         let handle = tokio::spawn(async move {
+            // The slot last fired for. The next one is searched for after it,
+            // so a jittered run that wakes late can't fire the same slot twice.
+            let mut last_slot: Option<DateTime<Utc>> = None;
             loop {
-                let next = schedule.upcoming(Utc).next();
+                let now = Utc::now();
+                let from = last_slot.map_or(now, |slot| slot.max(now));
+                let next = schedule.after(&from).next();
                 if let Some(next_time) = next {
-                    let now = Utc::now();
-                    let delay = next_time - now;
-                    
-                    if delay.num_seconds() > 0 {
-                        tokio::time::sleep(Duration::from_secs(delay.num_seconds() as u64)).await;
+                    // Sleep the full sub-second delay; truncating to whole
+                    // seconds woke us early and re-fired until the minute ticked.
+                    if let Ok(delay) = (next_time - Utc::now()).to_std() {
+                        tokio::time::sleep(delay).await;
                     }
+                    last_slot = Some(next_time);
+                    tokio::time::sleep(random_jitter(jitter)).await;
+
+                    delivery.run(&job).await;
 
-                    info!("Cron job #{} triggered: {}", job_id, message);
-                    
-                    let cbs = callbacks.read().await;
-                    if cbs.is_empty() {
-                        warn!("No send callbacks registered — cron message dropped");
-                    } else {
-                        for callback in cbs.iter() {
-                            callback(message.clone()).await;
+                    if job.once {
+                        if let Err(e) = memory.disable_cron_job(job_id).await {
+                            warn!("Failed to retire one-time job #{}: {}", job_id, e);
                         }
+                        info!("One-time job #{} done", job_id);
+                        break;
                     }
                 } else {
                     break;
@@ -150,17 +572,17 @@ impl Scheduler {
         });
 
         let mut jobs = self.jobs.write().await;
-        jobs.insert(job.id, handle);
+        jobs.insert(job_id, handle);
 
         Ok(())
     }
 
-    pub fn stop(&self) {
-        // Abort all running jobs
-        if let Ok(jobs) = self.jobs.try_write() {
-            for (_, handle) in jobs.iter() {
-                handle.abort();
-            }
+    pub async fn stop(&self) {
+        // Abort all running jobs and wait for them to wind down
+        let mut jobs = self.jobs.write().await;
+        for (_, handle) in jobs.drain() {
+            handle.abort();
+            let _ = handle.await;
         }
     }
 }
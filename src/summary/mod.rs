@@ -0,0 +1,92 @@
+//! Background auto-summary: once the conversation goes quiet, or when
+//! `memory.auto_summary.schedule` fires, the model is asked for new facts
+//! about the user in the messages since the last summary, and they are
+//! appended to the facts file.
+//!
+//! Only messages newer than the latest one at startup are considered, so a
+//! restart doesn't summarize the same conversation twice.
+
+use chrono::{DateTime, Local, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::agent::Agent;
+use crate::config::AutoSummaryConfig;
+use crate::error::Result;
+use crate::memory::{Memory, Message};
+use crate::scheduler;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Save the new facts the model finds in `messages`. Returns how many were
+/// added; facts already in memory are skipped.
+pub async fn summarize(agent: &Agent, messages: &[Message]) -> Result<usize> {
+    let mut added = 0;
+    for fact in agent.extract_facts(messages).await? {
+        if agent.save_to_memory(&fact).await? {
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Watch the active session and summarize it as `config` describes. Runs
+/// until the task is dropped; failures are logged and retried on the next
+/// check.
+pub async fn run(agent: Arc<Agent>, memory: Arc<Memory>, config: AutoSummaryConfig, max_history: usize) {
+    let idle = chrono::Duration::minutes(config.idle_minutes as i64);
+    let mut summarized_through = match memory.get_history(1).await {
+        Ok(history) => history.last().map(|m| m.timestamp.clone()),
+        Err(e) => {
+            warn!("Auto-summary couldn't read history: {}", e);
+            None
+        }
+    };
+    let mut next_run = next_fire(config.schedule.as_deref());
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let scheduled = next_run.is_some_and(|t| Utc::now() >= t);
+        if scheduled {
+            next_run = next_fire(config.schedule.as_deref());
+        }
+
+        let history = match memory.get_history(max_history).await {
+            Ok(history) => history,
+            Err(e) => {
+                warn!("Auto-summary couldn't read history: {}", e);
+                continue;
+            }
+        };
+        let recent: Vec<Message> = history
+            .into_iter()
+            .filter(|m| summarized_through.as_ref().is_none_or(|t| m.timestamp > *t))
+            .collect();
+        let Some(last) = recent.last() else {
+            continue;
+        };
+
+        let quiet = last.local_time().is_some_and(|t| Local::now() - t >= idle);
+        if !quiet && !scheduled {
+            continue;
+        }
+
+        if recent.iter().any(|m| m.role == "user") {
+            match summarize(&agent, &recent).await {
+                Ok(0) => info!("Auto-summary found no new facts in {} message(s)", recent.len()),
+                Ok(n) => info!("Auto-summary saved {} new fact(s)", n),
+                Err(e) => {
+                    warn!("Auto-summary failed, will retry: {}", e);
+                    continue;
+                }
+            }
+        }
+        summarized_through = Some(last.timestamp.clone());
+    }
+}
+
+fn next_fire(schedule: Option<&str>) -> Option<DateTime<Utc>> {
+    scheduler::upcoming(schedule?, 1).ok()?.into_iter().next()
+}
@@ -1,18 +1,35 @@
 use anyhow::Result;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use teloxide::{
     prelude::*,
-    types::{BotCommand, ChatId},
+    requests::Output,
+    types::{BotCommand, ChatId, InputFile},
     utils::command::BotCommands,
+    RequestError,
 };
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::agent::Agent;
-use crate::config::Config;
+use crate::config::{Config, TelegramConfig};
+use crate::commands::CommandRegistry;
+use crate::frontend::{as_command, chunk_text, Attachment, Context};
 use crate::memory::Memory;
-use crate::scheduler::Scheduler;
-use crate::workspace::Workspace;
+use crate::scheduler::{parse_schedule_args, task_label, Scheduler};
+use crate::tts::Tts;
+use crate::workspace::{format_size, Workspace};
+
+const DEFAULT_HISTORY_TURNS: usize = 10;
+const MAX_HISTORY_TURNS: usize = 50;
+/// Delay before the first retry of a failed send; doubled for each retry after.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+type TuiCallback = Arc<RwLock<Option<Box<dyn Fn(String, bool) + Send + Sync>>>>;
+type AllowedUsers = Arc<RwLock<Vec<i64>>>;
+/// Files attached with `/use`, per chat, waiting for the next message.
+type PendingAttachments = Arc<RwLock<HashMap<ChatId, Attachment>>>;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase")]
@@ -27,10 +44,36 @@ enum Command {
     Schedule,
     #[command(description = "Cancel a scheduled task")]
     Cancel,
+    #[command(description = "Cancel all scheduled tasks")]
+    Cancelall,
     #[command(description = "List generated files")]
     Workspace,
     #[command(description = "Save last code block")]
     Save,
+    #[command(description = "Attach a file to the next message")]
+    Use,
+    #[command(description = "Show git history of a file")]
+    Log,
+    #[command(description = "Search workspace files")]
+    Search,
+    #[command(description = "Rename a workspace file")]
+    Rename,
+    #[command(description = "Download a workspace file")]
+    Get,
+    #[command(description = "Search conversation history")]
+    Find,
+    #[command(description = "Export chat history")]
+    Export,
+    #[command(description = "Compact the database")]
+    Maintenance,
+    #[command(description = "Show recent messages")]
+    History,
+    #[command(description = "Check Ollama connectivity")]
+    Ping,
+    #[command(description = "List available models")]
+    Models,
+    #[command(description = "Toggle spoken replies")]
+    Voice,
     #[command(description = "View saved memories")]
     Memory,
     #[command(description = "Clear all memories")]
@@ -48,7 +91,9 @@ pub struct TelegramBot {
     scheduler: Arc<Scheduler>,
     workspace: Arc<Workspace>,
     chat_id: Arc<RwLock<Option<ChatId>>>,
-    tui_callback: Arc<RwLock<Option<Box<dyn Fn(String, bool) + Send + Sync>>>>,
+    tui_callback: TuiCallback,
+    allowed_users: AllowedUsers,
+    commands: Arc<CommandRegistry>,
 }
 
 impl TelegramBot {
@@ -59,6 +104,7 @@ impl TelegramBot {
         scheduler: Arc<Scheduler>,
         workspace: Arc<Workspace>,
     ) -> Self {
+        let allowed_users = Arc::new(RwLock::new(config.telegram.allowed_users.clone()));
         Self {
             config,
             agent,
@@ -67,9 +113,29 @@ impl TelegramBot {
             workspace,
             chat_id: Arc::new(RwLock::new(None)),
             tui_callback: Arc::new(RwLock::new(None)),
+            allowed_users,
+            commands: Arc::new(CommandRegistry::with_builtins()),
         }
     }
 
+    /// Use `commands` for registry-backed and custom slash commands instead
+    /// of the built-in set.
+    pub fn with_commands(mut self, commands: Arc<CommandRegistry>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Replace the Telegram user allowlist. An empty list allows everyone.
+    pub async fn set_allowed_users(&self, users: Vec<i64>) {
+        info!("Telegram allowlist updated: {} user(s)", users.len());
+        *self.allowed_users.write().await = users;
+    }
+
+    /// Whether the allowlist lets user `user_id` talk to the bot.
+    pub async fn is_user_allowed(&self, user_id: i64) -> bool {
+        user_allowed(&self.allowed_users, user_id).await
+    }
+
     pub async fn set_tui_callback<F>(&self, callback: F)
     where
         F: Fn(String, bool) + Send + Sync + 'static,
@@ -78,65 +144,92 @@ impl TelegramBot {
         *cb = Some(Box::new(callback));
     }
 
-    async fn send_to_tui(&self, message: &str, is_user: bool) {
-        let cb = self.tui_callback.read().await;
-        if let Some(callback) = cb.as_ref() {
-            callback(message.to_string(), is_user);
-        }
-    }
-
-    async fn send_to_telegram(&self, bot: &Bot, message: &str) {
-        let chat_id = self.chat_id.read().await;
-        if let Some(chat_id) = *chat_id {
-            for chunk in message.as_bytes().chunks(4000) {
-                let text = String::from_utf8_lossy(chunk).to_string();
-                if let Err(e) = bot.send_message(chat_id, &text).await {
-                    tracing::error!("Failed to send message to Telegram: {}", e);
-                }
-            }
-        }
-    }
-
     pub async fn run(&self) -> Result<()> {
         let bot = Bot::new(self.config.telegram.token.clone());
         
-        bot.set_my_commands(vec![
+        let mut bot_commands = vec![
             BotCommand::new("start", "Welcome message"),
             BotCommand::new("status", "Show system status"),
             BotCommand::new("jobs", "List scheduled tasks"),
             BotCommand::new("schedule", "Create a cron job"),
             BotCommand::new("cancel", "Cancel a scheduled task"),
+            BotCommand::new("cancelall", "Cancel all scheduled tasks"),
             BotCommand::new("workspace", "List generated files"),
             BotCommand::new("save", "Save last code block"),
+            BotCommand::new("use", "Attach a file to the next message"),
+            BotCommand::new("log", "Show git history of a file"),
+            BotCommand::new("search", "Search workspace files"),
+            BotCommand::new("rename", "Rename a workspace file"),
+            BotCommand::new("get", "Download a workspace file"),
+            BotCommand::new("find", "Search conversation history"),
+            BotCommand::new("export", "Export chat history"),
+            BotCommand::new("maintenance", "Compact the database"),
+            BotCommand::new("history", "Show recent messages"),
+            BotCommand::new("ping", "Check Ollama connectivity"),
+            BotCommand::new("models", "List available models"),
+            BotCommand::new("voice", "Toggle spoken replies"),
             BotCommand::new("memory", "View saved memories"),
             BotCommand::new("forget", "Clear all memories"),
             BotCommand::new("clear", "Clear chat history"),
             BotCommand::new("help", "Show commands"),
-        ]).await?;
+        ];
+        for (name, description) in self.commands.list() {
+            if !bot_commands.iter().any(|c| c.command == name) {
+                bot_commands.push(BotCommand::new(name, description));
+            }
+        }
+        bot.set_my_commands(bot_commands).await?;
+
+        // Jobs created with a chat_id are delivered straight to that chat.
+        let chat_bot = bot.clone();
+        let telegram_config = self.config.telegram.clone();
+        self.scheduler
+            .set_chat_callback(move |chat_id, msg: String| {
+                let bot = chat_bot.clone();
+                let telegram_config = telegram_config.clone();
+                async move {
+                    if let Err(e) = send_chunked(&bot, ChatId(chat_id), &msg, &telegram_config).await {
+                        tracing::error!("Failed to deliver cron message to chat {}: {}", chat_id, e);
+                    }
+                }
+            })
+            .await;
 
-        let agent = self.agent.clone();
-        let memory = self.memory.clone();
-        let scheduler = self.scheduler.clone();
-        let workspace = self.workspace.clone();
+        let context = Context {
+            agent: self.agent.clone(),
+            memory: self.memory.clone(),
+            scheduler: self.scheduler.clone(),
+            workspace: self.workspace.clone(),
+            max_history: self.config.memory.max_history,
+            confirmations: Default::default(),
+            undo: Default::default(),
+            user: None,
+        };
+        let commands = self.commands.clone();
         let config = self.config.clone();
         let chat_id = self.chat_id.clone();
         let tui_callback = self.tui_callback.clone();
+        let allowed_users = self.allowed_users.clone();
+        let tts = Arc::new(Tts::new(self.config.tts.clone()));
+        let attachments: PendingAttachments = Arc::new(RwLock::new(HashMap::new()));
 
         info!("🦀 Telegram bot is ready! Waiting for messages...");
 
         let handler = Update::filter_message()
+            .filter_async(is_allowed)
             .branch(dptree::entry().filter_command::<Command>().endpoint(handle_command))
             .branch(dptree::endpoint(handle_message));
 
         Dispatcher::builder(bot.clone(), handler)
             .dependencies(dptree::deps![
-                agent,
-                memory,
-                scheduler,
-                workspace,
+                context,
+                commands,
                 Arc::new(config),
                 chat_id,
-                tui_callback
+                tui_callback,
+                allowed_users,
+                tts,
+                attachments
             ])
             .enable_ctrlc_handler()
             .build()
@@ -147,17 +240,123 @@ impl TelegramBot {
     }
 }
 
+/// Send `text` to `chat_id`, split into messages of at most
+/// `telegram.max_message_bytes` bytes.
+async fn send_chunked(bot: &Bot, chat_id: ChatId, text: &str, telegram: &TelegramConfig) -> ResponseResult<()> {
+    for chunk in chunk_text(text, telegram.max_message_bytes) {
+        send(bot.send_message(chat_id, chunk), telegram).await?;
+    }
+    Ok(())
+}
+
+/// Send `request`, retrying up to `telegram.send_retries` times when it fails
+/// for a transient reason: a network error, a timeout, or flood control, in
+/// which case Telegram's `retry_after` is honoured. Other errors, such as a
+/// chat that doesn't exist, are returned straight away.
+async fn send<R>(request: R, telegram: &TelegramConfig) -> ResponseResult<Output<R>>
+where
+    R: Request<Err = RequestError>,
+{
+    let timeout = Duration::from_secs(telegram.send_timeout_secs);
+    let mut attempt = 0;
+    loop {
+        let result = match tokio::time::timeout(timeout, request.send_ref()).await {
+            Ok(result) => result,
+            Err(_) => Err(RequestError::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("no response from Telegram after {}s", telegram.send_timeout_secs),
+            ))),
+        };
+        let error = match result {
+            Ok(output) => return Ok(output),
+            Err(e) => e,
+        };
+
+        let delay = match &error {
+            RequestError::RetryAfter(secs) => secs.duration(),
+            RequestError::Network(_) => RETRY_BASE_DELAY * 2u32.saturating_pow(attempt),
+            RequestError::Io(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                RETRY_BASE_DELAY * 2u32.saturating_pow(attempt)
+            }
+            _ => return Err(error),
+        };
+        if attempt >= telegram.send_retries {
+            return Err(error);
+        }
+        attempt += 1;
+        warn!(
+            "Telegram send failed ({}), retrying in {}s ({}/{})",
+            error,
+            delay.as_secs(),
+            attempt,
+            telegram.send_retries
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Run a built-in command that lives in the registry.
+async fn builtin(commands: &CommandRegistry, name: &str, context: &Context) -> String {
+    commands
+        .dispatch(name, "", context)
+        .await
+        .unwrap_or_else(|| format!("/{} is not available.", name))
+}
+
+async fn history_text(memory: &Memory, agent: &Agent, turns: usize) -> String {
+    let turns = turns.clamp(1, MAX_HISTORY_TURNS);
+    let history = memory.get_history(turns).await.unwrap_or_default();
+    if history.is_empty() {
+        return "No conversation history yet.".to_string();
+    }
+
+    let mut lines = vec![format!("📜 Last {} message(s)\n", history.len())];
+    for msg in history {
+        let (label, content) = if msg.role == "assistant" {
+            let label = match &msg.model {
+                Some(model) => format!("🦀 RustyClaw ({})", model),
+                None => "🦀 RustyClaw".to_string(),
+            };
+            (label, Agent::clean_response(&agent.strip_thinking(&msg.content).await))
+        } else {
+            ("👤 You".to_string(), msg.content)
+        };
+        lines.push(format!("{}: {}\n", label, content));
+    }
+    lines.join("\n")
+}
+
+async fn user_allowed(allowed_users: &AllowedUsers, user_id: i64) -> bool {
+    let allowed = allowed_users.read().await;
+    allowed.is_empty() || allowed.contains(&user_id)
+}
+
+async fn is_allowed(msg: Message, allowed_users: AllowedUsers) -> bool {
+    let Some(user) = msg.from.as_ref() else {
+        let permitted = allowed_users.read().await.is_empty();
+        if !permitted {
+            warn!("Ignoring message without a sender in chat {}", msg.chat.id);
+        }
+        return permitted;
+    };
+
+    let permitted = user_allowed(&allowed_users, user.id.0 as i64).await;
+    if !permitted {
+        warn!("Ignoring message from unauthorized user {} in chat {}", user.id, msg.chat.id);
+    }
+    permitted
+}
+
 async fn handle_command(
     bot: Bot,
     msg: Message,
     cmd: Command,
-    agent: Arc<Agent>,
-    memory: Arc<Memory>,
-    scheduler: Arc<Scheduler>,
-    workspace: Arc<Workspace>,
+    context: Context,
+    commands: Arc<CommandRegistry>,
     config: Arc<Config>,
 ) -> ResponseResult<()> {
     let chat_id = msg.chat.id;
+    let Context { memory, scheduler, workspace, .. } = context.clone();
     
     let response = match cmd {
         Command::Start => {
@@ -171,78 +370,86 @@ async fn handle_command(
             /clear — Clear conversation history\n\
             /help — Show all commands".to_string()
         }
-        Command::Status => {
-            let jobs = scheduler.list_jobs().await.unwrap_or_default();
-            let files = workspace.list_files();
-            format!(
-                "🦀 RustyClaw Status\n\n\
-                Model: {}\n\
-                Host: {}\n\
-                Context: {} tokens\n\
-                Scheduled jobs: {}\n\
-                Workspace files: {}",
-                config.ollama.model,
-                config.ollama.host,
-                config.ollama.context_length,
-                jobs.len(),
-                files.len()
-            )
-        }
-        Command::Jobs => {
-            let jobs = scheduler.list_jobs().await.unwrap_or_default();
-            if jobs.is_empty() {
-                "No scheduled jobs. Ask me to schedule something!".to_string()
-            } else {
-                let mut lines = vec!["🕐 Scheduled Jobs\n".to_string()];
-                for job in jobs {
-                    lines.push(format!("#{} — {}\n  Schedule: {}", job.id, job.task, job.schedule));
-                }
-                lines.join("\n")
-            }
-        }
+        Command::Status => builtin(&commands, "status", &context).await,
+        Command::Jobs => builtin(&commands, "jobs", &context).await,
         Command::Cancel => {
             "Usage: /cancel <job_id>".to_string()
         }
+        Command::Voice => {
+            "Usage: /voice on|off\n\nWhen on, replies also arrive as voice notes (needs tts.url in config.yaml).".to_string()
+        }
+        Command::Cancelall => match scheduler.cancel_all().await {
+            Ok(0) => "No scheduled jobs to cancel.".to_string(),
+            Ok(n) => format!("✅ Cancelled {} job(s)", n),
+            Err(e) => format!("❌ Error: {}", e),
+        },
         Command::Workspace => {
-            let files = workspace.list_files();
+            let files = workspace.list_files_with_metadata().await;
             if files.is_empty() {
                 "Workspace is empty. Ask me to write some code!".to_string()
             } else {
                 let mut lines = vec!["📁 Workspace Files\n".to_string()];
                 for f in files {
                     let size_kb = f.size as f64 / 1024.0;
-                    lines.push(format!("{} ({:.1} KB)", f.name, size_kb));
+                    let mut line = match &f.language {
+                        Some(lang) => format!("{} [{}] ({:.1} KB)", f.name, lang, size_kb),
+                        None => format!("{} ({:.1} KB)", f.name, size_kb),
+                    };
+                    if let Some(desc) = &f.description {
+                        line.push_str(&format!("\n  {}", desc));
+                    }
+                    lines.push(line);
                 }
                 lines.join("\n")
             }
         }
-        Command::Clear => {
-            memory.clear_history().await.ok();
-            "🧹 Conversation history cleared.".to_string()
-        }
-        Command::Memory => {
-            let memory_content = agent.memory_content().await;
-            let (is_large, line_count) = agent.check_memory_size().await;
-            if memory_content.is_empty() {
-                "🧠 My Memory\n\nNo memories saved yet. Tell me something about yourself!".to_string()
-            } else {
-                let header = if is_large {
-                    format!("🧠 My Memory ({} lines)\n\n⚠️ Memory is getting large!\n\n", line_count)
-                } else {
-                    format!("🧠 My Memory ({} lines)\n\n", line_count)
-                };
-                format!("{}{}", header, memory_content)
+        Command::Export => {
+            match memory.export_transcript().await {
+                Ok(transcript) if transcript.is_empty() => "No conversation history to export.".to_string(),
+                Ok(transcript) => {
+                    let file = InputFile::memory(transcript.into_bytes()).file_name("conversation.txt");
+                    send(bot.send_document(chat_id, file), &config.telegram).await?;
+                    return Ok(());
+                }
+                Err(e) => format!("❌ Export failed: {}", e),
             }
         }
-        Command::Forget => {
-            if agent.clear_memory().await.is_ok() {
-                "🧹 All memories have been forgotten.".to_string()
-            } else {
-                "❌ Failed to clear memory.".to_string()
+        Command::Maintenance => {
+            match memory.vacuum().await {
+                Ok((before, after)) => format!(
+                    "🧰 Database compacted\n\nBefore: {:.1} KB\nAfter: {:.1} KB",
+                    before as f64 / 1024.0,
+                    after as f64 / 1024.0
+                ),
+                Err(e) => format!("❌ Maintenance failed: {}", e),
             }
         }
+        Command::Ping => builtin(&commands, "ping", &context).await,
+        Command::Models => builtin(&commands, "models", &context).await,
+        Command::History => history_text(&memory, &context.agent, DEFAULT_HISTORY_TURNS).await,
+        Command::Clear => context.clear_history().await,
+        Command::Memory => builtin(&commands, "memory", &context).await,
+        Command::Forget => builtin(&commands, "forget", &context).await,
         Command::Save => {
-            "Usage: /save filename.py\n\nThis will save the last code block from my response.".to_string()
+            "Usage: /save filename.py [description]\n\nThis will save the last code block from my response.".to_string()
+        }
+        Command::Use => {
+            "Usage: /use <filename>\n\nSends a workspace file along with your next message, e.g. /use main.py then \"refactor this\".".to_string()
+        }
+        Command::Log => {
+            "Usage: /log <filename>\n\nShows recent git commits for a workspace file.".to_string()
+        }
+        Command::Search => {
+            "Usage: /search <pattern>\n\nFinds lines in workspace files matching a regex or text.".to_string()
+        }
+        Command::Rename => {
+            "Usage: /rename <old> <new>".to_string()
+        }
+        Command::Get => {
+            "Usage: /get <filename>\n\nSends a workspace file as a document.".to_string()
+        }
+        Command::Find => {
+            "Usage: /find <words>\n\nSearches past messages for all of the given words.".to_string()
         }
         Command::Schedule => {
             "Usage: /schedule <cron> <prompt>\n\n\
@@ -258,35 +465,69 @@ async fn handle_command(
             /status — System status\n\
             /jobs — List scheduled tasks\n\
             /schedule <cron> <msg> — Create a cron job\n\
+            /next <cron> [count] — Preview a schedule\n\
             /cancel <id> — Cancel a task\n\
+            /cancelall — Cancel all tasks\n\
             /workspace — List generated files\n\
-            /save <filename> — Save last code block\n\
+            /save <filename> [description] — Save last code block\n\
+            /copy_last_code — Save the last code block with a generated name\n\
+            /use <filename> — Attach a file to your next message\n\
+            /log <filename> — Show file history\n\
+            /search <pattern> — Search workspace files\n\
+            /rename <old> <new> — Rename a file\n\
+            /diff <filename> — Compare the last two versions\n\
+            /clearworkspace — Delete all workspace files\n\
+            /get <filename> — Download a file\n\
+            /find <words> — Search chat history\n\
+            /export — Export chat history\n\
+            /maintenance — Compact the database\n\
+            /history [n] — Show recent messages\n\
+            /session new|switch <name> — Separate conversations\n\
+            /session list — List sessions\n\
+            /ping — Check Ollama connectivity\n\
+            /models — List available models\n\
+            /seed <n>|off — Set the sampling seed\n\
+            /model <name>|reset — Set this chat session's model\n\
+            /temp <0.0-2.0>|reset — Set this chat session's temperature\n\
+            /persona <text>|reset — Set this chat session's persona\n\
+            /voice on|off — Toggle spoken replies\n\
             /memory — View saved memories\n\
+            /search_memory <query> — Find saved memories\n\
             /forget — Clear all memories\n\
+            /context [redact] — Show what the model sees\n\
+            /pin [text] — Keep the last message, or text, in every prompt\n\
+            /pins — List pins\n\
+            /unpin <id> — Remove a pin\n\
+            /regenerate — Retry the last reply\n\
+            /undo — Undo the last reply's saves, jobs, and memories\n\
+            /t <name> <args> — Send a prompt template\n\
             /clear — Clear chat history\n\
             /help — This message".to_string()
         }
     };
 
-    for chunk in response.as_bytes().chunks(4000) {
-        let text = String::from_utf8_lossy(chunk).to_string();
-        bot.send_message(chat_id, &text).await?;
-    }
+    send_chunked(&bot, chat_id, &response, &config.telegram).await?;
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn handle_message(
     bot: Bot,
     msg: Message,
-    agent: Arc<Agent>,
-    memory: Arc<Memory>,
-    scheduler: Arc<Scheduler>,
-    workspace: Arc<Workspace>,
+    context: Context,
+    commands: Arc<CommandRegistry>,
     config: Arc<Config>,
     chat_id_storage: Arc<RwLock<Option<ChatId>>>,
+    tts: Arc<Tts>,
+    attachments: PendingAttachments,
 ) -> ResponseResult<()> {
     let chat_id = msg.chat.id;
+    let context = Context {
+        user: msg.from.as_ref().map(|u| u.first_name.clone()),
+        ..context
+    };
+    let Context { memory, scheduler, workspace, .. } = context.clone();
     
     {
         let mut stored = chat_id_storage.write().await;
@@ -298,19 +539,29 @@ async fn handle_message(
         None => return Ok(()),
     };
 
-    if user_text.starts_with("/cancel ") {
-        let parts: Vec<&str> = user_text.split_whitespace().collect();
+    // Commands typed with a custom `command_prefix` never reach
+    // `filter_command`, so they are parsed here in their `/` form. Those with
+    // arguments are handled below.
+    let text = as_command(&user_text, &config.command_prefix).unwrap_or_else(|| user_text.clone());
+    if text != user_text && !text.trim_end().contains(char::is_whitespace) {
+        if let Ok(cmd) = Command::parse(text.trim_end(), "") {
+            return handle_command(bot, msg, cmd, context, commands, config).await;
+        }
+    }
+
+    if text.starts_with("/cancel ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
         if parts.len() >= 2 {
             if let Ok(job_id) = parts[1].parse::<i64>() {
                 match scheduler.cancel_job(job_id).await {
                     Ok(true) => {
-                        bot.send_message(chat_id, format!("✅ Cancelled job #{}", job_id)).await?;
+                        send(bot.send_message(chat_id, format!("✅ Cancelled job #{}", job_id)), &config.telegram).await?;
                     }
                     Ok(false) => {
-                        bot.send_message(chat_id, format!("Job #{} not found.", job_id)).await?;
+                        send(bot.send_message(chat_id, format!("Job #{} not found.", job_id)), &config.telegram).await?;
                     }
                     Err(e) => {
-                        bot.send_message(chat_id, format!("Error: {}", e)).await?;
+                        send(bot.send_message(chat_id, format!("Error: {}", e)), &config.telegram).await?;
                     }
                 }
             }
@@ -318,16 +569,10 @@ async fn handle_message(
         return Ok(());
     }
 
-    if user_text.starts_with("/schedule ") {
-        let parts: Vec<&str> = user_text.split_whitespace().collect();
-        if parts.len() >= 7 {
-            let schedule = parts[1..6].join(" ");
-            let message = parts[6..].join(" ");
-            let task = if message.len() > 50 {
-                format!("{}...", &message[..47])
-            } else {
-                message.clone()
-            };
+    if text.starts_with("/schedule ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if let Some((schedule, message)) = parse_schedule_args(&parts[1..]) {
+            let task = task_label(&message);
             
             match scheduler.add_job(&schedule, &task, &message).await {
                 Ok(job_id) => {
@@ -335,38 +580,42 @@ async fn handle_message(
                         "✅ Scheduled job #{}: {}\nSchedule: {}\nMessage: {}",
                         job_id, task, schedule, message
                     );
-                    bot.send_message(chat_id, &response).await?;
+                    send(bot.send_message(chat_id, &response), &config.telegram).await?;
                 }
                 Err(e) => {
-                    let error = format!("❌ Invalid cron expression: {}", e);
-                    bot.send_message(chat_id, &error).await?;
+                    let error = format!("❌ Could not schedule: {}", e);
+                    send(bot.send_message(chat_id, &error), &config.telegram).await?;
                 }
             }
         } else {
-            bot.send_message(chat_id, "Usage: /schedule <cron> <message>").await?;
+            send(bot.send_message(chat_id, "Usage: /schedule <cron> <message>"), &config.telegram).await?;
         }
         return Ok(());
     }
 
-    if user_text.starts_with("/save ") {
-        let parts: Vec<&str> = user_text.split_whitespace().collect();
+    if text.starts_with("/save ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
         if parts.len() >= 2 {
             let filename = parts[1];
+            let description = Some(parts[2..].join(" ")).filter(|d| !d.is_empty());
             
             if let Ok(history) = memory.get_history(10).await {
                 for msg in history.iter().rev() {
                     if msg.role == "assistant" {
                         let code_blocks = Agent::extract_code_blocks(&msg.content);
-                        if !code_blocks.is_empty() {
-                            match workspace.save_file(filename, &code_blocks[0].1).await {
-                                Ok(path) => {
-                                    let name = path.file_name()
-                                        .and_then(|n| n.to_str())
-                                        .unwrap_or(filename);
-                                    bot.send_message(chat_id, format!("💾 Saved {} to workspace", name)).await?;
+                        if let Some((lang, code)) = code_blocks.first() {
+                            let language = Some(lang.as_str()).filter(|l| *l != "text");
+                            match workspace.save_file(filename, code, language, description.as_deref()).await {
+                                Ok(saved) => {
+                                    let text = format!(
+                                        "💾 Saved {} ({}) to workspace",
+                                        saved.name(),
+                                        format_size(saved.bytes)
+                                    );
+                                    send(bot.send_message(chat_id, text), &config.telegram).await?;
                                 }
                                 Err(e) => {
-                                    bot.send_message(chat_id, format!("❌ Error saving file: {}", e)).await?;
+                                    send(bot.send_message(chat_id, format!("❌ Error saving file: {}", e)), &config.telegram).await?;
                                 }
                             }
                             return Ok(());
@@ -374,75 +623,166 @@ async fn handle_message(
                     }
                 }
             }
-            bot.send_message(chat_id, "❌ No code blocks found in recent conversation.").await?;
+            send(bot.send_message(chat_id, "❌ No code blocks found in recent conversation."), &config.telegram).await?;
         }
         return Ok(());
     }
 
-    info!("Message received: {}...", &user_text[..user_text.len().min(80)]);
-
-    memory.add_message("user", &user_text).await.ok();
-
-    let history = memory.get_history(config.memory.max_history).await.unwrap_or_default();
+    if let Some(arg) = text.strip_prefix("/voice ") {
+        let response = match arg.trim() {
+            _ if !tts.is_enabled() => "🔇 Voice replies need tts.url set in config.yaml.".to_string(),
+            "on" | "off" => {
+                let enabled = arg.trim() == "on";
+                match memory.set_voice_replies(chat_id.0, enabled).await {
+                    Ok(()) if enabled => "🔊 Voice replies on.".to_string(),
+                    Ok(()) => "🔇 Voice replies off.".to_string(),
+                    Err(e) => format!("❌ Error: {}", e),
+                }
+            }
+            _ => "Usage: /voice on|off".to_string(),
+        };
+        send(bot.send_message(chat_id, response), &config.telegram).await?;
+        return Ok(());
+    }
 
-    bot.send_chat_action(chat_id, teloxide::types::ChatAction::Typing).await?;
+    if text.starts_with("/history ") {
+        let turns = text
+            .split_whitespace()
+            .nth(1)
+            .and_then(|n| n.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_HISTORY_TURNS);
+        let response = history_text(&memory, &context.agent, turns).await;
+        send_chunked(&bot, chat_id, &response, &config.telegram).await?;
+        return Ok(());
+    }
 
-    let response = agent.chat(&history).await.unwrap_or_else(|e| {
-        format!("Sorry, I had trouble thinking about that. Error: {}", e)
-    });
+    if text.starts_with("/log ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if parts.len() >= 2 {
+            let response = match workspace.file_log(parts[1], 10) {
+                Ok(entries) if entries.is_empty() => format!("No history for {}.", parts[1]),
+                Ok(entries) => format!("📜 History of {}\n\n{}", parts[1], entries.join("\n")),
+                Err(e) => format!("❌ {}", e),
+            };
+            send(bot.send_message(chat_id, response), &config.telegram).await?;
+        }
+        return Ok(());
+    }
 
-    let (cron_jobs, cron_errors) = Agent::parse_cron_blocks(&response);
-    
-    for error in cron_errors {
-        bot.send_message(chat_id, format!("⚠️ Cron error: {}", error)).await?;
+    if let Some(pattern) = text.strip_prefix("/search ") {
+        let pattern = pattern.trim();
+        let results = workspace.search(pattern);
+        let response = if results.is_empty() {
+            format!("No matches for '{}'.", pattern)
+        } else {
+            let mut lines = vec![format!("🔎 Matches for '{}'\n", pattern)];
+            for (name, line_no, line) in results {
+                lines.push(format!("{}:{}: {}", name, line_no, line));
+            }
+            lines.join("\n")
+        };
+        send_chunked(&bot, chat_id, &response, &config.telegram).await?;
+        return Ok(());
     }
 
-    for job in cron_jobs {
-        match scheduler.add_job(&job.schedule, &job.task, &job.message).await {
-            Ok(job_id) => {
-                let msg = format!(
-                    "✅ Scheduled job #{}: {}\nSchedule: {}",
-                    job_id, job.task, job.schedule
-                );
-                bot.send_message(chat_id, &msg).await?;
+    if text.starts_with("/rename ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if parts.len() == 3 {
+            match workspace.rename_file(parts[1], parts[2]).await {
+                Ok(()) => {
+                    send(bot.send_message(chat_id, format!("✏️ Renamed {} to {}", parts[1], parts[2])), &config.telegram).await?;
+                }
+                Err(e) => {
+                    send(bot.send_message(chat_id, format!("❌ Error renaming file: {}", e)), &config.telegram).await?;
+                }
             }
-            Err(e) => {
-                bot.send_message(chat_id, format!("❌ Error scheduling: {}", e)).await?;
+        } else {
+            send(bot.send_message(chat_id, "Usage: /rename <old> <new>"), &config.telegram).await?;
+        }
+        return Ok(());
+    }
+
+    if text.starts_with("/get ") {
+        let parts: Vec<&str> = text.split_whitespace().collect();
+        if parts.len() >= 2 {
+            match workspace.read_bytes(parts[1]) {
+                Some(bytes) => {
+                    let file = InputFile::memory(bytes).file_name(parts[1].to_string());
+                    send(bot.send_document(chat_id, file), &config.telegram).await?;
+                }
+                None => {
+                    send(bot.send_message(chat_id, format!("❌ File not found: {}", parts[1])), &config.telegram).await?;
+                }
             }
         }
+        return Ok(());
     }
 
-    let save_blocks = Agent::parse_save_blocks(&response);
-    for block in save_blocks {
-        match workspace.save_file(&block.filename, &block.content).await {
-            Ok(path) => {
-                let name = path.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or(&block.filename);
-                bot.send_message(chat_id, format!("💾 Saved {} to workspace", name)).await?;
+    if let Some(filename) = text.strip_prefix("/use ") {
+        let response = match context.attach(filename.trim()).await {
+            Ok(attachment) => {
+                let note = if attachment.truncated { " (truncated to fit the context)" } else { "" };
+                let response = format!("📎 {}{} will be sent with your next message.", attachment.name, note);
+                attachments.write().await.insert(chat_id, attachment);
+                response
             }
-            Err(e) => {
-                bot.send_message(chat_id, format!("❌ Error saving file: {}", e)).await?;
+            Err(e) => format!("❌ {}", e),
+        };
+        send(bot.send_message(chat_id, response), &config.telegram).await?;
+        return Ok(());
+    }
+
+    if let Some(query) = text.strip_prefix("/find ") {
+        let query = query.trim();
+        let response = match memory.search_history(query, 10).await {
+            Ok(found) if found.is_empty() => format!("No messages matching '{}'.", query),
+            Ok(found) => {
+                let mut lines = vec![format!("🔎 Messages matching '{}'\n", query)];
+                lines.extend(found.iter().map(|hit| hit.to_string()));
+                lines.join("\n")
             }
-        }
+            Err(e) => format!("❌ Search failed: {}", e),
+        };
+        send_chunked(&bot, chat_id, &response, &config.telegram).await?;
+        return Ok(());
     }
 
-    let memory_blocks = Agent::parse_memory_blocks(&response);
-    for fact in memory_blocks {
-        if agent.save_to_memory(&fact).await.unwrap_or(false) {
-            bot.send_message(chat_id, format!("🧠 Remembered: {}", fact)).await?;
-        }
+    if let Some((name, args)) = commands.parse(&text) {
+        let response = commands.dispatch(name, args, &context).await.unwrap_or_default();
+        send_chunked(&bot, chat_id, &response, &config.telegram).await?;
+        return Ok(());
     }
 
-    let clean = Agent::clean_response(&response);
-    if !clean.is_empty() {
-        for chunk in clean.as_bytes().chunks(4000) {
-            let text = String::from_utf8_lossy(chunk).to_string();
-            bot.send_message(chat_id, &text).await?;
-        }
+    info!("Message received: {}...", &user_text[..user_text.len().min(80)]);
+
+    bot.send_chat_action(chat_id, teloxide::types::ChatAction::Typing).await?;
+
+    let attachment = attachments.write().await.remove(&chat_id);
+    let reply = context.respond_with(&user_text, attachment.as_ref()).await;
+
+    for action in &reply.actions {
+        send(bot.send_message(chat_id, format!("{} {}", action.emoji(), action)), &config.telegram).await?;
     }
 
-    memory.add_message("assistant", &response).await.ok();
+    if !reply.text.is_empty() {
+        let mut send_text = true;
+        if tts.is_enabled() && memory.voice_replies(chat_id.0).await.unwrap_or(false) {
+            match tts.synthesize(&reply.text).await {
+                Ok(audio) => {
+                    let voice = InputFile::memory(audio).file_name("reply.ogg");
+                    match send(bot.send_voice(chat_id, voice), &config.telegram).await {
+                        Ok(_) => send_text = !tts.replace_text(),
+                        Err(e) => warn!("Failed to send voice reply: {}", e),
+                    }
+                }
+                Err(e) => warn!("Speech synthesis failed, replying with text: {}", e),
+            }
+        }
+
+        if send_text {
+            send_chunked(&bot, chat_id, &reply.text, &config.telegram).await?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,68 @@
+use anyhow::{anyhow, bail, Result};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::config::TtsConfig;
+
+/// Request body for an OpenAI-compatible `/v1/audio/speech` endpoint, which
+/// most local TTS servers implement.
+#[derive(Serialize)]
+struct SpeechRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: &'a str,
+}
+
+pub struct Tts {
+    config: TtsConfig,
+    client: Client,
+}
+
+impl Tts {
+    pub fn new(config: TtsConfig) -> Self {
+        Self {
+            config,
+            client: Client::builder()
+                .timeout(Duration::from_secs(60))
+                .build()
+                .unwrap(),
+        }
+    }
+
+    /// Whether a speech endpoint is configured at all.
+    pub fn is_enabled(&self) -> bool {
+        self.config.url.is_some()
+    }
+
+    /// Send voice replies without the matching text message.
+    pub fn replace_text(&self) -> bool {
+        self.config.replace_text
+    }
+
+    /// Synthesize `text` to Ogg/Opus audio, the format Telegram voice notes use.
+    pub async fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
+        let url = self
+            .config
+            .url
+            .as_deref()
+            .ok_or_else(|| anyhow!("tts.url is not set"))?;
+
+        let request = SpeechRequest {
+            model: &self.config.model,
+            input: text,
+            voice: &self.config.voice,
+            response_format: "opus",
+        };
+        let response = self.client.post(url).json(&request).send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            bail!("TTS endpoint returned error {}: {}", status, text);
+        }
+
+        Ok(response.bytes().await?.to_vec())
+    }
+}
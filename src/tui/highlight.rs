@@ -0,0 +1,103 @@
+//! Drawing chat messages line by line, with fenced code blocks set apart
+//! and, when `tui.syntax_highlighting` is on, colored by language.
+
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{self, ThemeSet};
+use syntect::parsing::SyntaxSet;
+
+/// Rows a message takes in the chat view: one per line.
+pub fn message_height(message: &str) -> usize {
+    message.split('\n').count()
+}
+
+/// Keyword coloring for code blocks, using one of syntect's bundled themes.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: highlighting::Theme,
+}
+
+impl Highlighter {
+    /// Load the bundled syntaxes and a theme that reads on a `dark` (or
+    /// light) background.
+    pub fn new(dark: bool) -> Self {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let name = if dark { "base16-ocean.dark" } else { "InspiredGitHub" };
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.remove(name).unwrap_or_default(),
+        }
+    }
+
+    /// `code` colored as `language` over `base`'s background, or `None` if
+    /// the language isn't one syntect knows.
+    fn highlight(&self, code: &[&str], language: &str, base: Style) -> Option<Vec<Line<'static>>> {
+        let syntax = self.syntaxes.find_syntax_by_token(language)?;
+        let mut lines = HighlightLines::new(syntax, &self.theme);
+        code.iter()
+            .map(|line| {
+                let line = format!("{}\n", line);
+                let ranges = lines.highlight_line(&line, &self.syntaxes).ok()?;
+                let spans = ranges
+                    .into_iter()
+                    .map(|(style, text)| {
+                        let fg = Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+                        Span::styled(text.trim_end_matches('\n').to_string(), base.fg(fg))
+                    })
+                    .collect::<Vec<_>>();
+                Some(Line::from(spans))
+            })
+            .collect()
+    }
+}
+
+/// The text after a fence starting `line`. The first line of a message
+/// starts with its "[time] Name: " header, so a fence may follow that.
+fn fence(line: &str, first: bool) -> Option<&str> {
+    let line = line.trim_start();
+    if let Some(rest) = line.strip_prefix("```") {
+        return Some(rest);
+    }
+    let (_, rest) = line.split_once(": ").filter(|_| first)?;
+    rest.strip_prefix("```")
+}
+
+/// `message` as chat rows: plain lines in `text`, fenced code blocks and
+/// their fences in `code`, colored by `highlighter` when it knows the
+/// block's language.
+pub fn message_lines(message: &str, text: Style, code: Style, highlighter: Option<&Highlighter>) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    // The language and lines of the code block being read.
+    let mut block: Option<(&str, Vec<&str>)> = None;
+
+    for (i, line) in message.split('\n').enumerate() {
+        match (fence(line, i == 0), block.take()) {
+            (Some(_), Some((language, body))) => {
+                lines.extend(code_lines(&body, language, code, highlighter));
+                lines.push(Line::styled(line.to_string(), code));
+            }
+            (None, Some((language, mut body))) => {
+                body.push(line);
+                block = Some((language, body));
+            }
+            (Some(info), None) => {
+                lines.push(Line::styled(line.to_string(), code));
+                block = Some((info.split_whitespace().next().unwrap_or_default(), Vec::new()));
+            }
+            (None, None) => lines.push(Line::styled(line.to_string(), text)),
+        }
+    }
+    // A block still open when the message ends, such as one cut short.
+    if let Some((language, body)) = block {
+        lines.extend(code_lines(&body, language, code, highlighter));
+    }
+    lines
+}
+
+fn code_lines(body: &[&str], language: &str, code: Style, highlighter: Option<&Highlighter>) -> Vec<Line<'static>> {
+    highlighter
+        .filter(|_| !language.is_empty())
+        .and_then(|h| h.highlight(body, language, code))
+        .unwrap_or_else(|| body.iter().map(|line| Line::styled(line.to_string(), code)).collect())
+}
@@ -0,0 +1,243 @@
+//! The TUI's key bindings: `tui.keybindings` on top of the defaults.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+use crate::config::KeyBindings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Send,
+    Newline,
+    Quit,
+    ClearInput,
+    ScrollUp,
+    ScrollDown,
+    HistoryPrev,
+    HistoryNext,
+    Complete,
+    Copy,
+    TogglePanel,
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "send" => Action::Send,
+            "newline" => Action::Newline,
+            "quit" => Action::Quit,
+            "clear_input" => Action::ClearInput,
+            "scroll_up" => Action::ScrollUp,
+            "scroll_down" => Action::ScrollDown,
+            "history_prev" => Action::HistoryPrev,
+            "history_next" => Action::HistoryNext,
+            "complete" => Action::Complete,
+            "copy" => Action::Copy,
+            "toggle_panel" => Action::TogglePanel,
+            _ => return None,
+        })
+    }
+
+    /// The keys bound when `tui.keybindings` doesn't set this action.
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Send => &["enter"],
+            // Most terminals report Shift+Enter as plain Enter.
+            Action::Newline => &["alt+enter", "ctrl+j"],
+            Action::Quit => &["ctrl+c"],
+            Action::ClearInput => &["esc", "ctrl+u"],
+            Action::ScrollUp => &["pageup"],
+            Action::ScrollDown => &["pagedown"],
+            Action::HistoryPrev => &["up"],
+            Action::HistoryNext => &["down"],
+            Action::Complete => &["tab"],
+            Action::Copy => &["ctrl+y"],
+            Action::TogglePanel => &["f2"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Shift is part of the character for printable keys ("K", not
+    /// "shift+k"), so it is dropped to match either way of reporting it.
+    fn from_event(event: &KeyEvent) -> Self {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        if matches!(event.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    fn label(&self) -> String {
+        let mut label = String::new();
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "Ctrl+"),
+            (KeyModifiers::ALT, "Alt+"),
+            (KeyModifiers::SHIFT, "Shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                label.push_str(name);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => label.push_str("Space"),
+            KeyCode::Char(c) if self.modifiers.is_empty() => label.push(c),
+            KeyCode::Char(c) => label.push(c.to_ascii_uppercase()),
+            KeyCode::F(n) => label.push_str(&format!("F{}", n)),
+            KeyCode::BackTab => label.push_str("Shift+Tab"),
+            KeyCode::PageUp => label.push_str("PgUp"),
+            KeyCode::PageDown => label.push_str("PgDn"),
+            KeyCode::Up => label.push('↑'),
+            KeyCode::Down => label.push('↓'),
+            KeyCode::Left => label.push('←'),
+            KeyCode::Right => label.push('→'),
+            code => label.push_str(&format!("{:?}", code)),
+        }
+        label
+    }
+}
+
+/// Parse a key like "enter", "ctrl+c", "alt+shift+up", "f5", or "k".
+fn parse_key(spec: &str) -> Result<Key, String> {
+    let mut parts: Vec<&str> = spec.trim().split('+').collect();
+    // "ctrl++" binds the plus key.
+    if spec.trim().ends_with("++") {
+        parts.truncate(parts.len() - 2);
+        parts.push("+");
+    }
+    let Some(name) = parts.pop().filter(|n| !n.is_empty()) else {
+        return Err(format!("'{}' names no key", spec));
+    };
+
+    let mut modifiers = KeyModifiers::NONE;
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            other => return Err(format!("'{}' has an unknown modifier '{}'", spec, other)),
+        };
+    }
+
+    let code = match name.to_lowercase().as_str() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        lower => match lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    // Terminals report Ctrl/Alt letters in lowercase.
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    }
+                    (Some(c), None) if modifiers.is_empty() => KeyCode::Char(c),
+                    (Some(c), None) => KeyCode::Char(c.to_ascii_lowercase()),
+                    _ => return Err(format!("'{}' is not a key", spec)),
+                }
+            }
+        },
+    };
+
+    if matches!(code, KeyCode::Char(_) | KeyCode::BackTab) {
+        modifiers.remove(KeyModifiers::SHIFT);
+    }
+    Ok(Key { code, modifiers })
+}
+
+/// Which action, if any, each key press triggers.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+    labels: HashMap<Action, String>,
+}
+
+impl Keymap {
+    /// Build the keymap, reporting every unparsable key and every key bound
+    /// to more than one action.
+    pub fn from_config(config: &KeyBindings) -> Result<Self, Vec<String>> {
+        let mut keymap = Self {
+            bindings: HashMap::new(),
+            labels: HashMap::new(),
+        };
+        let mut problems = Vec::new();
+        let mut owners: HashMap<Key, &str> = HashMap::new();
+
+        for (name, keys) in config.entries() {
+            let Some(action) = Action::from_name(name) else {
+                continue;
+            };
+            let keys: Vec<&str> = match keys {
+                Some(keys) => keys.iter().map(String::as_str).collect(),
+                None => action.default_keys().to_vec(),
+            };
+            if keys.is_empty() {
+                problems.push(format!("tui.keybindings.{} must list at least one key", name));
+            }
+
+            for spec in keys {
+                let key = match parse_key(spec) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        problems.push(format!("tui.keybindings.{}: {}", name, e));
+                        continue;
+                    }
+                };
+                match owners.insert(key, name) {
+                    Some(other) if other != name => problems.push(format!(
+                        "tui.keybindings: '{}' is bound to both {} and {}",
+                        spec, other, name
+                    )),
+                    _ => {}
+                }
+                keymap.bindings.insert(key, action);
+                keymap.labels.entry(action).or_insert_with(|| key.label());
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(keymap)
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.get(&Key::from_event(event)).copied()
+    }
+
+    /// The first key bound to `action`, for hints.
+    pub fn label(&self, action: Action) -> &str {
+        self.labels.get(&action).map(String::as_str).unwrap_or("?")
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::from_config(&KeyBindings::default()).expect("default key bindings are valid")
+    }
+}
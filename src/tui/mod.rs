@@ -1,37 +1,282 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent, MouseButton, MouseEvent,
+        MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
+use chrono::{DateTime, Local, Utc};
 use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 
 use crate::agent::Agent;
-use crate::config::Config;
-use crate::memory::Memory;
-use crate::scheduler::Scheduler;
-use crate::workspace::Workspace;
+use crate::commands::CommandRegistry;
+use crate::config::{Config, TuiConfig};
+use crate::frontend::{as_command, Attachment, Confirmations, Context, Reply, UndoLog, CONFIRM_WINDOW};
+use crate::memory::{format_transcript, Memory, Message};
+use crate::scheduler::{parse_schedule_args, task_label, Scheduler};
+use crate::workspace::{format_size, Workspace};
+
+mod highlight;
+mod keys;
+mod panel;
+
+use highlight::{message_height, message_lines, Highlighter};
+pub use keys::{Action, Keymap};
+use panel::{Entry, Panel, PANEL_WIDTH};
+
+/// Slash commands the TUI handles itself, as (usage, description). Together
+/// with the `CommandRegistry` they drive both `/help` and the autocomplete
+/// palette.
+const COMMANDS: &[(&str, &str)] = &[
+    ("/quit", "Exit"),
+    ("/clear", "Clear history"),
+    ("/stop", "Stop the reply being generated"),
+    ("/schedule <cron> <msg>", "Create a cron job"),
+    ("/cancel <id>", "Cancel a cron job"),
+    ("/cancelall", "Cancel all cron jobs"),
+    ("/workspace", "List files"),
+    ("/save <file> [desc]", "Save last code block"),
+    ("/use <file>", "Attach a file to the next message"),
+    ("/log <file>", "Show file history"),
+    ("/search <pattern>", "Search files"),
+    ("/rename <old> <new>", "Rename a file"),
+    ("/find <words>", "Search chat history"),
+    ("/export", "Save chat to the workspace"),
+    ("/maintenance", "Compact the database"),
+];
+
+const MAX_PALETTE_ROWS: u16 = 8;
+const GREETING_PROMPT: &str = "I just opened the terminal chat. Greet me in one or two sentences, \
+picking up on anything you remember about me or our last conversation.";
+const GREETING_HISTORY: usize = 10;
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+/// Rows moved per mouse wheel notch.
+const SCROLL_STEP: usize = 3;
+/// Lines of a file shown when it's opened from the panel.
+const MAX_VIEW_LINES: usize = 200;
+/// Lines the input box grows to before it scrolls.
+const MAX_INPUT_LINES: u16 = 6;
+
+fn command_name(usage: &str) -> &str {
+    usage.split_whitespace().next().unwrap_or(usage)
+}
+
+/// The TUI's own commands, then the registry's, then `/help`, written with
+/// the configured command `prefix`.
+fn command_list(registry: &CommandRegistry, prefix: &str) -> Vec<(String, String)> {
+    let mut commands: Vec<(String, String)> = COMMANDS
+        .iter()
+        .map(|(usage, description)| (usage.to_string(), description.to_string()))
+        .collect();
+    for (name, description) in registry.list() {
+        if !commands.iter().any(|(usage, _)| command_name(usage) == format!("/{}", name)) {
+            commands.push((format!("/{}", name), description));
+        }
+    }
+    commands.push(("/help".to_string(), "This message".to_string()));
+    commands
+        .into_iter()
+        .map(|(usage, description)| (format!("{}{}", prefix, &usage[1..]), description))
+        .collect()
+}
+
+/// Commands whose name starts with `input`, while the user is still typing the
+/// command word itself.
+fn command_matches<'a>(commands: &'a [(String, String)], input: &str) -> Vec<&'a (String, String)> {
+    if input.is_empty() || input.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    commands
+        .iter()
+        .filter(|(usage, _)| command_name(usage).starts_with(input))
+        .collect()
+}
+
+fn common_prefix<'a>(names: &[&'a str]) -> &'a str {
+    let Some(mut prefix) = names.first().copied() else {
+        return "";
+    };
+    for name in &names[1..] {
+        while !name.starts_with(prefix) {
+            prefix = &prefix[..prefix.len() - 1];
+        }
+    }
+    prefix
+}
+
+/// Resolved styles for every themed element of the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub title: Style,
+    pub user: Style,
+    pub text: Style,
+    pub busy: Style,
+    pub hint: Style,
+    pub selected: Style,
+    pub code: Style,
+}
+
+impl Theme {
+    /// Build the preset named in `tui.theme`, then apply any `tui.colors`
+    /// overrides. Unknown names and colors fall back to the dark defaults;
+    /// `Config::validate` rejects them before we get here.
+    ///
+    /// ```
+    /// use ratatui::style::{Color, Modifier, Style};
+    /// use rustyclaw::config::TuiConfig;
+    /// use rustyclaw::tui::Theme;
+    ///
+    /// let dark = Theme::from_config(&TuiConfig::default());
+    /// assert_eq!(dark.user, Style::default().fg(Color::Cyan));
+    ///
+    /// let mut config = TuiConfig { theme: "light".into(), ..Default::default() };
+    /// let light = Theme::from_config(&config);
+    /// assert_eq!(light.title, Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD));
+    /// assert_eq!(light.selected, Style::default().fg(Color::White).bg(Color::Blue));
+    ///
+    /// config.theme = "mono".into();
+    /// assert_eq!(Theme::from_config(&config).busy, Style::default().add_modifier(Modifier::ITALIC));
+    ///
+    /// // Overrides replace one color of the preset and keep the rest of the style.
+    /// config.theme = "light".into();
+    /// config.colors.title = Some("#ff8800".into());
+    /// config.colors.assistant = Some("208".into());
+    /// config.colors.highlight = Some("darkgray".into());
+    /// let themed = Theme::from_config(&config);
+    /// assert_eq!(themed.title, Style::default().fg(Color::Rgb(0xff, 0x88, 0x00)).add_modifier(Modifier::BOLD));
+    /// assert_eq!(themed.text, Style::default().fg(Color::Indexed(208)));
+    /// assert_eq!(themed.selected, Style::default().fg(Color::White).bg(Color::DarkGray));
+    /// assert_eq!(themed.user, light.user);
+    ///
+    /// // An unknown theme falls back to dark.
+    /// let unknown = TuiConfig { theme: "neon".into(), ..Default::default() };
+    /// assert_eq!(Theme::from_config(&unknown), dark);
+    /// ```
+    pub fn from_config(config: &TuiConfig) -> Self {
+        let mut theme = match config.theme.as_str() {
+            "light" => Self {
+                title: Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+                user: Style::default().fg(Color::Magenta),
+                text: Style::default().fg(Color::Black),
+                busy: Style::default().fg(Color::Red),
+                hint: Style::default().fg(Color::Gray),
+                selected: Style::default().fg(Color::White).bg(Color::Blue),
+                code: Style::default().fg(Color::Black).bg(Color::Indexed(254)),
+            },
+            "mono" => Self {
+                title: Style::default().add_modifier(Modifier::BOLD),
+                user: Style::default().add_modifier(Modifier::BOLD),
+                text: Style::default(),
+                busy: Style::default().add_modifier(Modifier::ITALIC),
+                hint: Style::default().add_modifier(Modifier::DIM),
+                selected: Style::default().add_modifier(Modifier::REVERSED),
+                code: Style::default().add_modifier(Modifier::DIM),
+            },
+            _ => Self {
+                title: Style::default().fg(Color::Green).add_modifier(Modifier::BOLD),
+                user: Style::default().fg(Color::Cyan),
+                text: Style::default().fg(Color::White),
+                busy: Style::default().fg(Color::Yellow),
+                hint: Style::default().fg(Color::DarkGray),
+                selected: Style::default().fg(Color::Black).bg(Color::Green),
+                code: Style::default().fg(Color::White).bg(Color::Indexed(236)),
+            },
+        };
+
+        let color = |c: &Option<String>| c.as_deref().and_then(|c| c.parse::<Color>().ok());
+        let colors = &config.colors;
+        if let Some(c) = color(&colors.title) {
+            theme.title = theme.title.fg(c);
+        }
+        if let Some(c) = color(&colors.user) {
+            theme.user = theme.user.fg(c);
+        }
+        if let Some(c) = color(&colors.assistant) {
+            theme.text = theme.text.fg(c);
+        }
+        if let Some(c) = color(&colors.busy) {
+            theme.busy = theme.busy.fg(c);
+        }
+        if let Some(c) = color(&colors.hint) {
+            theme.hint = theme.hint.fg(c);
+        }
+        if let Some(c) = color(&colors.highlight) {
+            theme.selected = theme.selected.bg(c);
+        }
+        if let Some(c) = color(&colors.code) {
+            theme.code = theme.code.bg(c);
+        }
+        theme
+    }
+}
+
+type TelegramCallback = Arc<RwLock<Option<Arc<dyn Fn(String) + Send + Sync>>>>;
 
 pub struct TuiApp {
     config: Config,
+    theme: Theme,
+    /// Colors code blocks by language (`tui.syntax_highlighting`).
+    highlighter: Option<Highlighter>,
     agent: Arc<Agent>,
     memory: Arc<Memory>,
     scheduler: Arc<Scheduler>,
     workspace: Arc<Workspace>,
+    commands: Arc<CommandRegistry>,
+    /// Every command shown by `/help` and the palette, as (usage, description).
+    command_list: Vec<(String, String)>,
     messages: Vec<(String, bool)>,
     input: String,
     processing: bool,
-    telegram_callback: Arc<RwLock<Option<Arc<dyn Fn(String) + Send + Sync>>>>,
+    /// The message being answered in the background and the task answering it.
+    pending: Option<(String, JoinHandle<Reply>)>,
+    /// A message sent while busy, held until the pending reply arrives
+    /// (`tui.queue_when_busy`).
+    queued: Option<String>,
+    quit: bool,
+    /// Prefix being Tab-cycled and the index of the candidate last inserted.
+    completion: Option<(String, usize)>,
+    /// Cleaned text of the most recent assistant reply, for Ctrl+Y.
+    last_response: Option<String>,
+    /// Index in `messages` of that reply, which `/regenerate` replaces.
+    last_reply: Option<usize>,
+    /// Opened on first copy and kept alive: on X11 the clipboard contents
+    /// vanish when the owning handle is dropped.
+    clipboard: Option<arboard::Clipboard>,
+    /// File from `/use`, sent along with the next message only.
+    attachment: Option<Attachment>,
+    /// Short-lived message shown in place of the key hints.
+    notice: Option<(String, Instant)>,
+    /// How many rows the chat view is scrolled up from the newest.
+    scroll: usize,
+    /// Index in `messages` of the message clicked on, which Ctrl+Y copies.
+    selected: Option<usize>,
+    keymap: Keymap,
+    /// Lines entered this session, oldest first, for recalling with the
+    /// history keys.
+    sent: Vec<String>,
+    /// Index in `sent` of the line being recalled.
+    recall: Option<usize>,
+    /// The jobs and files panel, while it's open. It takes the keyboard.
+    panel: Option<Panel>,
+    confirmations: Arc<Confirmations>,
+    undo: Arc<UndoLog>,
+    telegram_callback: TelegramCallback,
 }
 
 impl TuiApp {
@@ -41,16 +286,39 @@ impl TuiApp {
         memory: Arc<Memory>,
         scheduler: Arc<Scheduler>,
         workspace: Arc<Workspace>,
+        commands: Arc<CommandRegistry>,
     ) -> Self {
         Self {
+            theme: Theme::from_config(&config.tui),
+            highlighter: (config.tui.syntax_highlighting && config.tui.theme != "mono")
+                .then(|| Highlighter::new(config.tui.theme != "light")),
+            keymap: Keymap::from_config(&config.tui.keybindings).unwrap_or_default(),
+            sent: Vec::new(),
+            recall: None,
+            panel: None,
+            confirmations: Arc::default(),
+            undo: Arc::default(),
+            command_list: command_list(&commands, &config.command_prefix),
             config,
             agent,
             memory,
             scheduler,
             workspace,
+            commands,
             messages: Vec::new(),
             input: String::new(),
             processing: false,
+            pending: None,
+            queued: None,
+            quit: false,
+            completion: None,
+            last_response: None,
+            last_reply: None,
+            clipboard: None,
+            attachment: None,
+            notice: None,
+            scroll: 0,
+            selected: None,
             telegram_callback: Arc::new(RwLock::new(None)),
         }
     }
@@ -70,154 +338,606 @@ impl TuiApp {
         }
     }
 
-    fn add_message(&mut self, role: &str, content: &str) {
-        let is_user = role == "user";
-        self.messages.push((format!("{}: {}", if is_user { "You" } else { "RustyClaw" }, content), is_user));
+    /// Tab completion: extend to the longest common prefix of the matching
+    /// commands, or cycle through them once the prefix can't grow further.
+    fn complete_command(&mut self) {
+        let prefix = match &self.completion {
+            Some((prefix, _)) => prefix.clone(),
+            None => self.input.clone(),
+        };
+        let matches = command_matches(&self.command_list, &prefix);
+        let names: Vec<&str> = matches.iter().map(|(usage, _)| command_name(usage)).collect();
+
+        match names.as_slice() {
+            [] => {}
+            [only] => {
+                self.input = format!("{} ", only);
+                self.completion = None;
+            }
+            _ => {
+                let common = common_prefix(&names);
+                if self.completion.is_none() && common.len() > self.input.len() {
+                    self.input = common.to_string();
+                } else {
+                    let next = match &self.completion {
+                        Some((_, i)) => (i + 1) % names.len(),
+                        None => 0,
+                    };
+                    self.input = names[next].to_string();
+                    self.completion = Some((prefix, next));
+                }
+            }
+        }
     }
 
-    fn add_status(&mut self, emoji: &str, message: &str) {
-        self.messages.push((format!("{} {}", emoji, message), false));
+    /// Copy the last code block of the latest reply, or the whole reply if it
+    /// has none, to the system clipboard.
+    fn copy_last_response(&mut self) {
+        if let Some((message, _)) = self.selected.and_then(|i| self.messages.get(i)) {
+            let message = message.clone();
+            self.copy("selected message", message);
+            return;
+        }
+        let Some(response) = self.last_response.as_deref() else {
+            self.notify("Nothing to copy yet");
+            return;
+        };
+        let (what, text) = match Agent::extract_code_blocks(response).pop() {
+            Some((_, code)) => ("code block", code),
+            None => ("reply", response.to_string()),
+        };
+        self.copy(&format!("last {}", what), text);
     }
 
-    async fn process_message(&mut self, user_text: String) {
-        self.processing = true;
-        self.add_message("user", &user_text);
+    fn copy(&mut self, what: &str, text: String) {
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => {
+                    self.notify(&format!("📋 No clipboard available ({})", e));
+                    return;
+                }
+            }
+        }
+        let result = self.clipboard.as_mut().map(|c| c.set_text(text));
+        match result {
+            Some(Ok(())) => self.notify(&format!("📋 Copied {} to clipboard", what)),
+            Some(Err(e)) => self.notify(&format!("📋 Copy failed: {}", e)),
+            None => {}
+        }
+    }
 
-        self.memory.add_message("user", &user_text).await.ok();
-        
-        let history = self.memory.get_history(self.config.memory.max_history).await.unwrap_or_default();
+    /// The rows shown in a chat view `rows` tall, top to bottom, each with
+    /// the index in `messages` of the message it belongs to. Only messages
+    /// in view are styled.
+    fn visible_rows(&self, rows: usize) -> Vec<(usize, Line<'static>)> {
+        let mut below = self.scroll;
+        let mut visible = Vec::new();
+        for (i, (message, _)) in self.messages.iter().enumerate().rev() {
+            if visible.len() == rows {
+                break;
+            }
+            let height = message_height(message);
+            if below >= height {
+                below -= height;
+                continue;
+            }
+            let lines = self.message_lines(i).into_iter().rev().skip(below);
+            visible.extend(lines.take(rows - visible.len()).map(|line| (i, line)));
+            below = 0;
+        }
+        visible.reverse();
+        visible
+    }
 
-        let response = self.agent.chat(&history).await.unwrap_or_else(|e| {
-            format!("Sorry, I had trouble thinking about that. Error: {}", e)
-        });
+    /// How many messages are at least partly below the chat view.
+    fn messages_below(&self) -> usize {
+        let mut hidden = 0;
+        self.messages
+            .iter()
+            .rev()
+            .take_while(|(message, _)| {
+                let below = hidden < self.scroll;
+                hidden += message_height(message);
+                below
+            })
+            .count()
+    }
 
-        let (cron_jobs, cron_errors) = Agent::parse_cron_blocks(&response);
-        
-        for error in cron_errors {
-            self.add_status("⚠️", &format!("Cron error: {}", error));
+    fn message_lines(&self, i: usize) -> Vec<Line<'static>> {
+        let (message, is_user) = &self.messages[i];
+        if self.selected == Some(i) {
+            return message_lines(message, self.theme.selected, self.theme.selected, None);
         }
+        let text = if *is_user { self.theme.user } else { self.theme.text };
+        message_lines(message, text, self.theme.code, self.highlighter.as_ref())
+    }
 
-        for job in cron_jobs {
-            match self.scheduler.add_job(&job.schedule, &job.task, &job.message).await {
-                Ok(job_id) => {
-                    self.add_status("✅", &format!("Scheduled job #{}: {} ({})", job_id, job.task, job.schedule));
-                }
-                Err(e) => {
-                    self.add_status("❌", &format!("Error scheduling: {}", e));
-                }
+    /// Scroll the chat view `rows` tall up by `SCROLL_STEP` rows, or down
+    /// when `up` is false.
+    fn scroll(&mut self, up: bool, rows: usize) {
+        if up {
+            let total: usize = self.messages.iter().map(|(m, _)| message_height(m)).sum();
+            let max = total.saturating_sub(rows);
+            self.scroll = (self.scroll + SCROLL_STEP).min(max);
+        } else {
+            self.scroll = self.scroll.saturating_sub(SCROLL_STEP);
+        }
+    }
+
+    /// Replace the input with an earlier line (`back`) or a later one, like
+    /// a shell. Moving past the newest line clears the input.
+    fn recall(&mut self, back: bool) {
+        let Some(newest) = self.sent.len().checked_sub(1) else {
+            return;
+        };
+        self.recall = match (self.recall, back) {
+            (None, true) => Some(newest),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i < newest => Some(i + 1),
+            (_, false) => None,
+        };
+        self.input = self.recall.map(|i| self.sent[i].clone()).unwrap_or_default();
+    }
+
+    /// Scroll the chat with the wheel and select messages by clicking them.
+    /// Other mouse events (moves, drags, other buttons) are ignored.
+    fn handle_mouse(&mut self, mouse: MouseEvent, area: Rect) {
+        let chat = split_chat(layout(area, &self.input)[1], self.panel.is_some()).0.inner(Margin::new(1, 1));
+        let rows = chat.height as usize;
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.scroll(true, rows),
+            MouseEventKind::ScrollDown => self.scroll(false, rows),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let clicked = chat
+                    .contains(Position::new(mouse.column, mouse.row))
+                    .then(|| self.visible_rows(rows).get((mouse.row - chat.y) as usize).map(|(i, _)| *i))
+                    .flatten();
+                self.selected = if clicked == self.selected { None } else { clicked };
             }
+            _ => {}
         }
+    }
 
-        let save_blocks = Agent::parse_save_blocks(&response);
-        for block in save_blocks {
-            match self.workspace.save_file(&block.filename, &block.content).await {
-                Ok(path) => {
-                    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or(&block.filename);
-                    self.add_status("💾", &format!("Saved {} to workspace", name));
-                }
-                Err(e) => {
-                    self.add_status("❌", &format!("Error saving file: {}", e));
+    async fn toggle_panel(&mut self) {
+        if self.panel.take().is_none() {
+            let mut panel = Panel::default();
+            panel.refresh(&self.scheduler, &self.workspace).await;
+            self.panel = Some(panel);
+        }
+    }
+
+    /// Keys while the panel is open: type to filter, move with the history
+    /// keys, and send to act on the selected entry. Clearing an empty
+    /// filter closes the panel.
+    async fn handle_panel_key(&mut self, key: KeyEvent, action: Option<Action>) {
+        let Some(panel) = self.panel.as_mut() else {
+            return;
+        };
+        match action {
+            Some(Action::HistoryPrev) => panel.move_selection(true),
+            Some(Action::HistoryNext) => panel.move_selection(false),
+            Some(Action::ClearInput) if panel.filter.is_empty() => self.panel = None,
+            Some(Action::ClearInput) => panel.set_filter(String::new()),
+            Some(Action::Send) => {
+                if let Some(entry) = panel.selected() {
+                    self.open_entry(entry).await;
                 }
             }
+            Some(_) => {}
+            None => match key.code {
+                KeyCode::Char(c) => {
+                    let filter = format!("{}{}", panel.filter, c);
+                    panel.set_filter(filter);
+                }
+                KeyCode::Backspace => {
+                    let mut filter = panel.filter.clone();
+                    filter.pop();
+                    panel.set_filter(filter);
+                }
+                _ => {}
+            },
         }
+    }
 
-        let memory_blocks = Agent::parse_memory_blocks(&response);
-        for fact in memory_blocks {
-            if self.agent.save_to_memory(&fact).await.unwrap_or(false) {
-                self.add_status("🧠", &format!("Remembered: {}", fact));
+    /// Cancel a job, or show a file in the chat.
+    async fn open_entry(&mut self, entry: Entry) {
+        match entry {
+            Entry::Job(job) => match self.scheduler.cancel_job(job.id).await {
+                Ok(true) => self.add_status("✅", &format!("Cancelled job #{}: {}", job.id, job.task)),
+                Ok(false) => self.add_status("ℹ️", &format!("Job #{} not found.", job.id)),
+                Err(e) => self.add_status("❌", &format!("Error: {}", e)),
+            },
+            Entry::File(file) => match self.workspace.read_file(&file.name) {
+                Some(content) => {
+                    self.add_status("📄", &file.name);
+                    let lines: Vec<&str> = content.lines().collect();
+                    for line in lines.iter().take(MAX_VIEW_LINES) {
+                        self.messages.push((format!("    {}", line), false));
+                    }
+                    if lines.len() > MAX_VIEW_LINES {
+                        self.add_status("…", &format!("{} more lines", lines.len() - MAX_VIEW_LINES));
+                    }
+                    self.scroll = 0;
+                }
+                None => self.add_status("❌", &format!("{} can't be shown as text", file.name)),
+            },
+        }
+        if let Some(panel) = self.panel.as_mut() {
+            panel.refresh(&self.scheduler, &self.workspace).await;
+        }
+    }
+
+    fn notify(&mut self, message: &str) {
+        self.notice = Some((message.to_string(), Instant::now()));
+    }
+
+    fn add_message(&mut self, role: &str, content: &str) {
+        let time = chrono::Local::now().format("%H:%M").to_string();
+        self.add_message_at(role, content, &time);
+    }
+
+    fn add_message_at(&mut self, role: &str, content: &str, time: &str) {
+        let is_user = role == "user";
+        self.messages.push((
+            format!("[{}] {}: {}", time, if is_user { "You" } else { "RustyClaw" }, content),
+            is_user,
+        ));
+    }
+
+    fn add_status(&mut self, emoji: &str, message: &str) {
+        self.messages.push((format!("{} {}", emoji, message), false));
+    }
+
+    fn context(&self) -> Context {
+        Context {
+            agent: self.agent.clone(),
+            memory: self.memory.clone(),
+            scheduler: self.scheduler.clone(),
+            workspace: self.workspace.clone(),
+            max_history: self.config.memory.max_history,
+            confirmations: self.confirmations.clone(),
+            undo: self.undo.clone(),
+            user: None,
+        }
+    }
+
+    /// Send `user_text` to the model, or, while a reply is still coming,
+    /// queue it or hand it back to the input box.
+    fn submit(&mut self, user_text: String) {
+        if !self.processing {
+            self.process_message(user_text);
+        } else if self.config.tui.queue_when_busy && self.queued.is_none() {
+            self.queued = Some(user_text);
+            self.notify("Queued; it is sent when the current reply arrives");
+        } else {
+            self.input = user_text;
+            self.notify_busy();
+        }
+    }
+
+    fn notify_busy(&mut self) {
+        let notice = format!("Still thinking… {}stop cancels the current reply", self.config.command_prefix);
+        self.notify(&notice);
+    }
+
+    /// Start answering `user_text` in the background; `finish_message`
+    /// shows the reply once it's ready.
+    fn process_message(&mut self, user_text: String) {
+        self.processing = true;
+        self.add_message("user", &user_text);
+
+        let attachment = self.attachment.take();
+        let context = self.context();
+        let text = user_text.clone();
+        let task = tokio::spawn(async move { context.respond_with(&text, attachment.as_ref()).await });
+        self.pending = Some((user_text, task));
+    }
+
+    async fn finish_message(&mut self) {
+        let Some((user_text, task)) = self.pending.take() else {
+            return;
+        };
+        let reply = match task.await {
+            Ok(reply) => reply,
+            Err(e) => {
+                self.add_status("❌", &format!("Error: {}", e));
+                self.processing = false;
+                return;
             }
+        };
+
+        for action in &reply.actions {
+            self.add_status(action.emoji(), &action.to_string());
         }
 
-        let clean = Agent::clean_response(&response);
-        if !clean.is_empty() {
-            self.add_message("assistant", &clean);
+        if !reply.text.is_empty() {
+            self.last_reply = Some(self.messages.len());
+            self.add_message("assistant", &reply.text);
+            self.last_response = Some(reply.text.clone());
         }
 
-        self.memory.add_message("assistant", &response).await.ok();
+        self.send_to_telegram(&format!("💻 TUI: {}\n\n{}", user_text, reply.text)).await;
 
-        self.send_to_telegram(&format!("💻 TUI: {}\n\n{}", user_text, clean)).await;
+        self.processing = false;
+        if let Some(next) = self.queued.take() {
+            self.process_message(next);
+        }
+    }
 
+    /// Cancel the reply being generated, and any queued message.
+    fn stop(&mut self) {
+        let Some((_, task)) = self.pending.take() else {
+            self.add_status("ℹ️", "Nothing to stop.");
+            return;
+        };
+        task.abort();
         self.processing = false;
+        self.notice = None;
+        match self.queued.take() {
+            Some(queued) => {
+                self.add_status("⏹️", "Stopped. The queued message was returned to the input.");
+                self.input = queued;
+            }
+            None => self.add_status("⏹️", "Stopped."),
+        }
     }
 
-    async fn handle_command(&mut self, command: &str) {
+    /// Ask for a new take on the last reply and show it where the old one was.
+    async fn regenerate(&mut self) {
+        self.processing = true;
+        match self.context().regenerate().await {
+            Ok(reply) => {
+                for action in &reply.actions {
+                    self.add_status(action.emoji(), &action.to_string());
+                }
+                if !reply.text.is_empty() {
+                    let old = self.last_reply.filter(|&i| i < self.messages.len());
+                    self.add_message("assistant", &reply.text);
+                    if let Some(i) = old {
+                        self.messages.swap_remove(i);
+                    } else {
+                        self.last_reply = Some(self.messages.len() - 1);
+                    }
+                    self.last_response = Some(reply.text);
+                }
+            }
+            Err(e) => self.add_status("ℹ️", &e.to_string()),
+        }
+        self.processing = false;
+    }
+
+    /// Run `input`, a line starting with the command prefix. Lines that
+    /// aren't a known command are sent to the model as typed.
+    async fn handle_command(&mut self, input: &str) {
+        let command = as_command(input, &self.config.command_prefix).unwrap_or_else(|| input.to_string());
+        let command = command.as_str();
         let parts: Vec<&str> = command.split_whitespace().collect();
-        let cmd = parts.get(0).map(|s| s.to_lowercase()).unwrap_or_default();
+        let cmd = parts.first().map(|s| s.to_lowercase()).unwrap_or_default();
 
         match cmd.as_str() {
             "/quit" | "/exit" => {
-                std::process::exit(0);
+                self.quit = true;
+            }
+            "/clear" if !self.context().confirm("clear").await => {
+                self.add_status(
+                    "⚠️",
+                    &format!(
+                        "This deletes the conversation history. Send /clear again within {} seconds to confirm.",
+                        CONFIRM_WINDOW.as_secs()
+                    ),
+                );
             }
             "/clear" => {
                 self.memory.clear_history().await.ok();
                 self.messages.clear();
+                self.last_reply = None;
                 self.add_status("🧹", "Chat history cleared");
             }
-            "/status" => {
-                let jobs = self.scheduler.list_jobs().await.unwrap_or_default();
-                let files = self.workspace.list_files();
-                self.add_status("🦀", &format!(
-                    "Model: {} | Host: {} | Jobs: {} | Files: {}",
-                    self.config.ollama.model,
-                    self.config.ollama.host,
-                    jobs.len(),
-                    files.len()
-                ));
-            }
-            "/jobs" => {
-                let jobs = self.scheduler.list_jobs().await.unwrap_or_default();
-                if jobs.is_empty() {
-                    self.add_status("ℹ️", "No scheduled jobs");
-                } else {
-                    for job in jobs {
-                        self.add_status("🕐", &format!("#{}: {} ({})", job.id, job.task, job.schedule));
+            "/schedule" => match parse_schedule_args(&parts[1..]) {
+                Some((schedule, message)) => {
+                    let task = task_label(&message);
+                    match self.scheduler.add_job(&schedule, &task, &message).await {
+                        Ok(job_id) => self.add_status("✅", &format!("Scheduled job #{}: {} ({})", job_id, task, schedule)),
+                        Err(e) => self.add_status("❌", &format!("Could not schedule: {}", e)),
                     }
                 }
-            }
+                None => self.add_status("ℹ️", "Usage: /schedule <min> <hour> <day> <month> <weekday> <message>"),
+            },
+            "/cancel" => match parts.get(1).map(|id| id.parse::<i64>()) {
+                Some(Ok(job_id)) => match self.scheduler.cancel_job(job_id).await {
+                    Ok(true) => self.add_status("✅", &format!("Cancelled job #{}", job_id)),
+                    Ok(false) => self.add_status("ℹ️", &format!("Job #{} not found.", job_id)),
+                    Err(e) => self.add_status("❌", &format!("Error: {}", e)),
+                },
+                Some(Err(_)) => self.add_status("❌", &format!("'{}' is not a job id", parts[1])),
+                None => self.add_status("ℹ️", "Usage: /cancel <job_id>"),
+            },
+            "/cancelall" => match self.scheduler.cancel_all().await {
+                Ok(0) => self.add_status("ℹ️", "No scheduled jobs to cancel"),
+                Ok(n) => self.add_status("✅", &format!("Cancelled {} job(s)", n)),
+                Err(e) => self.add_status("❌", &format!("Error: {}", e)),
+            },
             "/workspace" => {
-                let files = self.workspace.list_files();
+                let files = self.workspace.list_files_with_metadata().await;
                 if files.is_empty() {
                     self.add_status("ℹ️", "Workspace is empty");
                 } else {
                     for f in files {
                         let size_kb = f.size as f64 / 1024.0;
-                        self.add_status("📁", &format!("{} ({:.1} KB)", f.name, size_kb));
+                        let mut line = match &f.language {
+                            Some(lang) => format!("{} [{}] ({:.1} KB)", f.name, lang, size_kb),
+                            None => format!("{} ({:.1} KB)", f.name, size_kb),
+                        };
+                        if let Some(desc) = &f.description {
+                            line.push_str(&format!(" — {}", desc));
+                        }
+                        self.add_status("📁", &line);
                     }
                 }
             }
-            "/memory" => {
-                let content = self.agent.memory_content().await;
-                if content.is_empty() {
-                    self.add_status("🧠", "No memories saved yet");
+            "/log" => {
+                match parts.get(1) {
+                    Some(filename) => match self.workspace.file_log(filename, 10) {
+                        Ok(entries) if entries.is_empty() => {
+                            self.add_status("ℹ️", &format!("No history for {}", filename));
+                        }
+                        Ok(entries) => {
+                            for entry in entries {
+                                self.add_status("📜", &entry);
+                            }
+                        }
+                        Err(e) => self.add_status("❌", &e.to_string()),
+                    },
+                    None => self.add_status("ℹ️", "Usage: /log <filename>"),
+                }
+            }
+            "/search" => {
+                let pattern = command.split_once(' ').map(|(_, p)| p.trim()).unwrap_or("");
+                if pattern.is_empty() {
+                    self.add_status("ℹ️", "Usage: /search <pattern>");
                 } else {
-                    for line in content.lines().take(10) {
-                        self.messages.push((line.to_string(), false));
+                    let results = self.workspace.search(pattern);
+                    if results.is_empty() {
+                        self.add_status("ℹ️", &format!("No matches for '{}'", pattern));
+                    } else {
+                        for (name, line_no, line) in results {
+                            self.add_status("🔎", &format!("{}:{}: {}", name, line_no, line));
+                        }
                     }
                 }
             }
-            "/forget" => {
-                if self.agent.clear_memory().await.is_ok() {
-                    self.add_status("🧹", "All memories forgotten");
+            "/find" => {
+                let query = command.split_once(' ').map(|(_, q)| q.trim()).unwrap_or("");
+                if query.is_empty() {
+                    self.add_status("ℹ️", "Usage: /find <words>");
                 } else {
-                    self.add_status("❌", "Failed to clear memory");
+                    match self.memory.search_history(query, 10).await {
+                        Ok(found) if found.is_empty() => {
+                            self.add_status("ℹ️", &format!("No messages matching '{}'", query));
+                        }
+                        Ok(found) => {
+                            for hit in found {
+                                self.add_status("🔎", &hit.to_string());
+                            }
+                        }
+                        Err(e) => self.add_status("❌", &format!("Search failed: {}", e)),
+                    }
                 }
             }
+            "/export" => {
+                match self.memory.export_transcript().await {
+                    Ok(transcript) if transcript.is_empty() => {
+                        self.add_status("ℹ️", "No conversation history to export");
+                    }
+                    Ok(transcript) => match self.workspace.save_file("conversation.txt", &transcript, None, Some("Conversation transcript")).await {
+                        Ok(saved) => self.add_status("💾", &format!(
+                            "Exported chat to {} ({})",
+                            saved.path.display(),
+                            format_size(saved.bytes)
+                        )),
+                        Err(e) => self.add_status("❌", &format!("Export failed: {}", e)),
+                    },
+                    Err(e) => self.add_status("❌", &format!("Export failed: {}", e)),
+                }
+            }
+            "/maintenance" => {
+                match self.memory.vacuum().await {
+                    Ok((before, after)) => self.add_status("🧰", &format!(
+                        "Database compacted: {:.1} KB -> {:.1} KB",
+                        before as f64 / 1024.0,
+                        after as f64 / 1024.0
+                    )),
+                    Err(e) => self.add_status("❌", &format!("Maintenance failed: {}", e)),
+                }
+            }
+            "/use" => {
+                let Some(filename) = parts.get(1) else {
+                    self.add_status("ℹ️", "Usage: /use <filename>");
+                    return;
+                };
+                match self.context().attach(filename).await {
+                    Ok(attachment) => {
+                        let note = if attachment.truncated { ", truncated to fit the context" } else { "" };
+                        self.add_status("📎", &format!("{} will be sent with your next message{}", attachment.name, note));
+                        self.attachment = Some(attachment);
+                    }
+                    Err(e) => self.add_status("❌", &e.to_string()),
+                }
+            }
+            "/save" => {
+                if parts.len() < 2 {
+                    self.add_status("ℹ️", "Usage: /save <filename> [description]");
+                    return;
+                }
+                let filename = parts[1];
+                let description = Some(parts[2..].join(" ")).filter(|d| !d.is_empty());
+
+                let history = self.memory.get_history(10).await.unwrap_or_default();
+                let block = history
+                    .iter()
+                    .rev()
+                    .filter(|m| m.role == "assistant")
+                    .find_map(|m| Agent::extract_code_blocks(&m.content).into_iter().next());
+
+                match block {
+                    Some((lang, code)) => {
+                        let language = Some(lang.as_str()).filter(|l| *l != "text");
+                        match self.workspace.save_file(filename, &code, language, description.as_deref()).await {
+                            Ok(saved) => self.add_status("💾", &format!(
+                                "Saved {} ({}) to workspace",
+                                saved.name(),
+                                format_size(saved.bytes)
+                            )),
+                            Err(e) => self.add_status("❌", &format!("Error saving file: {}", e)),
+                        }
+                    }
+                    None => self.add_status("❌", "No code blocks found in recent conversation."),
+                }
+            }
+            "/rename" => {
+                if parts.len() == 3 {
+                    match self.workspace.rename_file(parts[1], parts[2]).await {
+                        Ok(()) => self.add_status("✏️", &format!("Renamed {} to {}", parts[1], parts[2])),
+                        Err(e) => self.add_status("❌", &format!("Error renaming file: {}", e)),
+                    }
+                } else {
+                    self.add_status("ℹ️", "Usage: /rename <old> <new>");
+                }
+            }
+            "/stop" => self.stop(),
+            "/regenerate" if self.processing => self.notify_busy(),
+            "/regenerate" => self.regenerate().await,
             "/help" => {
-                let help = r#"Commands:
-/quit - Exit
-/clear - Clear history
-/status - Show status
-/jobs - List cron jobs
-/workspace - List files
-/memory - View memories
-/forget - Clear memories
-/help - This message"#;
-                for line in help.lines() {
-                    self.messages.push((line.to_string(), false));
+                self.messages.push(("Commands:".to_string(), false));
+                for (usage, description) in self.command_list.clone() {
+                    self.messages.push((format!("{} - {}", usage, description), false));
                 }
+                self.messages.push((
+                    format!(
+                        "Keys: {} starts a new line, {} clears the input, {} opens the jobs and files panel, {} or {}quit exits",
+                        self.keymap.label(Action::Newline),
+                        self.keymap.label(Action::ClearInput),
+                        self.keymap.label(Action::TogglePanel),
+                        self.keymap.label(Action::Quit),
+                        self.config.command_prefix
+                    ),
+                    false,
+                ));
             }
             _ => {
-                self.process_message(command.to_string()).await;
+                let output = match self.commands.parse(command) {
+                    Some((name, args)) => self.commands.dispatch(name, args, &self.context()).await,
+                    None => None,
+                };
+                match output {
+                    Some(output) => {
+                        for line in output.lines() {
+                            self.messages.push((line.to_string(), false));
+                        }
+                    }
+                    None => self.submit(input.to_string()),
+                }
             }
         }
     }
@@ -229,64 +949,178 @@ pub async fn run_tui(
     memory: Arc<Memory>,
     scheduler: Arc<Scheduler>,
     workspace: Arc<Workspace>,
+    commands: Arc<CommandRegistry>,
 ) -> Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste)?;
+    if config.tui.mouse {
+        execute!(stdout, EnableMouseCapture)?;
+    }
     
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    let started = Utc::now();
+
+    let (reminder_tx, mut reminder_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    scheduler.add_send_callback(move |msg: String| {
+        let tx = reminder_tx.clone();
+        async move {
+            tx.send(msg).ok();
+        }
+    }).await;
 
-    let mut app = TuiApp::new(config, agent, memory, scheduler, workspace);
+    let mut app = TuiApp::new(config, agent, memory, scheduler, workspace, commands);
 
-    app.add_status("🦀", "Welcome to RustyClaw!");
-    app.add_status("ℹ️", "Type /help for commands");
+    let greeting = app.config.tui.greeting.clone().unwrap_or_else(|| "Welcome to RustyClaw!".to_string());
+    app.add_status("🦀", &greeting);
+    app.add_status("ℹ️", &format!("Type {}help for commands", app.config.command_prefix));
+
+    // Generated off the UI thread; shown when it arrives and never stored.
+    let (greeting_tx, mut greeting_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    if app.config.tui.ai_greeting {
+        let agent = app.agent.clone();
+        let memory = app.memory.clone();
+        tokio::spawn(async move {
+            let mut messages = memory.get_history(GREETING_HISTORY).await.unwrap_or_default();
+            messages.push(Message {
+                role: "user".to_string(),
+                content: GREETING_PROMPT.to_string(),
+                timestamp: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+                model: None,
+            });
+            match agent.try_chat(&messages).await {
+                Ok(response) => {
+                    greeting_tx.send(Agent::clean_response(&agent.strip_thinking(&response).await)).ok();
+                }
+                Err(e) => tracing::warn!("Could not generate a greeting: {}", e),
+            }
+        });
+    }
 
     let history = app.memory.get_history(20).await.unwrap_or_default();
     if !history.is_empty() {
         app.messages.push(("── Previous Conversation ──".to_string(), false));
         for msg in history {
+            let time = msg
+                .local_time()
+                .map(|t| t.format("%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| msg.timestamp.clone());
             let content = if msg.role == "assistant" {
-                Agent::clean_response(&msg.content)
+                let clean = Agent::clean_response(&app.agent.strip_thinking(&msg.content).await);
+                app.last_response = Some(clean.clone());
+                app.last_reply = Some(app.messages.len());
+                clean
             } else {
                 msg.content
             };
-            app.add_message(&msg.role, &content);
+            app.add_message_at(&msg.role, &content, &time);
         }
     }
 
-    loop {
+    while !app.quit {
+        while let Ok(greeting) = greeting_rx.try_recv() {
+            app.add_message("assistant", &greeting);
+        }
+        while let Ok(reminder) = reminder_rx.try_recv() {
+            app.add_status("⏰", &reminder);
+        }
+        if app.pending.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            app.finish_message().await;
+        }
+        if let Some(panel) = app.panel.as_mut().filter(|p| p.needs_refresh()) {
+            panel.refresh(&app.scheduler, &app.workspace).await;
+        }
+
         terminal.draw(|f| ui(f, &app))?;
 
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        break;
+        if event::poll(Duration::from_millis(100))? {
+            match event::read()? {
+                Event::Key(key) => {
+                    let action = app.keymap.action(&key);
+                    if action != Some(Action::Complete) {
+                        app.completion = None;
                     }
-                    KeyCode::Enter => {
-                        let input = app.input.clone();
-                        app.input.clear();
-                        
-                        if !input.is_empty() {
-                            if input.starts_with('/') {
-                                app.handle_command(&input).await;
-                            } else {
-                                app.process_message(input).await;
+                    let panel_key = !matches!(
+                        action,
+                        Some(Action::Quit | Action::TogglePanel | Action::ScrollUp | Action::ScrollDown)
+                    );
+                    if app.panel.is_some() && panel_key {
+                        app.handle_panel_key(key, action).await;
+                        continue;
+                    }
+                    match action {
+                        Some(Action::Quit) => {
+                            break;
+                        }
+                        Some(Action::TogglePanel) => {
+                            app.toggle_panel().await;
+                        }
+                        Some(Action::Copy) => {
+                            app.copy_last_response();
+                        }
+                        Some(Action::Complete) => {
+                            app.complete_command();
+                        }
+                        Some(Action::ClearInput) => {
+                            app.input.clear();
+                            app.recall = None;
+                            app.selected = None;
+                        }
+                        Some(Action::Newline) => {
+                            app.input.push('\n');
+                        }
+                        Some(Action::ScrollUp | Action::ScrollDown) => {
+                            let size = terminal.size()?;
+                            let chat = layout(Rect::new(0, 0, size.width, size.height), &app.input)[1];
+                            // The panel splits the chat row sideways, so its height is unchanged.
+                            app.scroll(action == Some(Action::ScrollUp), chat.height.saturating_sub(2) as usize);
+                        }
+                        Some(Action::HistoryPrev) => app.recall(true),
+                        Some(Action::HistoryNext) => app.recall(false),
+                        Some(Action::Send) => {
+                            let input = app.input.clone();
+                            app.input.clear();
+                            app.scroll = 0;
+                            app.selected = None;
+                            app.recall = None;
+
+                            if !input.is_empty() {
+                                if app.sent.last() != Some(&input) {
+                                    app.sent.push(input.clone());
+                                }
+                                if as_command(&input, &app.config.command_prefix).is_some() {
+                                    app.handle_command(&input).await;
+                                } else {
+                                    app.submit(input);
+                                }
                             }
                         }
+                        None => match key.code {
+                            KeyCode::Char(c) => {
+                                app.input.push(c);
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                            }
+                            _ => {}
+                        },
                     }
-                    KeyCode::Char(c) => {
-                        app.input.push(c);
-                    }
-                    KeyCode::Backspace => {
-                        app.input.pop();
-                    }
-                    KeyCode::Esc => {
-                        break;
-                    }
-                    _ => {}
                 }
+                // Bracketed paste delivers a multi-line paste whole, instead
+                // of as key presses whose newlines would each send a line.
+                Event::Paste(text) if app.panel.is_none() => {
+                    app.input.push_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+                }
+                Event::Mouse(mouse) => {
+                    let size = terminal.size()?;
+                    app.handle_mouse(mouse, Rect::new(0, 0, size.width, size.height));
+                }
+                Event::Resize(..) => {
+                    // Pick up the new size now; the chat view re-clamps to it on the redraw.
+                    terminal.autoresize()?;
+                }
+                _ => {}
             }
         }
     }
@@ -295,59 +1129,190 @@ pub async fn run_tui(
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
+        DisableBracketedPaste,
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
 
+    save_transcript(&app.config, &app.agent, &app.memory, started).await;
     Ok(())
 }
 
-fn ui(f: &mut Frame, app: &TuiApp) {
-    let chunks = Layout::default()
+/// Put the terminal back into cooked mode if the TUI is torn down from outside
+/// its own event loop (e.g. on SIGTERM).
+pub fn restore_terminal() {
+    disable_raw_mode().ok();
+    execute!(io::stdout(), LeaveAlternateScreen, DisableBracketedPaste, DisableMouseCapture).ok();
+}
+
+/// Write the messages stored in the active session since `started` to
+/// `tui.transcript_dir` as `transcript-<session>-<time>.txt`, with reasoning
+/// and action blocks removed from replies. Does nothing if the directory
+/// isn't set or nothing was said. Failures are logged.
+pub async fn save_transcript(config: &Config, agent: &Agent, memory: &Memory, started: DateTime<Utc>) {
+    let Some(dir) = &config.tui.transcript_dir else {
+        return;
+    };
+    match write_transcript(dir, agent, memory, started).await {
+        Ok(Some(path)) => tracing::info!("Transcript saved to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Could not save the transcript: {}", e),
+    }
+}
+
+async fn write_transcript(dir: &Path, agent: &Agent, memory: &Memory, started: DateTime<Utc>) -> Result<Option<PathBuf>> {
+    let mut messages = memory.history_since(&started.format("%Y-%m-%d %H:%M:%S").to_string()).await?;
+    if messages.is_empty() {
+        return Ok(None);
+    }
+    for message in messages.iter_mut().filter(|m| m.role == "assistant") {
+        message.content = Agent::clean_response(&agent.strip_thinking(&message.content).await);
+    }
+
+    let session = memory.active_session().await?;
+    let file_session: String = session
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("transcript-{}-{}.txt", file_session, Local::now().format("%Y%m%d-%H%M%S")));
+    std::fs::write(
+        &path,
+        format!("RustyClaw transcript, session {}\n\n{}\n", session, format_transcript(&messages)),
+    )?;
+    Ok(Some(path))
+}
+
+/// Title, chat, input, and hint rows, top to bottom. The input box is as
+/// tall as `input`, up to `MAX_INPUT_LINES`.
+fn layout(area: Rect, input: &str) -> Rc<[Rect]> {
+    Layout::default()
         .direction(Direction::Vertical)
         .margin(1)
         .constraints([
             Constraint::Length(3),
             Constraint::Min(1),
-            Constraint::Length(3),
+            Constraint::Length(input_lines(input).min(MAX_INPUT_LINES) + 2),
             Constraint::Length(1),
         ])
-        .split(f.area());
+        .split(area)
+}
+
+/// The chat pane and, when `panel` is set, the side panel to its right.
+fn split_chat(area: Rect, panel: bool) -> (Rect, Option<Rect>) {
+    if !panel {
+        return (area, None);
+    }
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(20), Constraint::Length(PANEL_WIDTH)])
+        .split(area);
+    (columns[0], Some(columns[1]))
+}
+
+fn input_lines(input: &str) -> u16 {
+    input.split('\n').count().try_into().unwrap_or(u16::MAX)
+}
+
+fn ui(f: &mut Frame, app: &TuiApp) {
+    let chunks = layout(f.area(), &app.input);
+    let (chat_area, panel_area) = split_chat(chunks[1], app.panel.is_some());
+    if let (Some(panel), Some(area)) = (&app.panel, panel_area) {
+        panel.render(f, area, &app.theme);
+    }
 
     let title = Paragraph::new("🦀 RustyClaw")
-        .style(Style::default().fg(Color::Green).add_modifier(Modifier::BOLD))
+        .style(app.theme.title)
         .block(Block::default().borders(Borders::ALL));
     f.render_widget(title, chunks[0]);
 
-    let messages: Vec<ListItem> = app
-        .messages
-        .iter()
-        .map(|(msg, is_user)| {
-            let style = if *is_user {
-                Style::default().fg(Color::Cyan)
-            } else {
-                Style::default().fg(Color::White)
-            };
-            ListItem::new(Line::from(Span::styled(msg, style)))
-        })
-        .collect();
+    // Show the newest messages that fit the current viewport, or older ones
+    // while scrolled up.
+    let rows = app.visible_rows(chat_area.height.saturating_sub(2) as usize);
+    let below = app.messages_below();
+    let messages: Vec<ListItem> = rows.into_iter().map(|(_, line)| ListItem::new(line)).collect();
 
-    let messages = List::new(messages)
-        .block(Block::default().borders(Borders::ALL).title("Chat"));
-    f.render_widget(messages, chunks[1]);
-
-    let input_style = if app.processing {
-        Style::default().fg(Color::Yellow)
+    let title = if below > 0 {
+        format!("Chat ({} newer below)", below)
     } else {
-        Style::default().fg(Color::White)
+        "Chat".to_string()
     };
-    
+    let messages = List::new(messages)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(messages, chat_area);
+
+    let input_style = if app.processing { app.theme.busy } else { app.theme.text };
+    // Past MAX_INPUT_LINES, keep the lines being typed in view.
+    let hidden = input_lines(&app.input).saturating_sub(MAX_INPUT_LINES);
     let input = Paragraph::new(app.input.as_str())
+        .scroll((hidden, 0))
         .style(input_style)
         .block(Block::default().borders(Borders::ALL).title(if app.processing { "Thinking..." } else { "Input" }));
     f.render_widget(input, chunks[2]);
 
-    let help = Paragraph::new("Enter: Send | Ctrl+C: Quit | /help for commands")
-        .style(Style::default().fg(Color::DarkGray));
+    let help = match &app.notice {
+        Some((notice, at)) if at.elapsed() < NOTICE_DURATION => {
+            Paragraph::new(notice.as_str()).style(app.theme.busy)
+        }
+        _ if app.panel.is_some() => Paragraph::new(format!(
+            "Type: Filter | {}/{}: Select | {}: Cancel job or view file | {}: Clear filter | {}: Close panel",
+            app.keymap.label(Action::HistoryPrev),
+            app.keymap.label(Action::HistoryNext),
+            app.keymap.label(Action::Send),
+            app.keymap.label(Action::ClearInput),
+            app.keymap.label(Action::TogglePanel),
+        ))
+        .style(app.theme.hint),
+        _ => Paragraph::new(format!(
+            "{}: Send | {}: New line | {}: Complete | {}: Copy | {}: Jobs & files | {}: Quit | {}help for commands",
+            app.keymap.label(Action::Send),
+            app.keymap.label(Action::Newline),
+            app.keymap.label(Action::Complete),
+            app.keymap.label(Action::Copy),
+            app.keymap.label(Action::TogglePanel),
+            app.keymap.label(Action::Quit),
+            app.config.command_prefix,
+        ))
+        .style(app.theme.hint),
+    };
     f.render_widget(help, chunks[3]);
+
+    render_palette(f, app, chunks[2]);
+}
+
+/// Overlay the matching commands just above the input box.
+fn render_palette(f: &mut Frame, app: &TuiApp, input_area: Rect) {
+    let prefix = match &app.completion {
+        Some((prefix, _)) => prefix.as_str(),
+        None => app.input.as_str(),
+    };
+    let matches = command_matches(&app.command_list, prefix);
+    if matches.is_empty() {
+        return;
+    }
+
+    let rows = (matches.len() as u16).min(MAX_PALETTE_ROWS);
+    let height = (rows + 2).min(input_area.y);
+    if height < 3 {
+        return;
+    }
+    let area = Rect::new(input_area.x, input_area.y - height, input_area.width, height);
+
+    let selected = app.completion.as_ref().map(|(_, i)| *i);
+    let items: Vec<ListItem> = matches
+        .iter()
+        .enumerate()
+        .map(|(i, (usage, description))| {
+            let style = if Some(i) == selected { app.theme.selected } else { app.theme.text };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{:<24}", usage), style.add_modifier(Modifier::BOLD)),
+                Span::styled(description.as_str(), style),
+            ]))
+        })
+        .collect();
+
+    let mut state = ListState::default().with_selected(selected);
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Commands"));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
 }
@@ -0,0 +1,118 @@
+//! The side panel: scheduled jobs and workspace files in one filterable list.
+
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use super::Theme;
+use crate::memory::CronJob;
+use crate::scheduler::Scheduler;
+use crate::workspace::{format_size, FileInfo, Workspace};
+
+/// How often the open panel re-reads jobs and files.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+pub const PANEL_WIDTH: u16 = 40;
+
+#[derive(Debug, Clone)]
+pub enum Entry {
+    Job(CronJob),
+    File(FileInfo),
+}
+
+impl Entry {
+    fn label(&self) -> String {
+        match self {
+            Entry::Job(job) => format!("⏰ #{} {} ({})", job.id, job.task, job.schedule),
+            Entry::File(file) => format!("📄 {} ({})", file.name, format_size(file.size)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Panel {
+    jobs: Vec<CronJob>,
+    files: Vec<FileInfo>,
+    /// Case-insensitive text an entry must contain to be listed.
+    pub filter: String,
+    selected: usize,
+    refreshed: Option<Instant>,
+}
+
+impl Panel {
+    pub fn needs_refresh(&self) -> bool {
+        self.refreshed.is_none_or(|at| at.elapsed() >= REFRESH_INTERVAL)
+    }
+
+    pub async fn refresh(&mut self, scheduler: &Scheduler, workspace: &Workspace) {
+        match scheduler.list_jobs().await {
+            Ok(jobs) => self.jobs = jobs,
+            Err(e) => warn!("Panel couldn't list jobs: {}", e),
+        }
+        self.files = workspace.list_files();
+        self.refreshed = Some(Instant::now());
+        self.selected = self.selected.min(self.entries().len().saturating_sub(1));
+    }
+
+    /// Jobs, then files, that match the filter.
+    fn entries(&self) -> Vec<Entry> {
+        let filter = self.filter.to_lowercase();
+        self.jobs
+            .iter()
+            .cloned()
+            .map(Entry::Job)
+            .chain(self.files.iter().cloned().map(Entry::File))
+            .filter(|entry| entry.label().to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<Entry> {
+        self.entries().into_iter().nth(self.selected)
+    }
+
+    pub fn move_selection(&mut self, up: bool) {
+        let last = self.entries().len().saturating_sub(1);
+        self.selected = if up { self.selected.saturating_sub(1) } else { (self.selected + 1).min(last) };
+    }
+
+    pub fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.selected = 0;
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("Jobs {} · Files {}", self.jobs.len(), self.files.len()));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+
+        let search = if self.filter.is_empty() {
+            Paragraph::new("🔍 type to filter").style(theme.hint)
+        } else {
+            Paragraph::new(format!("🔍 {}", self.filter)).style(theme.text)
+        };
+        f.render_widget(search, rows[0]);
+
+        let entries = self.entries();
+        if entries.is_empty() {
+            f.render_widget(Paragraph::new("Nothing here").style(theme.hint), rows[1]);
+            return;
+        }
+        let items: Vec<ListItem> = entries
+            .iter()
+            .map(|entry| ListItem::new(Line::from(Span::styled(entry.label(), theme.text))))
+            .collect();
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(List::new(items).highlight_style(theme.selected), rows[1], &mut state);
+    }
+}
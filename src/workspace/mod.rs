@@ -1,33 +1,281 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::time::SystemTime;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::config::WorkspaceConfig;
+use crate::error::{self, RustyClawError};
 use crate::memory::Memory;
 
+const MAX_SEARCH_RESULTS: usize = 50;
+/// Cap on the manifest shown to the model, so a crowded workspace can't
+/// crowd out the conversation.
+const MAX_MANIFEST_CHARS: usize = 2000;
+
 #[derive(Debug, Clone)]
 pub struct FileInfo {
     pub name: String,
     pub size: u64,
     pub modified: SystemTime,
+    pub language: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A file written by `Workspace::save_file`.
+#[derive(Debug, Clone)]
+pub struct SavedFile {
+    /// Where the file landed, after any `_1`, `_2`, ... suffix was added.
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+impl SavedFile {
+    pub fn name(&self) -> &str {
+        self.path.file_name().and_then(|n| n.to_str()).unwrap_or_default()
+    }
+}
+
+/// `bytes` as "512 B", "3.4 KB", or "1.2 MB".
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Best-effort language name for a filename, based on its extension.
+pub fn detect_language(filename: &str) -> Option<&'static str> {
+    let ext = Path::new(filename).extension()?.to_str()?.to_lowercase();
+    let language = match ext.as_str() {
+        "py" => "python",
+        "rs" => "rust",
+        "js" | "mjs" => "javascript",
+        "ts" => "typescript",
+        "sh" | "bash" => "bash",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "hpp" => "cpp",
+        "go" => "go",
+        "java" => "java",
+        "rb" => "ruby",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "md" => "markdown",
+        "sql" => "sql",
+        "txt" => "text",
+        _ => return None,
+    };
+    Some(language)
+}
+
+/// File extension for a code block's language tag, e.g. `rs` for `rust`.
+pub fn extension_for(language: &str) -> Option<&'static str> {
+    let ext = match language.to_lowercase().as_str() {
+        "python" | "py" => "py",
+        "rust" | "rs" => "rs",
+        "javascript" | "js" => "js",
+        "typescript" | "ts" => "ts",
+        "bash" | "sh" | "shell" | "zsh" => "sh",
+        "c" => "c",
+        "cpp" | "c++" => "cpp",
+        "go" | "golang" => "go",
+        "java" => "java",
+        "ruby" | "rb" => "rb",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "markdown" | "md" => "md",
+        "sql" => "sql",
+        _ => return None,
+    };
+    Some(ext)
 }
 
 pub struct Workspace {
     path: PathBuf,
+    allowed_extensions: Vec<String>,
+    allow_hidden: bool,
+    git: bool,
+    manifest: bool,
     memory: Memory,
 }
 
 impl Workspace {
-    pub fn new(path: PathBuf, memory: Memory) -> Result<Self> {
-        std::fs::create_dir_all(&path)?;
-        Ok(Self { path, memory })
+    pub fn new(config: WorkspaceConfig, memory: Memory) -> error::Result<Self> {
+        std::fs::create_dir_all(&config.path).map_err(|e| RustyClawError::Workspace(e.into()))?;
+        let allowed_extensions = config
+            .allowed_extensions
+            .iter()
+            .map(|e| e.trim_start_matches('.').to_lowercase())
+            .collect();
+        let workspace = Self {
+            path: config.path,
+            allowed_extensions,
+            allow_hidden: config.allow_hidden,
+            git: config.git,
+            manifest: config.manifest,
+            memory,
+        };
+
+        if workspace.git && !workspace.path.join(".git").exists() {
+            match workspace.run_git(&["init", "--quiet"]) {
+                Ok(_) => info!("Initialized git repository in {:?}", workspace.path),
+                Err(e) => warn!("Failed to initialize workspace git repository: {}", e),
+            }
+        }
+
+        Ok(workspace)
+    }
+
+    fn run_git(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(["-c", "user.name=RustyClaw", "-c", "user.email=rustyclaw@localhost"])
+            .args(args)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "git {} failed: {}",
+                args.first().unwrap_or(&""),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn commit_paths(&self, paths: &[&str], message: &str) {
+        if !self.git {
+            return;
+        }
+
+        let mut add = vec!["add", "--all", "--"];
+        add.extend_from_slice(paths);
+        let mut commit = vec!["commit", "--quiet", "-m", message, "--"];
+        commit.extend_from_slice(paths);
+
+        let result = self.run_git(&add).and_then(|_| self.run_git(&commit));
+
+        if let Err(e) = result {
+            warn!("Failed to commit {} to workspace git: {}", paths.join(", "), e);
+        }
+    }
+
+    /// Recent commits touching `filename`, newest first, as `<hash> <subject>` lines.
+    pub fn file_log(&self, filename: &str, limit: usize) -> error::Result<Vec<String>> {
+        self.git_log(filename, limit).map_err(RustyClawError::Workspace)
+    }
+
+    fn git_log(&self, filename: &str, limit: usize) -> Result<Vec<String>> {
+        if !self.git {
+            bail!("Workspace git history is disabled (set workspace.git: true)");
+        }
+
+        let safe_name = self.check_filename(filename)?;
+        let limit = format!("-n{}", limit);
+        let output = self.run_git(&["log", "--oneline", "--no-color", &limit, "--", &safe_name])?;
+
+        Ok(output.lines().map(|l| l.to_string()).collect())
     }
 
-    pub async fn save_file(&self, filename: &str, content: &str) -> Result<PathBuf> {
+    fn check_filename(&self, filename: &str) -> Result<String> {
         let safe_name = Path::new(filename)
             .file_name()
             .and_then(|n| n.to_str())
-            .unwrap_or("untitled.txt");
+            .map(|n| n.trim())
+            .unwrap_or("");
+
+        if safe_name.is_empty() {
+            bail!("Invalid filename '{}'", filename);
+        }
+
+        if safe_name.starts_with('.') && !self.allow_hidden {
+            bail!("Hidden files are not allowed: {}", safe_name);
+        }
+
+        if !self.allowed_extensions.is_empty() {
+            let ext = Path::new(safe_name)
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.to_lowercase())
+                .unwrap_or_default();
+            if !self.allowed_extensions.contains(&ext) {
+                bail!(
+                    "File extension '.{}' is not allowed (allowed: {})",
+                    ext,
+                    self.allowed_extensions.join(", ")
+                );
+            }
+        }
+
+        Ok(safe_name.to_string())
+    }
+
+    /// Write `content` to `filename` in the workspace root, adding a `_1`,
+    /// `_2`, ... suffix rather than overwriting, and record it.
+    ///
+    /// ```
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::{MemoryConfig, WorkspaceConfig};
+    /// use rustyclaw::memory::Memory;
+    /// use rustyclaw::Workspace;
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-allowlist-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let memory = Memory::connect(&MemoryConfig { database: dir.join("allowlist.db"), ..Default::default() }).await.unwrap();
+    /// let config = WorkspaceConfig {
+    ///     path: dir.join("workspace"),
+    ///     allowed_extensions: vec![".PY".to_string(), "md".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// let workspace = Workspace::new(config, memory.clone()).unwrap();
+    ///
+    /// assert_eq!(workspace.save_file("hello.py", "print(1)", None, None).await.unwrap().name(), "hello.py");
+    ///
+    /// let blocked = workspace.save_file("run.sh", "rm -rf ~", None, None).await.unwrap_err();
+    /// assert!(blocked.to_string().contains("File extension '.sh' is not allowed"), "{}", blocked);
+    /// let hidden = workspace.save_file(".env.md", "TOKEN=x", None, None).await.unwrap_err();
+    /// assert!(hidden.to_string().contains("Hidden files are not allowed"), "{}", hidden);
+    /// for empty in ["", "  ", "..", "../", "/"] {
+    ///     let error = workspace.save_file(empty, "x", None, None).await.unwrap_err();
+    ///     assert!(error.to_string().contains("Invalid filename"), "{:?}: {}", empty, error);
+    /// }
+    /// assert_eq!(std::fs::read_dir(workspace.path()).unwrap().count(), 1);
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ```
+    pub async fn save_file(
+        &self,
+        filename: &str,
+        content: &str,
+        language: Option<&str>,
+        description: Option<&str>,
+    ) -> error::Result<SavedFile> {
+        self.write_file(filename, content, language, description)
+            .await
+            .map_err(|e| RustyClawError::wrap(e, RustyClawError::Workspace))
+    }
+
+    async fn write_file(
+        &self,
+        filename: &str,
+        content: &str,
+        language: Option<&str>,
+        description: Option<&str>,
+    ) -> Result<SavedFile> {
+        let safe_name = &self.check_filename(filename)?;
         
         let filepath = self.path.join(safe_name);
         
@@ -59,10 +307,176 @@ impl Workspace {
             .and_then(|n| n.to_str())
             .unwrap_or(safe_name);
         
-        self.memory.log_file(final_name, Some(&format!("Generated file: {}", safe_name))).await?;
+        let language = language.or_else(|| detect_language(final_name));
+        let description = description
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("Generated file: {}", safe_name));
+        self.memory
+            .log_file(final_name, Some(&description), language)
+            .await?;
+        self.commit_paths(&[final_name], &format!("save {} via RustyClaw", final_name));
         
         info!("Saved file: {:?}", final_path);
-        Ok(final_path)
+        Ok(SavedFile {
+            path: final_path,
+            bytes: content.len() as u64,
+        })
+    }
+
+    /// Like `list_files`, but fills in the language and description recorded
+    /// when each file was saved.
+    ///
+    /// ````
+    /// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+    /// use rustyclaw::config::{MemoryConfig, WorkspaceConfig};
+    /// use rustyclaw::memory::Memory;
+    /// use rustyclaw::{Agent, Workspace};
+    ///
+    /// let dir = std::env::temp_dir().join(format!("rustyclaw-language-{}", std::process::id()));
+    /// std::fs::create_dir_all(&dir).unwrap();
+    /// let memory = Memory::connect(&MemoryConfig { database: dir.join("language.db"), ..Default::default() }).await.unwrap();
+    /// let config = WorkspaceConfig { path: dir.join("workspace"), ..Default::default() };
+    /// let workspace = Workspace::new(config, memory.clone()).unwrap();
+    ///
+    /// // The block's own language wins; without one, the extension decides.
+    /// let reply = "```save:x.py\nprint(1)\n```\n```save:notes.txt markdown\n# Notes\n```";
+    /// for block in Agent::parse_save_blocks(reply) {
+    ///     workspace.save_file(&block.filename, &block.content, block.language.as_deref(), None).await.unwrap();
+    /// }
+    ///
+    /// let mut files = workspace.list_files_with_metadata().await;
+    /// files.sort_by(|a, b| a.name.cmp(&b.name));
+    /// let languages: Vec<_> = files.iter().map(|f| (f.name.as_str(), f.language.as_deref())).collect();
+    /// assert_eq!(languages, [("notes.txt", Some("markdown")), ("x.py", Some("python"))]);
+    /// let recorded = memory.get_workspace_files().await.unwrap();
+    /// assert!(recorded.iter().any(|f| f.filename == "x.py" && f.language.as_deref() == Some("python")));
+    /// # memory.close().await;
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # });
+    /// ````
+    pub async fn list_files_with_metadata(&self) -> Vec<FileInfo> {
+        let mut files = self.list_files();
+        let logged = self.memory.get_workspace_files().await.unwrap_or_default();
+
+        for file in files.iter_mut() {
+            let Some(recorded) = logged.iter().find(|f| f.filename == file.name) else {
+                continue;
+            };
+            if recorded.language.is_some() {
+                file.language = recorded.language.clone();
+            }
+            file.description = recorded.description.clone();
+        }
+
+        files
+    }
+
+    /// Rename a file inside the workspace root. Refuses to overwrite an
+    /// existing target or to move anything outside the root.
+    pub async fn rename_file(&self, old: &str, new: &str) -> error::Result<()> {
+        self.move_file(old, new)
+            .await
+            .map_err(|e| RustyClawError::wrap(e, RustyClawError::Workspace))
+    }
+
+    async fn move_file(&self, old: &str, new: &str) -> Result<()> {
+        for name in [old, new] {
+            if Path::new(name.trim()).file_name().and_then(|n| n.to_str()) != Some(name.trim()) {
+                bail!("'{}' must be a plain filename inside the workspace", name);
+            }
+        }
+
+        let old_name = old.trim();
+        let new_name = self.check_filename(new)?;
+        let old_path = self.path.join(old_name);
+        let new_path = self.path.join(&new_name);
+
+        if !old_path.is_file() {
+            bail!("File not found: {}", old_name);
+        }
+        if new_path.exists() {
+            bail!("{} already exists", new_name);
+        }
+
+        std::fs::rename(&old_path, &new_path)?;
+        self.memory.rename_file(old_name, &new_name).await?;
+        self.commit_paths(
+            &[old_name, &new_name],
+            &format!("rename {} to {} via RustyClaw", old_name, new_name),
+        );
+
+        info!("Renamed file: {} -> {}", old_name, new_name);
+        Ok(())
+    }
+
+    /// Delete one file from the workspace root and forget its metadata.
+    pub async fn delete_file(&self, name: &str) -> error::Result<()> {
+        self.remove_file(name)
+            .await
+            .map_err(|e| RustyClawError::wrap(e, RustyClawError::Workspace))
+    }
+
+    async fn remove_file(&self, name: &str) -> Result<()> {
+        let name = name.trim();
+        if Path::new(name).file_name().and_then(|n| n.to_str()) != Some(name) {
+            bail!("'{}' must be a plain filename inside the workspace", name);
+        }
+        let path = self.path.join(name);
+        if !path.is_file() {
+            bail!("File not found: {}", name);
+        }
+
+        std::fs::remove_file(&path)?;
+        self.memory.delete_file(name).await?;
+        self.commit_paths(&[name], &format!("delete {} via RustyClaw", name));
+
+        info!("Deleted file: {}", name);
+        Ok(())
+    }
+
+    /// Delete every file in the workspace root and forget their metadata.
+    /// Subdirectories, including the `.git` repository, are left alone.
+    /// Returns how many files were deleted.
+    pub async fn clear(&self) -> error::Result<usize> {
+        let names: Vec<String> = self.list_files().into_iter().map(|f| f.name).collect();
+        for name in &names {
+            std::fs::remove_file(self.path.join(name))
+                .with_context(|| format!("Failed to delete {}", name))
+                .map_err(RustyClawError::Workspace)?;
+        }
+        self.memory.clear_workspace_files().await?;
+
+        if !names.is_empty() {
+            let paths: Vec<&str> = names.iter().map(String::as_str).collect();
+            self.commit_paths(&paths, "clear workspace via RustyClaw");
+        }
+
+        info!("Cleared {} file(s) from the workspace", names.len());
+        Ok(names.len())
+    }
+
+    /// A short listing of the files in the workspace for the model, or `None`
+    /// if `workspace.manifest` is off or there are no files. Names past
+    /// `MAX_MANIFEST_CHARS` are summarized as a count.
+    pub fn manifest(&self) -> Option<String> {
+        if !self.manifest {
+            return None;
+        }
+        let files = self.list_files();
+        if files.is_empty() {
+            return None;
+        }
+
+        let mut text = String::from("Files already in the workspace (use these names to update them):");
+        for (i, file) in files.iter().enumerate() {
+            let line = format!("\n- {} ({})", file.name, format_size(file.size));
+            if text.len() + line.len() > MAX_MANIFEST_CHARS {
+                text.push_str(&format!("\n- ...and {} more", files.len() - i));
+                break;
+            }
+            text.push_str(&line);
+        }
+        Some(text)
     }
 
     pub fn list_files(&self) -> Vec<FileInfo> {
@@ -78,10 +492,14 @@ impl Workspace {
                             .unwrap_or("unknown")
                             .to_string();
                         
+                        let language = detect_language(&name).map(|l| l.to_string());
+
                         files.push(FileInfo {
                             name,
                             size: metadata.len(),
                             modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                            language,
+                            description: None,
                         });
                     }
                 }
@@ -92,7 +510,7 @@ impl Workspace {
         files
     }
 
-    pub fn read_file(&self, filename: &str) -> Option<String> {
+    fn existing_file(&self, filename: &str) -> Option<PathBuf> {
         let safe_name = Path::new(filename)
             .file_name()
             .and_then(|n| n.to_str())?;
@@ -100,12 +518,80 @@ impl Workspace {
         let filepath = self.path.join(safe_name);
         
         if filepath.exists() && filepath.is_file() {
-            std::fs::read_to_string(filepath).ok()
+            Some(filepath)
         } else {
             None
         }
     }
 
+    /// The saved versions of `filename`, oldest first: the file itself, then
+    /// the `_1`, `_2`, ... copies `save_file` made instead of overwriting it.
+    pub fn versions(&self, filename: &str) -> Vec<String> {
+        let Some(base) = Path::new(filename.trim()).file_name().and_then(|n| n.to_str()) else {
+            return Vec::new();
+        };
+        let path = Path::new(base);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("untitled");
+        let suffix = path.extension().and_then(|s| s.to_str()).unwrap_or("txt");
+        let prefix = format!("{}_", stem);
+        let ending = format!(".{}", suffix);
+
+        let mut versions: Vec<(u64, String)> = self
+            .list_files()
+            .into_iter()
+            .filter_map(|f| {
+                if f.name == base {
+                    return Some((0, f.name));
+                }
+                let n = f.name.strip_prefix(&prefix)?.strip_suffix(&ending)?.parse().ok()?;
+                Some((n, f.name))
+            })
+            .collect();
+        versions.sort();
+        versions.into_iter().map(|(_, name)| name).collect()
+    }
+
+    pub fn read_file(&self, filename: &str) -> Option<String> {
+        std::fs::read_to_string(self.existing_file(filename)?).ok()
+    }
+
+    /// Raw file contents, for binary files that `read_file` can't decode.
+    pub fn read_bytes(&self, filename: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.existing_file(filename)?).ok()
+    }
+
+    /// Find lines matching `pattern` (a regex, or a literal if it isn't valid regex)
+    /// across text files. Returns `(filename, line number, line)`, capped at
+    /// `MAX_SEARCH_RESULTS`.
+    pub fn search(&self, pattern: &str) -> Vec<(String, usize, String)> {
+        let re = Regex::new(pattern)
+            .unwrap_or_else(|_| Regex::new(&regex::escape(pattern)).unwrap());
+        let mut results = Vec::new();
+
+        for file in self.list_files() {
+            let Ok(bytes) = std::fs::read(self.path.join(&file.name)) else {
+                continue;
+            };
+            if bytes.contains(&0) {
+                continue;
+            }
+            let Ok(text) = String::from_utf8(bytes) else {
+                continue;
+            };
+
+            for (i, line) in text.lines().enumerate() {
+                if re.is_match(line) {
+                    results.push((file.name.clone(), i + 1, line.trim().to_string()));
+                    if results.len() >= MAX_SEARCH_RESULTS {
+                        return results;
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
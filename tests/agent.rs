@@ -0,0 +1,185 @@
+//! Agent requests against a fake Ollama that records how many it is
+//! answering at once, or what it was asked.
+
+use axum::{extract::State, routing::post, Json, Router};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+use rustyclaw::agent::GenerateOptions;
+use rustyclaw::config::OllamaConfig;
+use rustyclaw::memory::Message;
+use rustyclaw::Agent;
+
+#[derive(Clone, Default)]
+struct Load {
+    running: Arc<AtomicUsize>,
+    peak: Arc<AtomicUsize>,
+}
+
+async fn slow_reply(State(load): State<Load>, Json(request): Json<Value>) -> Json<Value> {
+    let running = load.running.fetch_add(1, Ordering::SeqCst) + 1;
+    load.peak.fetch_max(running, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    load.running.fetch_sub(1, Ordering::SeqCst);
+
+    let content = request["model"].as_str().unwrap_or_default().to_string();
+    Json(json!({ "message": { "role": "assistant", "content": content }, "response": content, "done": true }))
+}
+
+/// An agent allowed `max_concurrent` requests, talking to a fake Ollama
+/// whose load is reported in the returned `Load`. Its facts file is in the
+/// returned temp dir.
+async fn agent(max_concurrent: usize) -> (Arc<Agent>, Load, PathBuf) {
+    let load = Load::default();
+    let fake = Router::new()
+        .route("/api/chat", post(slow_reply))
+        .route("/api/generate", post(slow_reply))
+        .with_state(load.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, fake).await });
+
+    let dir = std::env::temp_dir().join(format!("rustyclaw-agent-{}-{}", max_concurrent, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = OllamaConfig { host, max_concurrent, ..Default::default() };
+    let agent = Agent::new(config, "You are helpful.".into()).with_memory_file(dir.join("memory.md"));
+    (Arc::new(agent), load, dir)
+}
+
+/// Send six chat and generate requests at once and wait for them all.
+async fn send_at_once(agent: &Arc<Agent>) {
+    let message = Message {
+        role: "user".into(),
+        content: "Hi".into(),
+        timestamp: String::new(),
+        model: None,
+    };
+    let requests: Vec<_> = (0..6)
+        .map(|i| {
+            let (agent, message) = (agent.clone(), message.clone());
+            tokio::spawn(async move {
+                if i % 2 == 0 {
+                    agent.chat(&[message]).await.unwrap();
+                } else {
+                    agent.generate("Hi", &GenerateOptions::default()).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    for request in requests {
+        request.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn requests_wait_for_a_free_slot() {
+    for max_concurrent in [1, 2] {
+        let (agent, load, dir) = agent(max_concurrent).await;
+        send_at_once(&agent).await;
+        assert_eq!(load.peak.load(Ordering::SeqCst), max_concurrent);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
+
+/// Answers every chat with the same condensed list of facts, keeping what
+/// each request's last message was.
+async fn condense(State(asked): State<Arc<Mutex<Vec<String>>>>, Json(request): Json<Value>) -> Json<Value> {
+    let messages = request["messages"].as_array().cloned().unwrap_or_default();
+    let last = messages.last().and_then(|m| m["content"].as_str()).unwrap_or_default();
+    asked.lock().unwrap().push(last.to_string());
+    let content = "- Likes green tea\n- Lives in Oslo";
+    Json(json!({ "message": { "role": "assistant", "content": content }, "done": true }))
+}
+
+#[tokio::test]
+async fn memory_is_consolidated_once_it_grows_past_the_limit() {
+    let asked = Arc::new(Mutex::new(Vec::new()));
+    let fake = Router::new().route("/api/chat", post(condense)).with_state(asked.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, fake).await });
+
+    let dir = std::env::temp_dir().join(format!("rustyclaw-agent-consolidate-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("memory.md");
+    let config = OllamaConfig { host, ..Default::default() };
+    let agent = Agent::new(config, "You are helpful.".into())
+        .with_memory_file(path.clone())
+        .with_max_memory_lines(5);
+
+    // At the limit, counting the blank lines between appended facts:
+    // nothing to do yet.
+    for fact in ["Likes tea", "Likes green tea", "Lives in Oslo"] {
+        assert!(agent.save_to_memory(fact).await.unwrap());
+    }
+    assert!(asked.lock().unwrap().is_empty());
+    assert_eq!(agent.check_memory_size().await, (false, 5));
+
+    // One line past it: the facts go to the model and its list replaces them.
+    assert!(agent.save_to_memory("Moved to Oslo in 2020").await.unwrap());
+    let asked = asked.lock().unwrap().clone();
+    assert_eq!(asked.len(), 1);
+    assert!(asked[0].contains("- Likes tea") && asked[0].contains("- Moved to Oslo in 2020"), "{}", asked[0]);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "- Likes green tea\n- Lives in Oslo\n");
+    assert_eq!(std::fs::read_to_string(dir.join("memory.md.bak")).unwrap().lines().filter(|l| !l.is_empty()).count(), 4);
+    assert_eq!(agent.check_memory_size().await, (false, 2));
+    std::fs::remove_dir_all(dir).ok();
+}
+
+/// Keeps each `/api/generate` request body and completes it with "world".
+async fn record(State(requests): State<Arc<Mutex<Vec<Value>>>>, Json(request): Json<Value>) -> Json<Value> {
+    requests.lock().unwrap().push(request);
+    Json(json!({ "response": "world", "done": true }))
+}
+
+#[tokio::test]
+async fn generate_sends_only_the_prompt_and_the_options_that_are_set() {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let fake = Router::new().route("/api/generate", post(record)).with_state(requests.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let host = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, fake).await });
+
+    let config = OllamaConfig { host, model: "codellama".into(), temperature: 0.5, context_length: 2048, ..Default::default() };
+    let memory_file = std::env::temp_dir().join("rustyclaw-no-memory.md");
+    let agent = Agent::new(config, "You are helpful.".into()).with_memory_file(memory_file);
+
+    assert_eq!(agent.generate("Hello", &GenerateOptions::default()).await.unwrap(), "world");
+    let options = GenerateOptions {
+        system: Some("Complete the code.".into()),
+        suffix: Some("\n}".into()),
+        raw: true,
+        temperature: Some(0.25),
+        seed: Some(42),
+    };
+    agent.generate("fn main() {", &options).await.unwrap();
+
+    let requests = requests.lock().unwrap().clone();
+    // No system prompt, memory or history, and unset fields left out.
+    assert_eq!(
+        requests[0],
+        json!({
+            "model": "codellama",
+            "prompt": "Hello",
+            "stream": false,
+            "options": { "temperature": 0.5, "num_ctx": 2048 },
+        })
+    );
+    assert_eq!(
+        requests[1],
+        json!({
+            "model": "codellama",
+            "prompt": "fn main() {",
+            "suffix": "\n}",
+            "system": "Complete the code.",
+            "raw": true,
+            "stream": false,
+            "options": { "temperature": 0.25, "num_ctx": 2048, "seed": 42 },
+        })
+    );
+}
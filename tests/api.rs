@@ -0,0 +1,248 @@
+//! The API server end to end, against a fake Ollama that always gives the
+//! same reply.
+
+use axum::{body::Body, extract::State, routing::post, Json, Router};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, Notify};
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+use rustyclaw::api::ApiServer;
+use rustyclaw::{Config, RustyClaw};
+
+/// The fake model's reply, in the pieces it streams. The last piece waits
+/// for `Server::release`.
+const REPLY: [&str; 4] = ["Noted. ```mem", "ory\nLikes tea\n``", "`\n", "All set."];
+
+#[derive(Clone)]
+struct FakeOllama {
+    release: Arc<Notify>,
+}
+
+async fn fake_chat(State(fake): State<FakeOllama>, Json(request): Json<Value>) -> Body {
+    if request["stream"] != json!(true) {
+        let reply = json!({ "message": { "role": "assistant", "content": REPLY.concat() }, "done": true });
+        return Body::from(reply.to_string());
+    }
+
+    let (tx, rx) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        for (i, piece) in REPLY.iter().enumerate() {
+            if i == REPLY.len() - 1 {
+                fake.release.notified().await;
+            }
+            let line = json!({ "message": { "role": "assistant", "content": piece }, "done": false });
+            tx.send(format!("{}\n", line)).ok();
+        }
+        tx.send(format!("{}\n", json!({ "done": true }))).ok();
+    });
+    let lines = futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|line| (Ok::<_, std::convert::Infallible>(line), rx))
+    });
+    Body::from_stream(lines)
+}
+
+struct Server {
+    url: String,
+    dir: PathBuf,
+    release: Arc<Notify>,
+    claw: RustyClaw,
+}
+
+impl Server {
+    /// The API on a free port, with its data in a fresh temp dir named after
+    /// `name`, and `token` required if given.
+    async fn start(name: &str, token: Option<&str>) -> Self {
+        // Log lines are only formatted when something collects them.
+        tracing_subscriber::fmt().with_test_writer().try_init().ok();
+        let release = Arc::new(Notify::new());
+        let fake = Router::new()
+            .route("/api/chat", post(fake_chat))
+            .with_state(FakeOllama { release: release.clone() });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ollama = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, fake).await });
+
+        let dir = std::env::temp_dir().join(format!("rustyclaw-api-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = Config::default();
+        config.ollama.host = ollama;
+        config.memory.database = dir.join("test.db");
+        config.memory.facts_file = dir.join("memory.md");
+        config.workspace.path = dir.join("workspace");
+        config.api.token = token.map(str::to_string);
+
+        let claw = RustyClaw::builder(config.clone()).with_warm_up(false).build().await.unwrap();
+        let server = ApiServer::new(
+            config,
+            claw.agent().clone(),
+            claw.memory().clone(),
+            claw.scheduler().clone(),
+            claw.workspace().clone(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { server.serve(listener).await });
+
+        Self { url, dir, release, claw }
+    }
+
+    fn facts(&self) -> String {
+        std::fs::read_to_string(self.dir.join("memory.md")).unwrap_or_default()
+    }
+
+    async fn stop(self) {
+        self.claw.memory().close().await;
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+#[tokio::test]
+async fn websocket_streams_deltas_and_acts_before_the_reply_ends() {
+    let server = Server::start("ws", None).await;
+    let ws_url = format!("{}/ws", server.url.replacen("http", "ws", 1));
+    let (mut socket, _) = tokio_tungstenite::connect_async(ws_url).await.unwrap();
+    socket
+        .send(WsMessage::text(json!({ "message": "Remember that I like tea" }).to_string()))
+        .await
+        .unwrap();
+
+    let mut deltas = String::new();
+    let done = loop {
+        let frame = tokio::time::timeout(Duration::from_secs(10), socket.next())
+            .await
+            .expect("no frame within 10s")
+            .unwrap()
+            .unwrap();
+        let frame: Value = serde_json::from_str(frame.to_text().unwrap()).unwrap();
+        match frame["type"].as_str() {
+            Some("delta") => {
+                deltas.push_str(frame["content"].as_str().unwrap());
+                if deltas.ends_with("```\n") {
+                    // The model is still writing, yet the memory is saved.
+                    for _ in 0..100 {
+                        if server.facts().contains("Likes tea") {
+                            break;
+                        }
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    assert!(server.facts().contains("Likes tea"));
+                    server.release.notify_one();
+                }
+            }
+            Some("done") => break frame,
+            _ => panic!("unexpected frame: {}", frame),
+        }
+    };
+
+    assert_eq!(deltas, REPLY.concat());
+    assert_eq!(done["actions"], json!(["Remembered: Likes tea"]));
+    let response = done["response"].as_str().unwrap();
+    assert!(response.contains("All set.") && !response.contains("```"));
+    assert_eq!(server.facts().matches("Likes tea").count(), 1);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn chat_answers_and_acts_on_the_reply() {
+    let server = Server::start("chat", None).await;
+    let client = reqwest::Client::new();
+
+    // Byte 80 falls inside a multi-byte character, which must not matter.
+    let message = format!("{}é and remember that I like tea", "x".repeat(79));
+    let response = client
+        .post(format!("{}/chat", server.url))
+        .json(&json!({ "message": message }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["actions"], json!(["Remembered: Likes tea"]));
+    assert!(body["response"].as_str().unwrap().contains("All set."));
+    assert!(server.facts().contains("Likes tea"));
+
+    let history = server.claw.memory().get_history(10).await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!((history[0].role.as_str(), history[0].content.as_str()), ("user", message.as_str()));
+
+    let empty = client.post(format!("{}/chat", server.url)).json(&json!({ "message": " " })).send().await.unwrap();
+    assert_eq!(empty.status(), 400);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn jobs_can_be_created_listed_and_cancelled() {
+    let server = Server::start("jobs", None).await;
+    let client = reqwest::Client::new();
+    let jobs = format!("{}/jobs", server.url);
+
+    // Without a task, the label is cut at 47 characters even when that
+    // lands inside a multi-byte character's bytes.
+    let message = "é".repeat(60);
+    let created = client
+        .post(&jobs)
+        .json(&json!({ "schedule": "0 9 * * MON", "message": message }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), 201);
+    let created: Value = created.json().await.unwrap();
+    assert_eq!(created["task"], json!(format!("{}...", "é".repeat(47))));
+    let id = created["id"].as_i64().unwrap();
+
+    let listed: Value = client.get(&jobs).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], json!(id));
+    assert_eq!(listed[0]["message"], json!(message));
+
+    let invalid = client
+        .post(&jobs)
+        .json(&json!({ "schedule": "0 25 * * *", "message": "Too late" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(invalid.status(), 400);
+
+    let cancelled = client.delete(format!("{}/{}", jobs, id)).send().await.unwrap();
+    assert_eq!(cancelled.status(), 204);
+    let unknown = client.delete(format!("{}/{}", jobs, id + 100)).send().await.unwrap();
+    assert_eq!(unknown.status(), 404);
+    let listed: Value = client.get(&jobs).send().await.unwrap().json().await.unwrap();
+    assert_eq!(listed, json!([]));
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn workspace_lists_saved_files() {
+    let server = Server::start("workspace", None).await;
+    server
+        .claw
+        .workspace()
+        .save_file("notes.md", "# Notes\n", Some("markdown"), Some("Meeting notes"))
+        .await
+        .unwrap();
+
+    let files: Value = reqwest::get(format!("{}/workspace", server.url)).await.unwrap().json().await.unwrap();
+    assert_eq!(files.as_array().unwrap().len(), 1);
+    assert_eq!(files[0]["name"], json!("notes.md"));
+    assert_eq!(files[0]["description"], json!("Meeting notes"));
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn token_is_required_when_set() {
+    let server = Server::start("token", Some("s3cret")).await;
+    let client = reqwest::Client::new();
+    let jobs = format!("{}/jobs", server.url);
+
+    assert_eq!(client.get(&jobs).send().await.unwrap().status(), 401);
+    assert_eq!(client.get(&jobs).bearer_auth("wrong").send().await.unwrap().status(), 401);
+    assert_eq!(client.get(&jobs).bearer_auth("s3cret").send().await.unwrap().status(), 200);
+    server.stop().await;
+}
@@ -0,0 +1,71 @@
+//! Audit log rotation, including a rotation that fails.
+
+use std::path::Path;
+use std::time::Duration;
+
+use rustyclaw::audit::{AuditEntry, AuditLog};
+use rustyclaw::config::AuditConfig;
+
+fn entry() -> AuditEntry {
+    AuditEntry {
+        timestamp: "2024-05-01T09:30:00Z".into(),
+        session: "main".into(),
+        user: "Hi".into(),
+        model: "llama3.2".into(),
+        response: Some("Hello!".into()),
+        error: None,
+        prompt_tokens: Some(12),
+        response_tokens: Some(3),
+        latency_ms: 250,
+    }
+}
+
+fn lines(path: &Path) -> usize {
+    std::fs::read_to_string(path).map(|s| s.lines().count()).unwrap_or(0)
+}
+
+/// Wait for the writer to flush `count` lines to `path`.
+async fn written(path: &Path, count: usize) {
+    for _ in 0..100 {
+        if lines(path) == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("{} has {} lines, expected {}", path.display(), lines(path), count);
+}
+
+#[tokio::test]
+async fn a_failed_rotation_is_retried_after_another_max_bytes() {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-audit-rotate-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("audit.jsonl");
+    let rotated = dir.join("audit.jsonl.1");
+    // Room for two entries; the third fills the file.
+    let line = serde_json::to_string(&entry()).unwrap().len() as u64 + 1;
+    let config = AuditConfig { enabled: true, path: path.clone(), max_bytes: line * 5 / 2 };
+
+    // A non-empty directory where the old log should go makes the rename fail.
+    std::fs::create_dir_all(rotated.join("blocked")).unwrap();
+    let audit = AuditLog::start(&config);
+    for _ in 0..3 {
+        audit.record(entry());
+    }
+    written(&path, 3).await;
+
+    // The next attempt waits for another max_bytes instead of coming with
+    // the very next entry.
+    std::fs::remove_dir_all(&rotated).unwrap();
+    audit.record(entry());
+    written(&path, 4).await;
+    assert!(!rotated.exists());
+
+    for _ in 0..2 {
+        audit.record(entry());
+    }
+    audit.shutdown().await;
+    assert_eq!(lines(&rotated), 6);
+    assert_eq!(lines(&path), 0);
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,123 @@
+//! Backups restored into a config, and archives that must be refused.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustyclaw::config::Config;
+use rustyclaw::memory::Memory;
+
+/// A config whose database, facts file and workspace live in a fresh temp
+/// dir named after `name`.
+fn config(name: &str) -> (Config, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-backup-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.memory.database = dir.join("test.db");
+    config.memory.facts_file = dir.join("memory.md");
+    config.workspace.path = dir.join("workspace");
+    (config, dir)
+}
+
+async fn messages(config: &Config) -> Vec<String> {
+    let memory = Memory::connect(&config.memory).await.unwrap();
+    let history = memory.get_history(10).await.unwrap();
+    memory.close().await;
+    history.into_iter().map(|m| m.content).collect()
+}
+
+#[tokio::test]
+async fn restore_brings_back_what_was_backed_up() {
+    let (config, dir) = config("round-trip");
+    let memory = Memory::connect(&config.memory).await.unwrap();
+    memory.add_message("user", "Before the backup", None).await.unwrap();
+    memory.close().await;
+    fs::write(&config.memory.facts_file, "- Likes tea\n").unwrap();
+    fs::create_dir_all(config.workspace.path.join("notes")).unwrap();
+    fs::write(config.workspace.path.join("notes/todo.md"), "- stretch\n").unwrap();
+
+    let archive = dir.join("backup.tar.gz");
+    let summary = rustyclaw::backup::create(&config, &archive).await.unwrap();
+    assert!(summary.facts);
+    assert_eq!(summary.workspace_files, 1);
+
+    let memory = Memory::connect(&config.memory).await.unwrap();
+    memory.add_message("user", "After the backup", None).await.unwrap();
+    memory.close().await;
+    fs::write(&config.memory.facts_file, "- Likes coffee\n").unwrap();
+    fs::remove_file(config.workspace.path.join("notes/todo.md")).unwrap();
+    fs::write(config.workspace.path.join("new.txt"), "later").unwrap();
+
+    let summary = rustyclaw::backup::restore(&config, &archive).unwrap();
+    assert!(summary.facts);
+    assert_eq!(summary.workspace_files, 1);
+    assert_eq!(messages(&config).await, ["Before the backup"]);
+    assert_eq!(fs::read_to_string(&config.memory.facts_file).unwrap(), "- Likes tea\n");
+    assert_eq!(fs::read_to_string(config.workspace.path.join("notes/todo.md")).unwrap(), "- stretch\n");
+    assert!(!config.workspace.path.join("new.txt").exists());
+
+    // Nothing is left beside the targets.
+    let mut left: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .filter(|name| !name.starts_with("test.db"))
+        .collect();
+    left.sort();
+    assert_eq!(left, ["backup.tar.gz", "memory.md", "workspace"]);
+    fs::remove_dir_all(dir).ok();
+}
+
+/// A gzipped tarball with a database entry followed by `add`'s entries.
+fn archive(path: &Path, add: impl FnOnce(&mut tar::Builder<flate2::write::GzEncoder<fs::File>>)) {
+    let file = fs::File::create(path).unwrap();
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+    let mut header = tar::Header::new_gnu();
+    header.set_size(0);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, "rustyclaw.db", &[][..]).unwrap();
+    add(&mut builder);
+    builder.into_inner().unwrap().finish().unwrap();
+}
+
+#[tokio::test]
+async fn links_are_refused_and_nothing_is_touched() {
+    let (config, dir) = config("links");
+    let memory = Memory::connect(&config.memory).await.unwrap();
+    memory.add_message("user", "Keep me", None).await.unwrap();
+    memory.close().await;
+    fs::write(&config.memory.facts_file, "- Likes tea\n").unwrap();
+    fs::create_dir_all(&config.workspace.path).unwrap();
+    fs::write(config.workspace.path.join("keep.txt"), "keep").unwrap();
+    let outside = dir.join("outside");
+    fs::create_dir_all(&outside).unwrap();
+
+    // A symlink out of the workspace, then a file written through it.
+    let symlink = dir.join("symlink.tar.gz");
+    archive(&symlink, |builder| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        builder.append_link(&mut header, "workspace/x", &outside).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, "workspace/x/evil.txt", &b"evil"[..]).unwrap();
+    });
+    let hardlink = dir.join("hardlink.tar.gz");
+    archive(&hardlink, |builder| {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        builder.append_link(&mut header, "workspace/passwd", "/etc/passwd").unwrap();
+    });
+
+    for bad in [symlink, hardlink] {
+        let error = rustyclaw::backup::restore(&config, &bad).unwrap_err();
+        assert!(error.to_string().contains("is not a file or directory"), "{}", error);
+    }
+    assert!(!outside.join("evil.txt").exists());
+    assert_eq!(messages(&config).await, ["Keep me"]);
+    assert_eq!(fs::read_to_string(&config.memory.facts_file).unwrap(), "- Likes tea\n");
+    assert_eq!(fs::read_to_string(config.workspace.path.join("keep.txt")).unwrap(), "keep");
+    fs::remove_dir_all(dir).ok();
+}
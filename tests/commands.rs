@@ -0,0 +1,99 @@
+//! Slash commands, built-in and registered, dispatched through a `CommandRegistry`.
+
+use std::path::PathBuf;
+
+use rustyclaw::commands::{handler_fn, CommandRegistry};
+use rustyclaw::{Config, RustyClaw};
+
+/// A config whose database, facts and workspace live in a fresh temp dir
+/// named after `name`.
+fn config(name: &str) -> (Config, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-commands-{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.memory.database = dir.join("test.db");
+    config.memory.facts_file = dir.join("memory.md");
+    config.workspace.path = dir.join("workspace");
+    (config, dir)
+}
+
+#[tokio::test]
+async fn jobs_and_status_say_when_the_scheduler_is_disabled() {
+    let (mut config, dir) = config("disabled");
+    config.scheduler.enabled = false;
+    let claw = RustyClaw::builder(config).with_warm_up(false).build().await.unwrap();
+    let commands = CommandRegistry::with_builtins();
+    let ctx = claw.context();
+
+    let jobs = commands.dispatch("jobs", "", &ctx).await.unwrap();
+    assert!(jobs.contains("Scheduler disabled"), "{}", jobs);
+    assert!(!jobs.contains("No scheduled jobs"), "{}", jobs);
+    let status = commands.dispatch("status", "", &ctx).await.unwrap();
+    assert!(status.contains("Scheduled jobs: scheduler disabled"), "{}", status);
+
+    let error = claw.scheduler().add_job("0 9 * * *", "Stretch", "Time to stretch").await.unwrap_err();
+    assert!(error.to_string().contains("Scheduler is disabled"), "{}", error);
+
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn jobs_and_status_count_jobs_when_the_scheduler_is_enabled() {
+    let (config, dir) = config("enabled");
+    let claw = RustyClaw::builder(config).with_warm_up(false).build().await.unwrap();
+    let commands = CommandRegistry::with_builtins();
+    let ctx = claw.context();
+
+    let jobs = commands.dispatch("jobs", "", &ctx).await.unwrap();
+    assert!(jobs.starts_with("No scheduled jobs"), "{}", jobs);
+    claw.scheduler().add_job("0 9 * * *", "Stretch", "Time to stretch").await.unwrap();
+    let jobs = commands.dispatch("jobs", "", &ctx).await.unwrap();
+    assert!(jobs.contains("Stretch") && jobs.contains("0 9 * * *"), "{}", jobs);
+    let status = commands.dispatch("status", "", &ctx).await.unwrap();
+    assert!(status.contains("Scheduled jobs: 1"), "{}", status);
+
+    claw.scheduler().cancel_all().await.unwrap();
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn registered_commands_are_parsed_and_dispatched() {
+    let (config, dir) = config("custom");
+    let claw = RustyClaw::builder(config).with_warm_up(false).build().await.unwrap();
+    let ctx = claw.context();
+
+    let mut commands = CommandRegistry::with_builtins();
+    commands
+        .register(
+            "/weather",
+            handler_fn("Ask about the weather", |args, ctx| async move {
+                let place = if args.is_empty() { "here".to_string() } else { args };
+                let model = ctx.agent.ollama_config().await.model;
+                format!("No forecast for {} yet, but {} is ready to chat.", place, model)
+            }),
+        )
+        .unwrap();
+    assert!(commands.list().contains(&("weather".to_string(), "Ask about the weather".to_string())));
+
+    let (name, args) = commands.parse("/weather@rustyclaw_bot  Paris ").unwrap();
+    assert_eq!((name, args), ("weather", "Paris"));
+    let reply = commands.dispatch(name, args, &ctx).await.unwrap();
+    let model = claw.config().ollama.model.clone();
+    assert_eq!(reply, format!("No forecast for Paris yet, but {} is ready to chat.", model));
+    let reply = commands.dispatch("weather", "", &ctx).await.unwrap();
+    assert!(reply.starts_with("No forecast for here yet"), "{}", reply);
+
+    // A registered name replaces the built-in of the same name.
+    commands.register("status", handler_fn("Custom status", |_, _| async { "All good.".to_string() })).unwrap();
+    assert_eq!(commands.dispatch("status", "", &ctx).await.unwrap(), "All good.");
+
+    assert!(commands.register("Weather!", handler_fn("Nope", |_, _| async { String::new() })).is_err());
+    assert!(commands.parse("/forecast Paris").is_none());
+    assert!(commands.dispatch("forecast", "Paris", &ctx).await.is_none());
+
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
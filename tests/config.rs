@@ -0,0 +1,122 @@
+//! Loading config files: environment overrides, includes and data paths.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rustyclaw::Config;
+
+/// `Config::load` reads `RUSTYCLAW_*` from the process environment, which
+/// every test here shares, so they take turns.
+static ENV: Mutex<()> = Mutex::new(());
+
+/// A fresh temp dir named after `name`.
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-config-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(dir: &Path, name: &str, yaml: &str) -> PathBuf {
+    let path = dir.join(name);
+    fs::write(&path, yaml).unwrap();
+    path
+}
+
+#[test]
+fn environment_beats_the_file() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = dir("env");
+    let path = write(&dir, "config.yaml", "ollama:\n  model: from-yaml\n  host: http://yaml:11434\n");
+
+    std::env::set_var("RUSTYCLAW_OLLAMA_MODEL", "from-env");
+    let config = Config::load(&path);
+    std::env::remove_var("RUSTYCLAW_OLLAMA_MODEL");
+    let config = config.unwrap();
+    assert_eq!(config.ollama.model, "from-env");
+    assert_eq!(config.ollama.host, "http://yaml:11434");
+
+    // Without the variable, or with it empty, the file wins again.
+    assert_eq!(Config::load(&path).unwrap().ollama.model, "from-yaml");
+    std::env::set_var("RUSTYCLAW_OLLAMA_MODEL", "");
+    let config = Config::load(&path);
+    std::env::remove_var("RUSTYCLAW_OLLAMA_MODEL");
+    assert_eq!(config.unwrap().ollama.model, "from-yaml");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn includes_merge_in_order_and_the_including_file_wins() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = dir("include");
+    fs::create_dir_all(dir.join("conf.d")).unwrap();
+    write(&dir, "conf.d/base.yaml", "ollama:\n  model: base\n  temperature: 0.2\n  context_length: 2048\n");
+    write(&dir, "conf.d/secrets.yaml", "ollama:\n  model: secret\ntelegram:\n  token: from-secrets\n");
+    let path = write(
+        &dir,
+        "config.yaml",
+        "include: [conf.d/base.yaml, conf.d/secrets.yaml]\nollama:\n  temperature: 0.9\n",
+    );
+
+    let config = Config::load(&path).unwrap();
+    // A later include overrides an earlier one, and the file itself
+    // overrides both, key by key within a section.
+    assert_eq!(config.ollama.model, "secret");
+    assert_eq!(config.ollama.temperature, 0.9);
+    assert_eq!(config.ollama.context_length, 2048);
+    assert_eq!(config.telegram.token, "from-secrets");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn include_cycles_are_rejected() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = dir("cycle");
+    write(&dir, "a.yaml", "include: [b.yaml]\n");
+    write(&dir, "b.yaml", "include: [a.yaml]\n");
+    let own = write(&dir, "own.yaml", "include: [own.yaml]\n");
+
+    let error = format!("{:#}", Config::load(&dir.join("a.yaml")).unwrap_err());
+    assert!(error.contains("Config include cycle"), "{}", error);
+    assert!(error.contains("a.yaml -> ") && error.contains("b.yaml -> "), "{}", error);
+    let error = format!("{:#}", Config::load(&own).unwrap_err());
+    assert!(error.contains("Config include cycle"), "{}", error);
+
+    // Including the same file twice, without a loop, is fine.
+    write(&dir, "shared.yaml", "ollama:\n  model: shared\n");
+    write(&dir, "left.yaml", "include: [shared.yaml]\n");
+    let diamond = write(&dir, "diamond.yaml", "include: [left.yaml, shared.yaml]\n");
+    assert_eq!(Config::load(&diamond).unwrap().ollama.model, "shared");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn relative_paths_resolve_under_data_dir() {
+    let _env = ENV.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = dir("data-dir");
+    let data = dir.join("data");
+    let elsewhere = dir.join("elsewhere.db");
+    let yaml = format!(
+        "data_dir: {}\nmemory:\n  database: {}\n  facts_file: facts/memory.md\nworkspace:\n  path: files\n",
+        data.display(),
+        elsewhere.display()
+    );
+    let path = write(&dir, "config.yaml", &yaml);
+
+    let config = Config::load(&path).unwrap();
+    assert!(data.is_dir());
+    assert_eq!(config.workspace.path, data.join("files"));
+    assert_eq!(config.memory.facts_file, data.join("facts/memory.md"));
+    assert_eq!(config.logging.directory, data.join("logs"));
+    // Absolute paths stay where they are.
+    assert_eq!(config.memory.database, elsewhere);
+
+    // RUSTYCLAW_DATA_DIR moves them all the same way.
+    let moved = dir.join("moved");
+    std::env::set_var("RUSTYCLAW_DATA_DIR", &moved);
+    let config = Config::load(&path);
+    std::env::remove_var("RUSTYCLAW_DATA_DIR");
+    assert_eq!(config.unwrap().workspace.path, moved.join("files"));
+    fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,68 @@
+//! Cron jobs fired by hand, against a fake Ollama that always gives the same
+//! reply.
+
+use axum::{routing::post, Json, Router};
+use chrono::Local;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+
+use rustyclaw::scheduler::JobOptions;
+use rustyclaw::{Config, RustyClaw};
+
+const REPLY: &str = "Today: all green.";
+
+async fn fake_chat(Json(_): Json<Value>) -> Json<Value> {
+    Json(json!({ "message": { "role": "assistant", "content": REPLY }, "done": true }))
+}
+
+#[tokio::test]
+async fn triggered_job_saves_the_reply_it_delivered() {
+    let fake = Router::new().route("/api/chat", post(fake_chat));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ollama = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, fake).await });
+
+    let dir = std::env::temp_dir().join(format!("rustyclaw-jobs-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.ollama.host = ollama;
+    config.memory.database = dir.join("test.db");
+    config.memory.facts_file = dir.join("memory.md");
+    config.workspace.path = dir.join("workspace");
+
+    let delivered = Arc::new(Mutex::new(Vec::new()));
+    let sink = delivered.clone();
+    let claw = RustyClaw::builder(config)
+        .with_warm_up(false)
+        .with_send_callback(move |msg| {
+            sink.lock().unwrap().push(msg);
+            async {}
+        })
+        .build()
+        .await
+        .unwrap();
+
+    let scheduler = claw.scheduler();
+    let options = JobOptions { output: Some("report-{date}.md".into()), ..Default::default() };
+    let report = scheduler.add_job_with("0 9 * * *", "Report", "Write today's report", options).await.unwrap();
+    let reminder = scheduler.add_job("0 9 * * *", "Stretch", "Time to stretch").await.unwrap();
+
+    assert!(scheduler.trigger(report).await.unwrap());
+    assert!(scheduler.trigger(reminder).await.unwrap());
+    assert!(!scheduler.trigger(reminder + 100).await.unwrap());
+
+    // The report job's reply is delivered and saved; the plain job just
+    // delivers its message.
+    assert_eq!(*delivered.lock().unwrap(), [REPLY, "Time to stretch"]);
+    let filename = format!("report-{}.md", Local::now().format("%Y-%m-%d"));
+    let saved = std::fs::read_to_string(claw.workspace().path().join(&filename)).unwrap();
+    assert_eq!(saved, REPLY);
+    let files = claw.workspace().list_files_with_metadata().await;
+    assert_eq!(files.len(), 1);
+
+    claw.scheduler().stop().await;
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,93 @@
+//! The history database under concurrent use.
+
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::Connection;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustyclaw::config::MemoryConfig;
+use rustyclaw::memory::Memory;
+
+/// A config for a database in a fresh temp dir named after `name`.
+fn config(name: &str) -> (MemoryConfig, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-memory-{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = MemoryConfig { database: dir.join("test.db"), pool_size: 8, ..Default::default() };
+    (config, dir)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn simultaneous_writes_all_succeed() {
+    let (config, dir) = config("concurrent");
+    let memory = Arc::new(Memory::connect(&config).await.unwrap());
+
+    let writes: Vec<_> = (0..100)
+        .map(|i| {
+            let memory = memory.clone();
+            tokio::spawn(async move { memory.add_message("user", &format!("Message {}", i), None).await })
+        })
+        .collect();
+    for write in writes {
+        write.await.unwrap().unwrap();
+    }
+
+    let history = memory.get_history(200).await.unwrap();
+    assert_eq!(history.len(), 100);
+    memory.close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
+
+/// Another process's connection to the same database, holding its write lock.
+async fn lock(config: &MemoryConfig) -> SqliteConnection {
+    let options = SqliteConnectOptions::new().filename(&config.database);
+    let mut conn = SqliteConnection::connect_with(&options).await.unwrap();
+    sqlx::query("BEGIN IMMEDIATE").execute(&mut conn).await.unwrap();
+    conn
+}
+
+#[tokio::test]
+async fn writes_wait_out_a_lock_and_report_one_that_never_clears() {
+    let (mut config, dir) = config("busy");
+    config.busy_timeout_ms = 50;
+    let memory = Memory::connect(&config).await.unwrap();
+
+    // Released while the write is retrying: it goes through.
+    let conn = lock(&config).await;
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        conn.close().await.ok();
+    });
+    memory.add_message("user", "Waited", None).await.unwrap();
+    release.await.unwrap();
+
+    // Held throughout: the write gives up with a message fit for the user.
+    let mut conn = lock(&config).await;
+    let error = memory.add_message("user", "Gave up", None).await.unwrap_err();
+    assert!(error.is_busy(), "{}", error);
+    assert_eq!(error.to_string(), "The database is busy right now, please try again in a moment.");
+    sqlx::query("ROLLBACK").execute(&mut conn).await.unwrap();
+
+    let history: Vec<String> = memory.get_history(10).await.unwrap().into_iter().map(|m| m.content).collect();
+    assert_eq!(history, ["Waited"]);
+    memory.close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
+
+#[tokio::test]
+async fn stored_messages_come_back_with_their_timestamp() {
+    let (config, dir) = config("timestamp");
+    let memory = Memory::connect(&config).await.unwrap();
+    memory.add_message("user", "What time is it?", None).await.unwrap();
+    memory.add_message("assistant", "Time to check.", Some("llama3.2")).await.unwrap();
+
+    // Filled in by the column's CURRENT_TIMESTAMP default, in UTC.
+    for message in memory.get_history(10).await.unwrap() {
+        assert!(!message.timestamp.is_empty(), "{:?}", message);
+        let when = message.local_time().expect("timestamp in YYYY-MM-DD HH:MM:SS");
+        assert!((chrono::Local::now() - when).num_minutes().abs() < 5, "{}", message.timestamp);
+    }
+    memory.close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,39 @@
+//! The Telegram bot's allowlist, as a config reload changes it.
+
+use rustyclaw::telegram::TelegramBot;
+use rustyclaw::{Config, RustyClaw};
+
+#[tokio::test]
+async fn reloading_the_allowlist_changes_who_is_allowed() {
+    let dir = std::env::temp_dir().join(format!("rustyclaw-telegram-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.memory.database = dir.join("test.db");
+    config.memory.facts_file = dir.join("memory.md");
+    config.workspace.path = dir.join("workspace");
+    config.telegram.allowed_users = vec![111];
+
+    let claw = RustyClaw::builder(config.clone()).with_warm_up(false).build().await.unwrap();
+    let bot = TelegramBot::new(
+        config,
+        claw.agent().clone(),
+        claw.memory().clone(),
+        claw.scheduler().clone(),
+        claw.workspace().clone(),
+    );
+    assert!(bot.is_user_allowed(111).await);
+    assert!(!bot.is_user_allowed(222).await);
+
+    // What the SIGHUP handler does with the reloaded config.
+    bot.set_allowed_users(vec![222]).await;
+    assert!(!bot.is_user_allowed(111).await);
+    assert!(bot.is_user_allowed(222).await);
+
+    // An empty list lets everyone in.
+    bot.set_allowed_users(Vec::new()).await;
+    assert!(bot.is_user_allowed(111).await && bot.is_user_allowed(333).await);
+
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,64 @@
+//! `/undo` reversing each kind of action, against a fake Ollama whose reply
+//! schedules a job, saves a file and remembers a fact.
+
+use axum::{body::Body, routing::post, Json, Router};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use rustyclaw::{Config, RustyClaw};
+
+const REPLY: &str = "Done.
+```cron
+{\"schedule\": \"0 9 * * *\", \"task\": \"Stretch\", \"message\": \"Time to stretch\"}
+```
+```save:notes.md
+# Notes
+```
+```memory
+Likes tea
+```";
+
+async fn fake_chat(Json(request): Json<Value>) -> Body {
+    let message = json!({ "role": "assistant", "content": REPLY });
+    if request["stream"] == json!(true) {
+        Body::from(format!("{}\n{}\n", json!({ "message": message, "done": false }), json!({ "done": true })))
+    } else {
+        Body::from(json!({ "message": message, "done": true }).to_string())
+    }
+}
+
+#[tokio::test]
+async fn undo_reverses_each_action_of_the_last_reply() {
+    let fake = Router::new().route("/api/chat", post(fake_chat));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let ollama = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, fake).await });
+
+    let dir = std::env::temp_dir().join(format!("rustyclaw-undo-{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut config = Config::default();
+    config.ollama.host = ollama;
+    config.memory.database = dir.join("test.db");
+    config.memory.facts_file = dir.join("memory.md");
+    config.workspace.path = dir.join("workspace");
+    let claw = RustyClaw::builder(config).with_warm_up(false).build().await.unwrap();
+    let facts = || std::fs::read_to_string(dir.join("memory.md")).unwrap_or_default();
+
+    let reply = claw.respond("Remind me to stretch, jot down notes, and remember I like tea").await;
+    assert_eq!(reply.actions.len(), 3, "{:?}", reply.actions);
+    assert_eq!(claw.scheduler().list_jobs().await.unwrap().len(), 1);
+    assert!(claw.workspace().path().join("notes.md").exists());
+    assert!(facts().contains("Likes tea"));
+
+    let undone = claw.context().undo().await;
+    assert_eq!(undone.lines().count(), 3, "{}", undone);
+    assert!(claw.scheduler().list_jobs().await.unwrap().is_empty());
+    assert_eq!(claw.scheduler().running_jobs().await, 0);
+    assert!(!claw.workspace().path().join("notes.md").exists());
+    assert!(!facts().contains("Likes tea"));
+
+    assert_eq!(claw.context().undo().await, "Nothing to undo.");
+    claw.memory().close().await;
+    std::fs::remove_dir_all(dir).ok();
+}